@@ -0,0 +1,352 @@
+//! Named actions for bar clicks (`redweather action <name>`)
+//!
+//! Waybar, polybar, i3blocks and the rest bind their clicks to
+//! `redweather action <name>`, so what a click does is set in one place.
+//! `[actions.<name>]` in config.toml adds actions or replaces built-in ones:
+//!
+//! ```toml
+//! [actions.radar]
+//! type = "url"
+//! url = "https://www.rainviewer.com/map.html?loc={lat},{lon},8"
+//!
+//! [actions.notify]
+//! type = "command"
+//! command = "notify-send 'Weather' {location}"
+//! ```
+
+use std::collections::BTreeMap;
+use std::process::Command;
+use tracing::error;
+
+use crate::cli::{Cli, PresetsCommand, EXIT_FAILURE, EXIT_OK, EXIT_USAGE};
+use crate::config::{load_key, try_update_config, ActionConfig, Config, Units};
+use crate::context::{fetch_failure, load_context};
+use crate::ipc::{self, Request, Response};
+use crate::presets::run_presets_command;
+use crate::template::{self, Value, Values};
+use crate::waybar::reload_waybar;
+use crate::weather::{load_weather, load_weather_all, ResolvedLocation};
+
+/// Placeholders in `url` and `command` templates
+pub const ACTION_FIELDS: &[&str] = &["lat", "lon", "location", "preset", "units"];
+
+/// Actions available without any config
+pub fn builtin_actions() -> BTreeMap<String, ActionConfig> {
+    let url = |url: &str| ActionConfig::Url { url: url.into() };
+    let preset = |preset: &str| ActionConfig::Preset {
+        preset: preset.into(),
+    };
+    [
+        ("dashboard", ActionConfig::Dashboard),
+        ("settings", ActionConfig::Settings),
+        ("refresh", ActionConfig::Refresh),
+        ("units", ActionConfig::Units),
+        ("next", preset("next")),
+        ("prev", preset("prev")),
+        (
+            "web",
+            url("https://openweathermap.org/weathermap?basemap=map&cities=true&layer=temperature&lat={lat}&lon={lon}&zoom=10"),
+        ),
+        ("windy", url("https://www.windy.com/?{lat},{lon},10")),
+        (
+            "weather-gov",
+            url("https://forecast.weather.gov/MapClick.php?lat={lat}&lon={lon}"),
+        ),
+        ("ventusky", url("https://www.ventusky.com/?p={lat};{lon};10")),
+    ]
+    .into_iter()
+    .map(|(name, action)| (name.to_string(), action))
+    .collect()
+}
+
+/// Built-in actions with the config's added and replaced
+pub fn all_actions(cfg: &Config) -> BTreeMap<String, ActionConfig> {
+    let mut actions = builtin_actions();
+    if let Some(configured) = &cfg.actions {
+        actions.extend(configured.clone());
+    }
+    actions
+}
+
+/// Runs an action other than `dashboard` and `settings`, which main opens
+/// itself, and returns the process exit code
+pub async fn run_action(cli: &Cli, name: &str, action: &ActionConfig) -> i32 {
+    let result = match action {
+        ActionConfig::Url { url } => {
+            return match render_for_location(cli, url, url_encode).await {
+                Ok(url) => open_url(&url),
+                Err(code) => code,
+            }
+        }
+        ActionConfig::Command { command } => {
+            return match render_for_location(cli, command, shell_quote).await {
+                Ok(command) => run_command(&command),
+                Err(code) => code,
+            }
+        }
+        ActionConfig::Preset { preset } => {
+            let command = match preset.as_str() {
+                "next" => PresetsCommand::Next,
+                "prev" => PresetsCommand::Prev,
+                name => PresetsCommand::Activate { name: name.into() },
+            };
+            return run_presets_command(&command).await;
+        }
+        ActionConfig::Units => toggle_units(),
+        ActionConfig::Refresh => return refresh(cli).await,
+        ActionConfig::Dashboard | ActionConfig::Settings => {
+            error!("Action \"{}\" must be opened by the caller", name);
+            return EXIT_FAILURE;
+        }
+    };
+
+    match result {
+        Ok(msg) => {
+            println!("{}", msg);
+            reload_waybar();
+            EXIT_OK
+        }
+        Err(e) => {
+            error!("{}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Prints every action, marking the ones set in config.toml with `*`
+pub fn list_actions(cfg: &Config) {
+    let configured = cfg.actions.as_ref();
+    let actions = all_actions(cfg);
+    let width = actions.keys().map(|n| n.len()).max().unwrap_or(0);
+    for (name, action) in &actions {
+        let marker = if configured.is_some_and(|c| c.contains_key(name)) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{} {:<width$}  {}", marker, name, describe(action));
+    }
+}
+
+/// Reports an action name that is neither configured nor built in
+pub fn unknown_action(name: &str) -> i32 {
+    error!(
+        "No action named \"{}\"; run `redweather action` to list them",
+        name
+    );
+    EXIT_USAGE
+}
+
+fn describe(action: &ActionConfig) -> String {
+    match action {
+        ActionConfig::Url { url } => format!("open {}", url),
+        ActionConfig::Command { command } => format!("run {}", command),
+        ActionConfig::Preset { preset } => match preset.as_str() {
+            "next" => "switch to the next preset".into(),
+            "prev" => "switch to the previous preset".into(),
+            name => format!("switch to preset \"{}\"", name),
+        },
+        ActionConfig::Units => "switch between imperial and metric".into(),
+        ActionConfig::Dashboard => "open the dashboard".into(),
+        ActionConfig::Settings => "open the location and settings window".into(),
+        ActionConfig::Refresh => "fetch fresh weather and update the bar".into(),
+    }
+}
+
+/// Renders a template for the location the bar shows
+///
+/// Resolving a preset needs no API key, so actions work before one is set up.
+/// On failure, returns the exit code.
+async fn render_for_location(
+    cli: &Cli,
+    src: &str,
+    escape: fn(&str) -> String,
+) -> Result<String, i32> {
+    let nodes = template::parse(src).map_err(|e| {
+        error!("Bad action template \"{}\": {}", src, e);
+        EXIT_FAILURE
+    })?;
+    let key = load_key().unwrap_or_default();
+    // Failures are logged as they are made
+    let ctx = load_context(&key, &cli.global, cli.location())
+        .await
+        .map_err(|f| f.code)?;
+    let preset = ctx.cfg.active_preset.clone().unwrap_or_default();
+    Ok(template::render(
+        &nodes,
+        &action_values(&ctx.resolved, &preset, escape),
+    ))
+}
+
+/// Placeholder values, escaped for where they end up
+fn action_values(resolved: &ResolvedLocation, preset: &str, escape: fn(&str) -> String) -> Values {
+    let loc = &resolved.location;
+    let value = |s: &str| Value::plain(escape(s));
+    Values::from([
+        ("lat", value(&format!("{:.4}", loc.lat))),
+        ("lon", value(&format!("{:.4}", loc.lon))),
+        ("location", value(&loc.label)),
+        ("preset", value(preset)),
+        ("units", value(resolved.settings.units.as_str())),
+    ])
+}
+
+/// Percent-encodes everything but unreserved URL characters
+fn url_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// Quotes a value for `sh`, so place names cannot run commands
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn open_url(url: &str) -> i32 {
+    match Command::new("xdg-open").arg(url).spawn() {
+        Ok(_) => EXIT_OK,
+        Err(e) => {
+            error!("Failed to open {}: {}", url, e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Starts a command without waiting, like Waybar's `on-click`
+fn run_command(command: &str) -> i32 {
+    match Command::new("sh").args(["-c", command]).spawn() {
+        Ok(_) => EXIT_OK,
+        Err(e) => {
+            error!("Failed to run {}: {}", command, e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Switches the active preset's own units if it has them, else the global ones
+fn toggle_units() -> anyhow::Result<String> {
+    try_update_config(|cfg| {
+        let toggle = |u: Units| match u {
+            Units::Imperial => Units::Metric,
+            Units::Metric => Units::Imperial,
+        };
+        let index = cfg.active_preset_index();
+        let preset = index.and_then(|i| {
+            cfg.location_presets
+                .as_mut()
+                .and_then(|p| p.get_mut(i))
+                .filter(|p| p.units.is_some())
+        });
+        let units = match preset {
+            Some(p) => {
+                let units = toggle(p.units.unwrap_or_default());
+                p.units = Some(units);
+                units
+            }
+            None => {
+                cfg.units = toggle(cfg.units);
+                cfg.units
+            }
+        };
+        Ok(format!("Units: {}", units.as_str()))
+    })
+}
+
+/// Asks a running `redweather serve` to refresh, or fetches here and
+/// signals the bar to pick up the new cache
+async fn refresh(cli: &Cli) -> i32 {
+    if ipc::can_attach(cli) {
+        match ipc::request(&Request::Refresh) {
+            Ok(Response::Error { message }) => {
+                error!("redweather serve: {}", message);
+                return EXIT_FAILURE;
+            }
+            Ok(_) => return EXIT_OK,
+            // No server running
+            Err(_) => {}
+        }
+    }
+    let key = load_key().unwrap_or_default();
+    let ctx = match load_context(&key, &cli.global, cli.location()).await {
+        Ok(c) => c,
+        Err(f) => return f.code,
+    };
+    let failed = if ctx.bar_locations.is_empty() {
+        load_weather(&ctx.resolved, true).await.err()
+    } else {
+        let results = load_weather_all(&ctx.bar_locations, true).await;
+        results.into_iter().find_map(Result::err)
+    };
+    if let Some(e) = failed {
+        return fetch_failure(&e).code;
+    }
+    reload_waybar();
+    EXIT_OK
+}
+
+/// The preset a preset action switches to, unless it cycles
+pub fn preset_action_target(action: &ActionConfig) -> Option<&str> {
+    match action {
+        ActionConfig::Preset { preset } if preset != "next" && preset != "prev" => Some(preset),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escaping() {
+        assert_eq!(url_encode("São Paulo, BR"), "S%C3%A3o%20Paulo%2C%20BR");
+        assert_eq!(url_encode("-40.7128"), "-40.7128");
+        assert_eq!(shell_quote("it's; rm -rf ~"), r"'it'\''s; rm -rf ~'");
+    }
+
+    #[test]
+    fn test_config_replaces_builtins() {
+        let cfg = Config {
+            actions: Some(BTreeMap::from([
+                (
+                    "web".to_string(),
+                    ActionConfig::Url {
+                        url: "https://example.com/{lat}".into(),
+                    },
+                ),
+                (
+                    "cabin".to_string(),
+                    ActionConfig::Preset {
+                        preset: "cabin".into(),
+                    },
+                ),
+            ])),
+            ..Config::default()
+        };
+        let actions = all_actions(&cfg);
+        assert_eq!(
+            actions["web"],
+            ActionConfig::Url {
+                url: "https://example.com/{lat}".into()
+            }
+        );
+        assert_eq!(actions["windy"], builtin_actions()["windy"]);
+        assert_eq!(preset_action_target(&actions["cabin"]), Some("cabin"));
+        assert_eq!(preset_action_target(&actions["next"]), None);
+        for action in builtin_actions().values() {
+            if let ActionConfig::Url { url } = action {
+                let nodes = template::parse(url).unwrap();
+                assert!(template::names(&nodes)
+                    .iter()
+                    .all(|n| ACTION_FIELDS.contains(n)));
+            }
+        }
+    }
+}
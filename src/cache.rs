@@ -0,0 +1,290 @@
+//! Cache inspection and cleanup (`redweather cache ...`)
+//!
+//! Each location's weather is cached in its own file per unit system, named
+//! after its coordinates. `list` and `show` match the files to presets;
+//! `prune` deletes the ones no preset uses any more and the ones too old to
+//! be shown, which the bar would fetch again anyway.
+
+use chrono::{Local, TimeZone, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+use crate::cli::{CacheCommand, EXIT_FAILURE, EXIT_OK, EXIT_USAGE};
+use crate::config::{load_config, CacheConfigResolved, Config, LocationPreset, Units};
+use crate::paths;
+use crate::utils::format_age;
+use crate::weather::{cache_entries, cache_key, cache_path, clear_cache, CacheEntry, Location};
+
+/// Runs `redweather cache <subcommand>` and returns the process exit code
+pub fn run_cache_command(action: &CacheCommand) -> i32 {
+    let result = match action {
+        CacheCommand::Path => match paths::cache_dir() {
+            Some(dir) => {
+                println!("{}", dir.display());
+                return EXIT_OK;
+            }
+            None => {
+                error!("Cannot determine the cache directory (is $HOME set?)");
+                return EXIT_FAILURE;
+            }
+        },
+        CacheCommand::List => list_cache(&load_config()),
+        CacheCommand::Show { preset } => return show_cache(&load_config(), preset.as_deref()),
+        CacheCommand::Clear => clear_cache().map(|n| println!("Removed {} cached file(s)", n)),
+        CacheCommand::Prune => prune_cache(&load_config()),
+    };
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(e) => {
+            error!("{}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// The preset a cache file belongs to, and the units it was fetched in
+struct Owner<'a> {
+    preset: &'a LocationPreset,
+    units: Units,
+}
+
+/// Where each preset's cache files would be, in either unit system
+fn owners(cfg: &Config) -> Vec<(PathBuf, Owner<'_>)> {
+    let mut owners = Vec::new();
+    for preset in cfg.location_presets.iter().flatten() {
+        let loc = Location::new(preset.lat, preset.lon, preset.label.clone());
+        for units in [Units::Imperial, Units::Metric] {
+            owners.push((cache_path(&cache_key(&loc, units)), Owner { preset, units }));
+        }
+    }
+    owners
+}
+
+fn owner_of<'a, 'b>(owners: &'a [(PathBuf, Owner<'b>)], path: &Path) -> Option<&'a Owner<'b>> {
+    owners.iter().find(|(p, _)| p == path).map(|(_, o)| o)
+}
+
+/// How a cache file is used under the configured policy
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    /// Used without fetching
+    Fresh,
+    /// Fetched again, but shown while that fails
+    Stale,
+    /// Too old to show
+    Expired,
+    Unreadable,
+}
+
+impl Status {
+    fn of(entry: &CacheEntry, policy: &CacheConfigResolved, now: i64) -> Self {
+        match entry.fetched_at {
+            None => Status::Unreadable,
+            Some(t) if now - t <= policy.ttl_secs => Status::Fresh,
+            Some(t) if now - t <= policy.max_stale_secs => Status::Stale,
+            Some(_) => Status::Expired,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Status::Fresh => "fresh",
+            Status::Stale => "stale",
+            Status::Expired => "expired",
+            Status::Unreadable => "unreadable",
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// A byte count in B, KiB or MiB
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{} B", b),
+        b if b < 1024 * 1024 => format!("{:.1} KiB", b as f64 / 1024.0),
+        b => format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+/// Prints one line per cache file: preset, units, age, status, size and provider
+fn list_cache(cfg: &Config) -> anyhow::Result<()> {
+    let entries = cache_entries()?;
+    if entries.is_empty() {
+        println!("Nothing cached");
+        return Ok(());
+    }
+    let owners = owners(cfg);
+    let policy = CacheConfigResolved::from_config(&cfg.cache);
+    let now = Utc::now().timestamp();
+    let rows: Vec<[String; 7]> = entries
+        .iter()
+        .map(|e| {
+            let owner = owner_of(&owners, &e.path);
+            [
+                owner.map_or_else(|| "-".into(), |o| o.preset.name.clone()),
+                owner.map_or("-", |o| o.units.as_str()).into(),
+                e.fetched_at
+                    .map_or_else(|| "-".into(), |t| format_age(now - t)),
+                Status::of(e, &policy, now).as_str().into(),
+                format_size(e.size),
+                e.provider.clone().unwrap_or_else(|| "-".into()),
+                file_name(&e.path),
+            ]
+        })
+        .collect();
+    let header = [
+        "PRESET", "UNITS", "AGE", "STATUS", "SIZE", "PROVIDER", "FILE",
+    ]
+    .map(String::from);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| format!("{:<w$}", cell, w = w))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+    Ok(())
+}
+
+/// Prints the details of a preset's cache files (the active preset by default)
+fn show_cache(cfg: &Config, name: Option<&str>) -> i32 {
+    let presets = cfg.location_presets.as_deref().unwrap_or(&[]);
+    let preset = match name {
+        Some(name) => presets.iter().find(|p| p.name == name),
+        None => cfg.active_preset_index().and_then(|i| presets.get(i)),
+    };
+    let Some(preset) = preset else {
+        match name {
+            Some(name) => error!(
+                "Unknown preset \"{}\" (see `redweather presets list`)",
+                name
+            ),
+            None => {
+                error!("No presets configured; name one or add one with `redweather presets add`")
+            }
+        }
+        return EXIT_USAGE;
+    };
+    let entries = match cache_entries() {
+        Ok(e) => e,
+        Err(e) => {
+            error!("{}", e);
+            return EXIT_FAILURE;
+        }
+    };
+    let owners = owners(cfg);
+    let policy = CacheConfigResolved::from_config(&cfg.cache);
+    let now = Utc::now().timestamp();
+    let mine: Vec<(&CacheEntry, &Owner)> = entries
+        .iter()
+        .filter_map(|e| owner_of(&owners, &e.path).map(|o| (e, o)))
+        .filter(|(_, o)| o.preset.name == preset.name)
+        .collect();
+    println!("{} ({})", preset.name, preset.label);
+    if mine.is_empty() {
+        println!("  Nothing cached");
+    }
+    for (entry, owner) in mine {
+        println!();
+        println!("  File:      {}", entry.path.display());
+        println!("  Units:     {}", owner.units.as_str());
+        println!(
+            "  Provider:  {}",
+            entry.provider.as_deref().unwrap_or("unknown")
+        );
+        println!("  Size:      {}", format_size(entry.size));
+        if let Some(t) = entry.fetched_at {
+            let when = Local
+                .timestamp_opt(t, 0)
+                .single()
+                .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            println!("  Fetched:   {}", when);
+            println!("  Age:       {}", format_age(now - t));
+        }
+        let detail = match Status::of(entry, &policy, now) {
+            Status::Fresh => format!(
+                "fresh; used until it is {} old",
+                format_age(policy.ttl_secs)
+            ),
+            Status::Stale => {
+                "stale; fetched again on the next update, shown while that fails".into()
+            }
+            Status::Expired => "expired; too old to show (see cache.max_stale_hours)".into(),
+            Status::Unreadable => "unreadable; fetched again on the next update".into(),
+        };
+        println!("  Status:    {}", detail);
+    }
+    EXIT_OK
+}
+
+/// Deletes cache files no preset uses, and any too old to be shown
+fn prune_cache(cfg: &Config) -> anyhow::Result<()> {
+    let owners = owners(cfg);
+    let policy = CacheConfigResolved::from_config(&cfg.cache);
+    let now = Utc::now().timestamp();
+    let mut removed = 0;
+    let mut freed = 0;
+    for entry in cache_entries()? {
+        let reason = if owner_of(&owners, &entry.path).is_none() {
+            "no preset uses it"
+        } else {
+            match Status::of(&entry, &policy, now) {
+                Status::Expired => "too old to show",
+                Status::Unreadable => "unreadable",
+                Status::Fresh | Status::Stale => continue,
+            }
+        };
+        fs::remove_file(&entry.path)
+            .map_err(|e| anyhow::anyhow!("Failed to remove {}: {}", entry.path.display(), e))?;
+        println!("Removed {} ({})", file_name(&entry.path), reason);
+        removed += 1;
+        freed += entry.size;
+    }
+    println!("Removed {} cached file(s), {}", removed, format_size(freed));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(fetched_at: Option<i64>) -> CacheEntry {
+        CacheEntry {
+            path: PathBuf::from("cache_x.json"),
+            size: 0,
+            fetched_at,
+            provider: None,
+        }
+    }
+
+    #[test]
+    fn test_status_and_size() {
+        let policy = CacheConfigResolved {
+            ttl_secs: 600,
+            max_stale_secs: 3600,
+        };
+        assert_eq!(Status::of(&entry(Some(1000)), &policy, 1500), Status::Fresh);
+        assert_eq!(Status::of(&entry(Some(1000)), &policy, 2000), Status::Stale);
+        assert_eq!(
+            Status::of(&entry(Some(1000)), &policy, 5000),
+            Status::Expired
+        );
+        assert_eq!(Status::of(&entry(None), &policy, 5000), Status::Unreadable);
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(15_462), "15.1 KiB");
+    }
+}
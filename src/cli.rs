@@ -0,0 +1,393 @@
+//! Command-line interface
+//!
+//! `redweather` with no subcommand prints Waybar JSON, same as `redweather bar`.
+//! The flags used by older Waybar configs (`--prompt`, `--dashboard`,
+//! `--open-web`, `--reload` and a bare ZIP/city argument) are still accepted and
+//! mapped onto the subcommands.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+use crate::config::Units;
+use crate::daemon::MIN_INTERVAL_SECS;
+use crate::output::OutputFormat;
+use crate::report::ChartStyle;
+
+/// Success
+pub const EXIT_OK: i32 = 0;
+/// Runtime failure: missing API key, network or location errors, invalid config
+pub const EXIT_FAILURE: i32 = 1;
+/// Bad usage: unknown arguments, unknown preset names (clap also uses 2)
+pub const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "redweather",
+    version,
+    about = "OpenWeatherMap weather for Waybar",
+    after_help = "Without a subcommand, prints Waybar JSON (same as `redweather bar`)."
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    /// Legacy alias for `settings`
+    #[arg(long, hide = true)]
+    pub prompt: bool,
+
+    /// Legacy alias for `dashboard`
+    #[arg(long = "dashboard", hide = true)]
+    pub dashboard_flag: bool,
+
+    /// Legacy alias for `web`
+    #[arg(long, hide = true)]
+    pub open_web: bool,
+
+    /// Legacy positional location, same as --location
+    #[arg(value_name = "LOCATION", hide = true)]
+    pub legacy_location: Option<String>,
+}
+
+/// Options accepted by every subcommand
+#[derive(Debug, Clone, Args)]
+pub struct GlobalArgs {
+    /// Show this ZIP code or city instead of the active preset
+    #[arg(short, long, global = true, value_name = "ZIP|CITY")]
+    pub location: Option<String>,
+
+    /// Use this preset instead of the active one
+    #[arg(short, long, global = true, value_name = "NAME")]
+    pub preset: Option<String>,
+
+    /// Unit system, overriding the config and presets
+    #[arg(short, long, global = true, value_enum)]
+    pub units: Option<UnitsArg>,
+
+    /// Use an alternate config file
+    #[arg(short, long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Override a config key for this run, e.g. --set ui.font_size=11 (repeatable)
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    pub set: Vec<String>,
+
+    /// Skip the cache and fetch fresh data
+    #[arg(short, long, global = true)]
+    pub reload: bool,
+
+    /// Output format for `bar` and `daemon`
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        value_name = "FORMAT"
+    )]
+    pub output: OutputFormat,
+
+    /// Log more on stderr: -v fetches and cache hits, -vv debug, -vvv everything
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Log only errors on stderr
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+}
+
+/// `--units` values
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum UnitsArg {
+    Imperial,
+    Metric,
+}
+
+impl From<UnitsArg> for Units {
+    fn from(u: UnitsArg) -> Self {
+        match u {
+            UnitsArg::Imperial => Units::Imperial,
+            UnitsArg::Metric => Units::Metric,
+        }
+    }
+}
+
+/// The subcommands
+#[derive(Debug, Clone, PartialEq, Subcommand)]
+pub enum Command {
+    /// Print weather as Waybar JSON (the default)
+    Bar,
+    /// Keep running and print a Waybar JSON line on every update
+    Daemon(DaemonArgs),
+    /// Run the shared weather service that the bar, dashboard and scripts attach to
+    Serve(DaemonArgs),
+    /// Open the GTK dashboard
+    Dashboard,
+    /// Open the location and settings window
+    Settings,
+    /// Manage location presets
+    Presets {
+        #[command(subcommand)]
+        action: Option<PresetsCommand>,
+    },
+    /// Inspect and clean up the weather cache
+    Cache {
+        #[command(subcommand)]
+        action: Option<CacheCommand>,
+    },
+    /// Print the forecast in the terminal
+    Report(ReportArgs),
+    /// Print the forecast as versioned JSON for scripts (see README)
+    Json {
+        /// Indent the output
+        #[arg(long)]
+        pretty: bool,
+    },
+    /// Open the forecast for the current location in a web browser (the `web` action)
+    Web,
+    /// Run a named action from `[actions]` or a built-in one; lists them without a name
+    Action {
+        /// Action to run, e.g. dashboard, refresh, next, windy
+        name: Option<String>,
+    },
+    /// Inspect the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Check the API key, config, cache, display and Waybar setup
+    Doctor,
+}
+
+/// Options for `redweather daemon` and `redweather serve`
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct DaemonArgs {
+    /// Seconds between refreshes
+    #[arg(long, default_value_t = 600, value_name = "SECS",
+          value_parser = clap::value_parser!(u64).range(MIN_INTERVAL_SECS..))]
+    pub interval: u64,
+}
+
+/// Options for `redweather report`
+#[derive(Debug, Clone, PartialEq, Args)]
+pub struct ReportArgs {
+    /// Hours in the hourly table
+    #[arg(long, default_value_t = 24, value_parser = clap::value_parser!(u8).range(12..=48))]
+    pub hours: u8,
+
+    /// Days in the daily table
+    #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u8).range(0..=8))]
+    pub days: u8,
+
+    /// Draw temperature and precipitation charts
+    #[arg(long, value_enum, value_name = "STYLE")]
+    pub chart: Option<ChartStyle>,
+
+    /// When to use color (auto: only on a terminal and without NO_COLOR)
+    #[arg(long, value_enum, default_value_t = ColorWhen::Auto, value_name = "WHEN")]
+    pub color: ColorWhen,
+
+    /// Table width in columns (default: the terminal width)
+    #[arg(long, value_name = "COLUMNS")]
+    pub width: Option<usize>,
+}
+
+/// `report --color` values
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ColorWhen {
+    Auto,
+    Always,
+    Never,
+}
+
+/// `redweather presets` subcommands
+#[derive(Debug, Clone, PartialEq, Subcommand)]
+pub enum PresetsCommand {
+    /// List presets, marking the active one (the default)
+    List,
+    /// Geocode a ZIP code or place and save it as a preset (and make it active)
+    Add {
+        /// Preset name
+        name: String,
+        /// ZIP code or place name, e.g. 10001 or "Berlin, DE"
+        #[arg(required = true, num_args = 1..)]
+        query: Vec<String>,
+        /// Display label (defaults to the geocoded name)
+        #[arg(long)]
+        label: Option<String>,
+    },
+    /// Remove a preset
+    #[command(alias = "rm")]
+    Remove { name: String },
+    /// Rename a preset
+    #[command(alias = "mv")]
+    Rename { old: String, new: String },
+    /// Make a preset the active one
+    Activate { name: String },
+    /// Switch to the next preset and refresh Waybar
+    Next,
+    /// Switch to the previous preset and refresh Waybar
+    Prev,
+}
+
+/// `redweather cache` subcommands
+#[derive(Debug, Clone, PartialEq, Subcommand)]
+pub enum CacheCommand {
+    /// List cached locations with their age, size and provider (the default)
+    #[command(alias = "ls")]
+    List,
+    /// Show the details of a preset's cached weather
+    Show {
+        /// Preset name (default: the active preset)
+        preset: Option<String>,
+    },
+    /// Print the cache directory
+    Path,
+    /// Delete all cached weather data
+    Clear,
+    /// Delete cached weather no preset uses, and any too old to show
+    Prune,
+}
+
+/// `redweather config` subcommands
+#[derive(Debug, Clone, PartialEq, Subcommand)]
+pub enum ConfigCommand {
+    /// Validate config.toml and report problems with line numbers
+    Check,
+    /// Print the config file path
+    Path,
+}
+
+impl Cli {
+    /// Returns the command to run, translating the legacy flags
+    pub fn effective_command(&self) -> Command {
+        if let Some(command) = &self.command {
+            return command.clone();
+        }
+        if self.prompt {
+            Command::Settings
+        } else if self.dashboard_flag {
+            Command::Dashboard
+        } else if self.open_web {
+            Command::Web
+        } else {
+            Command::Bar
+        }
+    }
+
+    /// Returns the one-off location query from --location or the legacy argument
+    pub fn location(&self) -> Option<&str> {
+        self.global
+            .location
+            .as_deref()
+            .or(self.legacy_location.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("redweather").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_legacy_flags() {
+        assert_eq!(parse(&[]).effective_command(), Command::Bar);
+        assert_eq!(parse(&["--prompt"]).effective_command(), Command::Settings);
+        assert_eq!(
+            parse(&["--dashboard"]).effective_command(),
+            Command::Dashboard
+        );
+        assert_eq!(parse(&["--open-web"]).effective_command(), Command::Web);
+
+        let cli = parse(&["--reload", "10001"]);
+        assert_eq!(cli.effective_command(), Command::Bar);
+        assert!(cli.global.reload);
+        assert_eq!(cli.location(), Some("10001"));
+    }
+
+    #[test]
+    fn test_subcommands_and_global_options() {
+        let cli = parse(&["report", "--units", "metric", "-p", "berlin"]);
+        assert_eq!(
+            cli.effective_command(),
+            Command::Report(ReportArgs {
+                hours: 24,
+                days: 7,
+                chart: None,
+                color: ColorWhen::Auto,
+                width: None,
+            })
+        );
+        assert_eq!(cli.global.units, Some(UnitsArg::Metric));
+        assert_eq!(cli.global.preset.as_deref(), Some("berlin"));
+
+        let cli = parse(&["daemon", "-vv"]);
+        assert_eq!(cli.global.verbose, 2);
+        assert!(Cli::try_parse_from(["redweather", "-v", "-q"]).is_err());
+
+        let cli = parse(&["bar", "--set", "units=metric", "--set=ui.font_size=11"]);
+        assert_eq!(cli.global.set, vec!["units=metric", "ui.font_size=11"]);
+
+        assert_eq!(
+            parse(&["action", "windy"]).effective_command(),
+            Command::Action {
+                name: Some("windy".into())
+            }
+        );
+        assert_eq!(
+            parse(&["presets"]).effective_command(),
+            Command::Presets { action: None }
+        );
+        assert_eq!(
+            parse(&["presets", "add", "berlin", "Berlin,", "DE"]).effective_command(),
+            Command::Presets {
+                action: Some(PresetsCommand::Add {
+                    name: "berlin".into(),
+                    query: vec!["Berlin,".into(), "DE".into()],
+                    label: None,
+                })
+            }
+        );
+        let cli = parse(&[
+            "report",
+            "--hours",
+            "48",
+            "--chart",
+            "braille",
+            "--color=never",
+        ]);
+        let Command::Report(args) = cli.effective_command() else {
+            panic!("expected report");
+        };
+        assert_eq!(args.hours, 48);
+        assert_eq!(args.chart, Some(ChartStyle::Braille));
+        assert_eq!(args.color, ColorWhen::Never);
+        assert!(Cli::try_parse_from(["redweather", "report", "--hours", "6"]).is_err());
+        assert_eq!(
+            parse(&["daemon"]).effective_command(),
+            Command::Daemon(DaemonArgs { interval: 600 })
+        );
+        assert!(Cli::try_parse_from(["redweather", "daemon", "--interval", "10"]).is_err());
+        assert_eq!(parse(&[]).global.output, OutputFormat::Waybar);
+        assert_eq!(
+            parse(&["daemon", "--output", "polybar"]).global.output,
+            OutputFormat::Polybar
+        );
+        assert_eq!(
+            parse(&["json", "--pretty"]).effective_command(),
+            Command::Json { pretty: true }
+        );
+
+        assert!(Cli::try_parse_from(["redweather", "--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! What each `redweather` command does
+//!
+//! `main.rs` only calls `main_entry`, which parses the command line and hands
+//! it to [`run`].
+
+use tracing::{error, warn};
+
+use crate::cli::{
+    CacheCommand, Cli, ColorWhen, Command, ConfigCommand, PresetsCommand, EXIT_FAILURE, EXIT_OK,
+};
+use crate::config::{self, config_path, load_config, ActionConfig};
+use crate::context::{fetch_failure, load_context, require_key, Failure, WeatherContext};
+use crate::output::{self, OutputFormat};
+use crate::validate::{check_config_file, Severity};
+use crate::weather::{load_weather, load_weather_all};
+use crate::{
+    actions, cache, daemon, doctor, export, ipc, logging, overrides, paths, presets, report, serve,
+    waybar,
+};
+
+/// Runs a parsed command line and returns the process exit code
+///
+/// Sets up this process first: the `--config` path, `--set` overrides,
+/// logging and the copy from legacy directories.
+pub async fn run(cli: Cli) -> i32 {
+    if let Some(path) = &cli.global.config {
+        paths::set_config_override(path.clone());
+    }
+    overrides::set_cli_overrides(cli.global.set.clone());
+    logging::init(
+        cli.global.verbose,
+        cli.global.quiet,
+        &config::load_log_config(),
+    );
+    paths::migrate_legacy_dirs();
+
+    // Config, cache and preset commands need neither an API key nor the network
+    match cli.effective_command() {
+        Command::Config { action } => run_config_command(&action),
+        Command::Cache { action } => {
+            cache::run_cache_command(action.as_ref().unwrap_or(&CacheCommand::List))
+        }
+        Command::Presets { action } => {
+            presets::run_presets_command(action.as_ref().unwrap_or(&PresetsCommand::List)).await
+        }
+        // These load the key and config themselves so they can retry and reload them
+        Command::Daemon(args) => daemon::run_daemon(&cli, &args).await,
+        Command::Serve(args) => serve::run_server(&cli, &args).await,
+        Command::Doctor => doctor::run_doctor().await,
+        Command::Action { name } => run_action_command(&cli, name.as_deref()).await,
+        Command::Web => run_action_command(&cli, Some("web")).await,
+        command => run_weather_command(&cli, &command).await,
+    }
+}
+
+/// Runs `redweather action [name]` and returns the process exit code
+///
+/// The dashboard and settings window open here, like their own commands.
+async fn run_action_command(cli: &Cli, name: Option<&str>) -> i32 {
+    let cfg = load_config();
+    let Some(name) = name else {
+        actions::list_actions(&cfg);
+        return EXIT_OK;
+    };
+    match actions::all_actions(&cfg).get(name) {
+        Some(ActionConfig::Dashboard) => run_weather_command(cli, &Command::Dashboard).await,
+        Some(ActionConfig::Settings) => run_weather_command(cli, &Command::Settings).await,
+        Some(action) => actions::run_action(cli, name, action).await,
+        None => actions::unknown_action(name),
+    }
+}
+
+/// Runs the commands that show weather: bar, dashboard, settings, report and json
+async fn run_weather_command(cli: &Cli, command: &Command) -> i32 {
+    #[cfg(not(feature = "gui"))]
+    if matches!(command, Command::Dashboard | Command::Settings) {
+        return gui_unavailable(command);
+    }
+
+    // A running `redweather serve` already has what the bar shows
+    if *command == Command::Bar && ipc::can_attach(cli) {
+        if let Ok(mut client) = ipc::Client::connect() {
+            match client.request(&ipc::Request::Get) {
+                Ok(ipc::Response::Update(snapshot)) => {
+                    println!("{}", output::render(&snapshot.bar, cli.global.output));
+                    return EXIT_OK;
+                }
+                Ok(other) => warn!("redweather serve: unexpected response {:?}", other),
+                Err(e) => warn!("redweather serve: {}", e),
+            }
+        }
+    }
+
+    let key = match require_key() {
+        Ok(k) => k,
+        Err(f) => return report_failure(command, &f, cli.global.output),
+    };
+
+    // Location and settings window
+    #[cfg(feature = "gui")]
+    if *command == Command::Settings {
+        let cfg = load_config();
+        return match crate::ui::run_prompt(&key, &cfg) {
+            Ok(()) => EXIT_OK,
+            Err(e) => {
+                tracing::error!("Settings window failed: {}", e);
+                EXIT_FAILURE
+            }
+        };
+    }
+
+    let WeatherContext {
+        cfg,
+        diagnostics: config_diags,
+        resolved,
+        bar_locations,
+    } = match load_context(&key, &cli.global, cli.location()).await {
+        Ok(c) => c,
+        Err(f) => return report_failure(command, &f, cli.global.output),
+    };
+
+    match command {
+        // The dashboard starts with cached data (if any) and refreshes in the background
+        #[cfg(feature = "gui")]
+        Command::Dashboard => {
+            let data = if cli.global.reload {
+                None
+            } else {
+                crate::weather::load_stale_cache(
+                    &resolved.cache_key(),
+                    resolved.settings.cache.max_stale_secs,
+                )
+            };
+            crate::dashboard::run_dashboard(data, resolved, key, cfg, ipc::can_attach(cli));
+            EXIT_OK
+        }
+        Command::Bar if !bar_locations.is_empty() => {
+            let results = load_weather_all(&bar_locations, cli.global.reload).await;
+            let bar = waybar::multi_bar_json(&bar_locations, &results, &cfg, &config_diags);
+            println!("{}", output::render(&bar, cli.global.output));
+            EXIT_OK
+        }
+        _ => {
+            let fetched = match load_weather(&resolved, cli.global.reload).await {
+                Ok(f) => f,
+                Err(e) => return report_failure(command, &fetch_failure(&e), cli.global.output),
+            };
+
+            if let Command::Json { pretty } = command {
+                for d in &config_diags {
+                    warn!("config: {}", d);
+                }
+                let doc =
+                    export::weather_export(&fetched, &resolved, chrono::Utc::now().timestamp());
+                let out = if *pretty {
+                    serde_json::to_string_pretty(&doc)
+                } else {
+                    serde_json::to_string(&doc)
+                };
+                return match out {
+                    Ok(out) => {
+                        println!("{}", out);
+                        EXIT_OK
+                    }
+                    Err(e) => {
+                        error!("Failed to serialize weather: {}", e);
+                        EXIT_FAILURE
+                    }
+                };
+            }
+
+            if let Command::Report(args) = command {
+                for d in &config_diags {
+                    warn!("config: {}", d);
+                }
+                let opts = report::ReportOptions {
+                    hours: args.hours as usize,
+                    days: args.days as usize,
+                    chart: args.chart,
+                    color: match args.color {
+                        ColorWhen::Auto => report::color_by_default(),
+                        ColorWhen::Always => true,
+                        ColorWhen::Never => false,
+                    },
+                    width: args.width.unwrap_or_else(report::terminal_width),
+                };
+                print!(
+                    "{}",
+                    report::render_report(
+                        &fetched,
+                        &resolved.location.label,
+                        &resolved.settings,
+                        &opts
+                    )
+                );
+                return EXIT_OK;
+            }
+
+            let bar = waybar::bar_json(&fetched, &resolved, &cfg, &config_diags);
+            println!("{}", output::render(&bar, cli.global.output));
+            EXIT_OK
+        }
+    }
+}
+
+/// Explains that the dashboard and settings window were left out of this build
+#[cfg(not(feature = "gui"))]
+fn gui_unavailable(command: &Command) -> i32 {
+    let what = match command {
+        Command::Settings => "The settings window",
+        _ => "The dashboard",
+    };
+    error!(
+        "{} is not available: redweather was built without the `gui` feature. \
+         Rebuild with default features for GTK support; `redweather presets` \
+         manages locations without it.",
+        what
+    );
+    EXIT_FAILURE
+}
+
+/// Reports a failure that has already been logged to stderr
+///
+/// The bar gets fallback JSON and exits 0, because Waybar hides a module whose
+/// command fails. Other commands return the failure's exit code.
+fn report_failure(command: &Command, failure: &Failure, format: OutputFormat) -> i32 {
+    if *command != Command::Bar {
+        return failure.code;
+    }
+    println!("{}", output::render(&failure.json(), format));
+    EXIT_OK
+}
+
+/// Runs `redweather config <subcommand>` and returns the process exit code
+fn run_config_command(action: &ConfigCommand) -> i32 {
+    let path = match config_path() {
+        Ok(p) => p,
+        Err(e) => {
+            error!("{}", e);
+            return EXIT_FAILURE;
+        }
+    };
+    match action {
+        ConfigCommand::Path => {
+            println!("{}", path.display());
+            EXIT_OK
+        }
+        ConfigCommand::Check => {
+            if !path.exists() {
+                println!("{}: not found, using defaults", path.display());
+                return EXIT_OK;
+            }
+
+            let diagnostics = check_config_file(&path);
+            for d in &diagnostics {
+                match d.line {
+                    Some(line) => {
+                        println!("{}:{}: {}: {}", path.display(), line, d.severity, d.message)
+                    }
+                    None => println!("{}: {}: {}", path.display(), d.severity, d.message),
+                }
+            }
+
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == Severity::Error)
+                .count();
+            let warnings = diagnostics.len() - errors;
+            if diagnostics.is_empty() {
+                println!("{}: OK", path.display());
+            } else {
+                println!("{} error(s), {} warning(s)", errors, warnings);
+            }
+
+            if errors > 0 {
+                EXIT_FAILURE
+            } else {
+                EXIT_OK
+            }
+        }
+    }
+}
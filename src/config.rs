@@ -3,32 +3,76 @@
 //! This module handles loading and parsing configuration from files and environment variables.
 
 use anyhow::Context;
+use chrono::{Local, TimeZone};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
-
-/// API key file path relative to HOME
-pub const KEY_FILE: &str = ".config/redweather/apikey";
-/// Configuration file path relative to HOME
-pub const CONFIG_FILE: &str = ".config/redweather/config.toml";
-/// Legacy home location file (JSON) relative to HOME
-const HOME_LOCATION_FILE: &str = ".config/redweather/home_location.json";
+use tracing::{info, warn};
+
+use crate::migrate::{self, MigrationContext, CONFIG_VERSION};
+use crate::overrides::{self, Override};
+use crate::paths;
+use crate::utils::fmt_time;
+use crate::validate::{validate_config_str, Diagnostic};
+
+/// Fewest days the dashboard forecast can show
+pub const MIN_FORECAST_DAYS: usize = 5;
+/// Most days the dashboard forecast can show (limited by the API)
+pub const MAX_FORECAST_DAYS: usize = 12;
+/// Age in seconds up to which cached weather is used (10 minutes)
+pub const DEFAULT_CACHE_TTL_SECS: i64 = 600;
+/// Age in seconds up to which old weather is shown while fetches fail (a day)
+pub const DEFAULT_MAX_STALE_SECS: i64 = 24 * 3600;
 
 /// Unit system for temperature and speed
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Units {
+    #[default]
     Imperial,
     Metric,
 }
 
-impl Default for Units {
-    fn default() -> Self {
-        Units::Imperial
+impl Units {
+    /// Returns the name used in config files and the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Units::Imperial => "imperial",
+            Units::Metric => "metric",
+        }
     }
 }
 
+/// Clock style for displayed times of day
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+pub enum ClockFormat {
+    #[default]
+    #[serde(rename = "12h")]
+    H12,
+    #[serde(rename = "24h")]
+    H24,
+}
+
+/// Timezone that times of day are displayed in
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeZoneDisplay {
+    /// The forecast location's own timezone
+    #[default]
+    Location,
+    /// This machine's timezone
+    Local,
+}
+
+/// Time display configuration
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct TimeConfig {
+    pub clock: Option<ClockFormat>,
+    pub timezone: Option<TimeZoneDisplay>,
+}
+
 /// Temperature band configuration for color coding
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TempBand {
@@ -51,12 +95,166 @@ pub struct ColorsConfig {
 }
 
 /// Named location preset
+///
+/// The optional fields override the global settings while the preset is in use.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LocationPreset {
     pub name: String,
     pub lat: f64,
     pub lon: f64,
     pub label: String,
+    /// Short marker for the bar when showing several locations, e.g. "🏠"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units: Option<Units>,
+    /// OpenWeatherMap key to use instead of the global one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<TimeConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temp_bands: Option<Vec<TempBand>>,
+}
+
+impl LocationPreset {
+    /// Creates a preset without any setting overrides
+    pub fn new(name: &str, lat: f64, lon: f64, label: &str) -> Self {
+        LocationPreset {
+            name: name.to_string(),
+            lat,
+            lon,
+            label: label.to_string(),
+            icon: None,
+            units: None,
+            api_key: None,
+            time: None,
+            temp_bands: None,
+        }
+    }
+}
+
+/// Forecast data shown by a tooltip section
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SectionKind {
+    Current,
+    Hourly,
+    Daily,
+}
+
+/// One tooltip section; unset fields use the section's defaults
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TooltipSection {
+    pub section: SectionKind,
+    /// Header line; an empty string hides it
+    pub title: Option<String>,
+    /// Hours or days to list (ignored for `current`)
+    pub count: Option<usize>,
+    /// Template for the section (current) or for each hour/day
+    pub template: Option<String>,
+}
+
+/// Bar text and tooltip layout templates, see [`crate::template`]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FormatConfig {
+    pub bar: Option<String>,
+    /// Tooltip sections in display order
+    pub tooltip: Option<Vec<TooltipSection>>,
+    /// Each location's part of `{locations}` when `bar_presets` is set
+    pub location_bar: Option<String>,
+    /// Text between the locations in `{locations}`
+    pub location_separator: Option<String>,
+    /// Each location's tooltip block when `bar_presets` is set
+    pub location_tooltip: Option<String>,
+}
+
+/// Value reported as Waybar's `percentage`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PercentageMetric {
+    /// Temperature within the `temp_bands` range
+    #[default]
+    Temperature,
+    /// Chance of precipitation in the coming hour
+    Pop,
+    Humidity,
+    /// UV index, with 11 (extreme) as 100%
+    Uv,
+}
+
+/// Waybar output settings; thresholds are in the units in use
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct WaybarConfig {
+    pub percentage: Option<PercentageMetric>,
+    /// Temperature at or above which the `hot` class is set
+    pub hot: Option<f64>,
+    /// Temperature at or below which the `freezing` class is set
+    pub freezing: Option<f64>,
+    /// Wind speed at or above which the `windy` class is set
+    pub windy: Option<f64>,
+    /// The module's `signal` in the Waybar config; when set, changes refresh
+    /// just this module with SIGRTMIN+N instead of reloading all of Waybar
+    pub signal: Option<u32>,
+}
+
+/// The named action each i3bar/swaybar button runs (`[i3bar]`); `""` leaves
+/// a button unbound
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct I3barConfig {
+    pub left: Option<String>,
+    pub middle: Option<String>,
+    pub right: Option<String>,
+    pub scroll_up: Option<String>,
+    pub scroll_down: Option<String>,
+}
+
+/// What a named action does (`[actions.<name>]`)
+///
+/// URL and command templates take `{lat}`, `{lon}`, `{location}`, `{preset}`
+/// and `{units}`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ActionConfig {
+    /// Open a URL in the browser
+    Url {
+        url: String,
+    },
+    /// Run a shell command; placeholder values are quoted
+    Command {
+        command: String,
+    },
+    /// Switch presets: `next`, `prev` or a preset name
+    Preset {
+        preset: String,
+    },
+    /// Switch the location in use between imperial and metric
+    Units,
+    Dashboard,
+    Settings,
+    /// Fetch fresh data and update the bar
+    Refresh,
+}
+
+/// Log file settings (`[log]`); see [`crate::logging`]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LogConfig {
+    /// Also log to a daily file in the state directory
+    pub file: Option<bool>,
+    /// Level or `RUST_LOG`-style filter for the file
+    pub level: Option<String>,
+    /// Days of log files to keep
+    pub keep_days: Option<usize>,
+}
+
+/// Cache settings (`[cache]`)
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CacheConfig {
+    /// Minutes cached weather is used before it is fetched again
+    pub ttl_minutes: Option<u64>,
+    /// Hours old data may be shown while fetches fail; past that, an error is
+    /// shown instead
+    pub max_stale_hours: Option<u64>,
 }
 
 /// Dashboard specific configuration
@@ -87,73 +285,64 @@ impl Default for DashboardConfig {
 }
 
 /// Main configuration structure
+///
+/// Build one with [`Config::default`] or [`Config::from_toml`], or load the
+/// user's with [`load_config`].
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Config {
+    /// Schema version, see [`crate::migrate`]
+    #[serde(default)]
+    pub config_version: u32,
     #[serde(default)]
     pub units: Units,
     pub location_presets: Option<Vec<LocationPreset>>,
     pub active_preset: Option<String>,
+    /// Presets shown side by side in the bar, instead of just the active one
+    pub bar_presets: Option<Vec<String>>,
     pub ui: Option<UiConfig>,
     pub colors: Option<ColorsConfig>,
     pub temp_bands: Option<Vec<TempBand>>,
+    pub time: Option<TimeConfig>,
+    pub format: Option<FormatConfig>,
+    pub waybar: Option<WaybarConfig>,
+    pub i3bar: Option<I3barConfig>,
+    /// Named actions for `redweather action <name>`, replacing built-ins of the same name
+    pub actions: Option<BTreeMap<String, ActionConfig>>,
     #[serde(default)]
     pub dashboard: Option<DashboardConfig>,
+    pub log: Option<LogConfig>,
+    pub cache: Option<CacheConfig>,
+    /// Units that beat `units` and every preset's (`--units`, or a `units`
+    /// override); temperature bands are still read in the file's units
+    #[serde(skip)]
+    pub units_override: Option<Units>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
+            config_version: CONFIG_VERSION,
             units: Units::default(),
             location_presets: None,
             active_preset: None,
+            bar_presets: None,
             ui: None,
             colors: None,
             temp_bands: None,
+            time: None,
+            format: None,
+            waybar: None,
+            i3bar: None,
+            actions: None,
             dashboard: None,
+            log: None,
+            cache: None,
+            units_override: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct LegacyLocation {
-    pub lat: Option<f64>,
-    pub lon: Option<f64>,
-    pub label: Option<String>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-struct ConfigWithLegacy {
-    #[serde(default)]
-    pub units: Units,
-    pub location: Option<LegacyLocation>,
-    pub location_presets: Option<Vec<LocationPreset>>,
-    pub active_preset: Option<String>,
-    pub ui: Option<UiConfig>,
-    pub colors: Option<ColorsConfig>,
-    pub temp_bands: Option<Vec<TempBand>>,
-    #[serde(default)]
-    pub dashboard: Option<DashboardConfig>,
-}
-
-/// Parses configuration TOML, capturing legacy `[location]` if present
-fn parse_config_with_legacy(
-    contents: &str,
-) -> Result<(Config, Option<LegacyLocation>), toml::de::Error> {
-    let parsed: ConfigWithLegacy = toml::from_str(contents)?;
-
-    let cfg = Config {
-        units: parsed.units,
-        location_presets: parsed.location_presets,
-        active_preset: parsed.active_preset,
-        ui: parsed.ui,
-        colors: parsed.colors,
-        temp_bands: parsed.temp_bands,
-        dashboard: parsed.dashboard,
-    };
-
-    Ok((cfg, parsed.location))
-}
-
 /// Resolved dashboard configuration with defaults applied
 #[derive(Debug, Clone)]
 pub struct DashboardConfigResolved {
@@ -166,6 +355,7 @@ pub struct DashboardConfigResolved {
 }
 
 impl DashboardConfigResolved {
+    /// Applies the defaults to `[dashboard]`
     pub fn from_config(c: &Option<DashboardConfig>) -> Self {
         let defaults = DashboardConfig::default();
         match c {
@@ -176,7 +366,7 @@ impl DashboardConfigResolved {
                 } else {
                     c.forecast_hours
                 },
-                forecast_days: c.forecast_days.max(5).min(12),
+                forecast_days: c.forecast_days.clamp(MIN_FORECAST_DAYS, MAX_FORECAST_DAYS),
                 window_width: c.window_width.unwrap_or(500),
                 window_height: c.window_height.unwrap_or(700),
                 max_window_height: c.max_window_height,
@@ -184,7 +374,9 @@ impl DashboardConfigResolved {
             None => DashboardConfigResolved {
                 show_hourly_graph: defaults.show_hourly_graph,
                 forecast_hours: defaults.forecast_hours,
-                forecast_days: defaults.forecast_days.max(5).min(12),
+                forecast_days: defaults
+                    .forecast_days
+                    .clamp(MIN_FORECAST_DAYS, MAX_FORECAST_DAYS),
                 window_width: 500,
                 window_height: 700,
                 max_window_height: None,
@@ -193,114 +385,42 @@ impl DashboardConfigResolved {
     }
 }
 
-/// Writes the config file to disk, ensuring parent directory exists
-fn write_config_file(path: &Path, config: &Config) -> anyhow::Result<()> {
-    let toml_string = toml::to_string_pretty(config).context("Failed to serialize config")?;
-
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).context("Failed to create config directory")?;
-    }
-
-    fs::write(path, toml_string).context("Failed to write config file")?;
-    Ok(())
+/// Resolved cache policy with defaults applied, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheConfigResolved {
+    /// Age up to which cached weather is used without fetching
+    pub ttl_secs: i64,
+    /// Age up to which cached weather is shown when a fetch fails
+    pub max_stale_secs: i64,
 }
 
-/// Inserts a preset if it doesn't already exist and sets active_preset if unset
-fn upsert_preset(config: &mut Config, preset: LocationPreset) -> bool {
-    let mut changed = false;
+/// `n` units of `unit` seconds, saturating instead of overflowing
+fn secs(n: u64, unit: u64) -> i64 {
+    i64::try_from(n.saturating_mul(unit)).unwrap_or(i64::MAX)
+}
 
-    if let Some(presets) = &mut config.location_presets {
-        if presets.iter().any(|p| p.name == preset.name) {
-            // Leave existing entry intact to avoid overwriting user data
-        } else {
-            presets.push(preset);
-            changed = true;
+impl CacheConfigResolved {
+    /// Applies the defaults to `[cache]`
+    pub fn from_config(c: &Option<CacheConfig>) -> Self {
+        let minutes = c.as_ref().and_then(|c| c.ttl_minutes);
+        let hours = c.as_ref().and_then(|c| c.max_stale_hours);
+        CacheConfigResolved {
+            ttl_secs: minutes.map_or(DEFAULT_CACHE_TTL_SECS, |m| secs(m, 60)),
+            max_stale_secs: hours.map_or(DEFAULT_MAX_STALE_SECS, |h| secs(h, 3600)),
         }
-    } else {
-        config.location_presets = Some(vec![preset]);
-        changed = true;
     }
-
-    if config.active_preset.is_none() {
-        if let Some(presets) = &config.location_presets {
-            if let Some(first) = presets.first() {
-                config.active_preset = Some(first.name.clone());
-                changed = true;
-            }
-        }
-    }
-
-    changed
 }
 
-/// Migrates legacy home_location.json into presets
-fn migrate_legacy_home_location(config: &mut Config, home_dir: &str) -> bool {
-    let path = PathBuf::from(home_dir).join(HOME_LOCATION_FILE);
-    let contents = match fs::read_to_string(&path) {
-        Ok(c) => c,
-        Err(_) => return false,
-    };
-
-    #[derive(Deserialize)]
-    struct LegacyHome {
-        lat: f64,
-        lon: f64,
-        label: Option<String>,
-    }
-
-    let parsed: LegacyHome = match serde_json::from_str(&contents) {
-        Ok(p) => p,
-        Err(_) => return false,
-    };
-
-    let preset = LocationPreset {
-        name: "home".to_string(),
-        lat: parsed.lat,
-        lon: parsed.lon,
-        label: parsed.label.unwrap_or_else(|| "Home".to_string()),
-    };
+/// Writes the config file to disk, ensuring parent directory exists
+fn write_config_file(path: &Path, config: &Config) -> anyhow::Result<()> {
+    let toml_string = toml::to_string_pretty(config).context("Failed to serialize config")?;
 
-    let changed = upsert_preset(config, preset);
-    if changed {
-        let _ = fs::remove_file(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("Failed to create config directory")?;
     }
-    changed
-}
-
-/// Migrates legacy `[location]` section into presets (if none exist)
-fn migrate_legacy_location_section(
-    config: &mut Config,
-    legacy_location: Option<LegacyLocation>,
-) -> bool {
-    let legacy = match legacy_location {
-        Some(loc) => loc,
-        None => return false,
-    };
-
-    let (lat, lon) = match (legacy.lat, legacy.lon) {
-        (Some(lat), Some(lon)) => (lat, lon),
-        _ => return false,
-    };
-
-    // If presets already exist, skip migration to avoid overwriting user data
-    if config
-        .location_presets
-        .as_ref()
-        .map_or(false, |p| !p.is_empty())
-    {
-        return false;
-    }
-
-    let preset = LocationPreset {
-        name: "imported".to_string(),
-        lat,
-        lon,
-        label: legacy
-            .label
-            .unwrap_or_else(|| "Imported location".to_string()),
-    };
 
-    upsert_preset(config, preset)
+    fs::write(path, toml_string).context("Failed to write config file")?;
+    Ok(())
 }
 
 /// Resolved UI configuration with defaults applied
@@ -311,6 +431,7 @@ pub struct UiConfigResolved {
 }
 
 impl UiConfigResolved {
+    /// Applies the defaults to `[ui]`
     pub fn from_config(ui: &Option<UiConfig>) -> Self {
         UiConfigResolved {
             font_size: ui.as_ref().and_then(|u| u.font_size).unwrap_or(9),
@@ -391,17 +512,135 @@ impl TempBand {
     }
 }
 
+/// Resolved time display settings
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeSettings {
+    pub clock: ClockFormat,
+    pub timezone: TimeZoneDisplay,
+}
+
+impl TimeSettings {
+    /// Layers a preset's time config over the global one
+    pub fn from_configs(global: &Option<TimeConfig>, preset: Option<&TimeConfig>) -> Self {
+        let global = global.as_ref();
+        TimeSettings {
+            clock: preset
+                .and_then(|t| t.clock)
+                .or_else(|| global.and_then(|t| t.clock))
+                .unwrap_or_default(),
+            timezone: preset
+                .and_then(|t| t.timezone)
+                .or_else(|| global.and_then(|t| t.timezone))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns the UTC offset to display `dt` with, given the location's offset
+    pub fn offset(&self, dt: i64, location_offset: i64) -> i64 {
+        match self.timezone {
+            TimeZoneDisplay::Location => location_offset,
+            TimeZoneDisplay::Local => Local
+                .timestamp_opt(dt, 0)
+                .single()
+                .map(|t| t.offset().local_minus_utc() as i64)
+                .unwrap_or(location_offset),
+        }
+    }
+
+    /// Formats a time of day such as a sunrise ("6:42 AM" or "06:42")
+    pub fn time(&self, dt: i64, location_offset: i64) -> String {
+        let fmt = match self.clock {
+            ClockFormat::H12 => "%-I:%M %p",
+            ClockFormat::H24 => "%H:%M",
+        };
+        fmt_time(dt, self.offset(dt, location_offset), fmt)
+    }
+
+    /// Formats a short hour label for forecasts ("3PM" or "15:00")
+    pub fn hour(&self, dt: i64, location_offset: i64) -> String {
+        let fmt = match self.clock {
+            ClockFormat::H12 => "%-I%p",
+            ClockFormat::H24 => "%H:00",
+        };
+        fmt_time(dt, self.offset(dt, location_offset), fmt)
+    }
+}
+
+/// Effective settings for one location: its preset's overrides layered over the
+/// global config
+#[derive(Debug, Clone)]
+pub struct LocationSettings {
+    pub units: Units,
+    pub temp_bands: Vec<TempBand>,
+    pub time: TimeSettings,
+    pub api_key: String,
+    pub cache: CacheConfigResolved,
+}
+
+impl LocationSettings {
+    /// Merges `preset` (if any) over `cfg`; `api_key` is the global key
+    pub fn resolve(cfg: &Config, preset: Option<&LocationPreset>, api_key: &str) -> Self {
+        let units = preset.and_then(|p| p.units).unwrap_or(cfg.units);
+        // A preset's own bands are in its units, global ones in the global
+        // units and the built-in palette in °F
+        let preset_bands = preset
+            .and_then(|p| p.temp_bands.as_ref())
+            .filter(|b| !b.is_empty());
+        let global_bands = cfg.temp_bands.as_ref().filter(|b| !b.is_empty());
+        let (bands, bands_units) = match (preset_bands, global_bands) {
+            (Some(bands), _) => (bands.clone(), units),
+            (None, Some(bands)) => (bands.clone(), cfg.units),
+            (None, None) => (TempBand::from_config(&None), Units::Imperial),
+        };
+        let units = cfg.units_override.unwrap_or(units);
+        LocationSettings {
+            units,
+            temp_bands: convert_bands(bands, bands_units, units),
+            time: TimeSettings::from_configs(&cfg.time, preset.and_then(|p| p.time.as_ref())),
+            api_key: preset
+                .and_then(|p| p.api_key.as_deref())
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .unwrap_or(api_key)
+                .to_string(),
+            cache: CacheConfigResolved::from_config(&cfg.cache),
+        }
+    }
+
+    /// Switches to `units`, converting the temperature bands along with them
+    pub fn with_units(mut self, units: Units) -> Self {
+        self.temp_bands = convert_bands(self.temp_bands, self.units, units);
+        self.units = units;
+        self
+    }
+}
+
+/// Converts band limits between °F and °C
+fn convert_bands(bands: Vec<TempBand>, from: Units, to: Units) -> Vec<TempBand> {
+    let convert = |t: f64| match (from, to) {
+        (Units::Imperial, Units::Metric) => (t - 32.0) * 5.0 / 9.0,
+        (Units::Metric, Units::Imperial) => t * 9.0 / 5.0 + 32.0,
+        _ => t,
+    };
+    bands
+        .into_iter()
+        .map(|b| TempBand {
+            max: convert(b.max),
+            color: b.color,
+        })
+        .collect()
+}
+
 /// Loads the OpenWeatherMap API key from environment variable or file
 ///
-/// Checks OWM_API_KEY environment variable first, then ~/.config/redweather/apikey
+/// Checks OWM_API_KEY environment variable first, then `$XDG_CONFIG_HOME/redweather/apikey`
 pub fn load_key() -> Option<String> {
     if let Ok(k) = env::var("OWM_API_KEY") {
         if !k.trim().is_empty() {
             return Some(k);
         }
     }
-    let home = env::var("HOME").ok()?;
-    let path = PathBuf::from(home).join(KEY_FILE);
+    let path = paths::key_file()?;
     if let Ok(contents) = fs::read_to_string(path) {
         let t = contents.trim().to_string();
         if !t.is_empty() {
@@ -411,63 +650,238 @@ pub fn load_key() -> Option<String> {
     None
 }
 
-/// Loads configuration from ~/.config/redweather/config.toml
+/// Returns the path of the config file
 ///
-/// Performs a one-time migration of legacy location storage:
-/// - Imports ~/.config/redweather/home_location.json as a "home" preset
-/// - Imports legacy `[location]` table as a preset if no presets exist
-/// Returns empty config if file doesn't exist or parsing fails.
+/// This is `--config <path>` if given, else `$XDG_CONFIG_HOME/redweather/config.toml`.
+pub fn config_path() -> anyhow::Result<PathBuf> {
+    paths::config_file().context("Neither HOME nor XDG_CONFIG_HOME is set")
+}
+
+/// Loads the effective configuration (config file plus overrides)
+///
+/// See [`load_config_with_diagnostics`]; diagnostics are only logged here.
 pub fn load_config() -> Config {
-    let home = match env::var("HOME") {
-        Ok(h) => h,
+    load_config_with_diagnostics().0
+}
+
+/// Loads the config file alone, without environment or `--set` overrides
+pub fn load_config_file() -> Config {
+    match config_path() {
+        Ok(path) => load_config_from(&path, paths::config_dir().as_deref(), &[]).config,
+        Err(_) => Config::default(),
+    }
+}
+
+/// Loads configuration and validates it, returning any problems found
+///
+/// Older files are upgraded through [`crate::migrate`] (e.g. the legacy
+/// home_location.json and `[location]` table become presets); the original is
+/// backed up before the migrated config is written back.
+///
+/// Environment and `--set` overrides (see [`crate::overrides`]) are layered on
+/// top of the file.
+///
+/// Returns empty config if file doesn't exist or parsing fails; in the latter case
+/// the diagnostics explain why so callers can surface them instead of silently
+/// running on defaults.
+pub fn load_config_with_diagnostics() -> (Config, Vec<Diagnostic>) {
+    let (overrides, errors) = overrides::collect();
+    let (config, mut diagnostics) = match config_path() {
+        Ok(path) => {
+            let loaded = load_config_from(&path, paths::config_dir().as_deref(), &overrides);
+            (loaded.config, loaded.diagnostics)
+        }
         Err(e) => {
-            eprintln!("Error getting HOME environment variable: {}", e);
-            return Config::default();
+            warn!("Error locating config file: {}", e);
+            (Config::default(), Vec::new())
         }
     };
-    let path = PathBuf::from(&home).join(CONFIG_FILE);
-
-    let (mut config, legacy_location) = match fs::read_to_string(&path) {
-        Ok(contents) => match parse_config_with_legacy(&contents) {
-            Ok((cfg, legacy)) => (cfg, legacy),
-            Err(e) => {
-                eprintln!("Error parsing config file {}: {}", path.display(), e);
-                (Config::default(), None)
-            }
-        },
+    diagnostics.extend(errors.into_iter().map(|e| Diagnostic::error(None, e)));
+    (config, diagnostics)
+}
+
+/// Reads just `[log]`, with overrides, without logging anything
+///
+/// Runs before the logger exists, so problems are left for the full load to
+/// report.
+pub fn load_log_config() -> LogConfig {
+    let mut table = config_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| toml::from_str::<toml::Table>(&contents).ok())
+        .unwrap_or_default();
+    overrides::apply(&mut table, &overrides::collect().0);
+    table
+        .remove("log")
+        .and_then(|log| log.try_into().ok())
+        .unwrap_or_default()
+}
+
+/// A config file as read by [`load_config_from`]
+struct LoadedConfig {
+    config: Config,
+    diagnostics: Vec<Diagnostic>,
+    /// Why `config` is not what the file holds (it could not be read, parsed or
+    /// migrated); such a config must never be written back over the file
+    error: Option<String>,
+}
+
+impl LoadedConfig {
+    fn failed(diagnostics: Vec<Diagnostic>, error: String) -> Self {
+        LoadedConfig {
+            config: Config::default(),
+            diagnostics,
+            error: Some(error),
+        }
+    }
+}
+
+/// Loads, validates and migrates the config file at `path`, then applies overrides
+///
+/// `config_dir` is where legacy side files (home_location.json) are looked up.
+fn load_config_from(
+    path: &Path,
+    config_dir: Option<&Path>,
+    overrides: &[Override],
+) -> LoadedConfig {
+    let (contents, exists) = match fs::read_to_string(path) {
+        Ok(contents) => (contents, true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => (String::new(), false),
         Err(e) => {
-            if e.kind() != std::io::ErrorKind::NotFound {
-                eprintln!("Error reading config file {}: {}", path.display(), e);
-            }
-            (Config::default(), None)
+            warn!("Error reading config file {}: {}", path.display(), e);
+            let diag = Diagnostic::error(None, format!("cannot read {}: {}", path.display(), e));
+            return LoadedConfig::failed(vec![diag], format!("cannot be read ({})", e));
         }
     };
+    let diagnostics = if exists {
+        validate_config_str(&contents)
+    } else {
+        Vec::new()
+    };
 
-    let mut changed = false;
-    changed |= migrate_legacy_home_location(&mut config, &home);
-    changed |= migrate_legacy_location_section(&mut config, legacy_location);
+    // Never migrate or rewrite a file we failed to parse: that would replace the
+    // user's (fixable) config with defaults.
+    let mut table: toml::Table = match toml::from_str(&contents) {
+        Ok(t) => t,
+        Err(e) => {
+            warn!("Error parsing config file {}: {}", path.display(), e);
+            let error = format!("has syntax errors ({})", e.message().trim());
+            return LoadedConfig::failed(diagnostics, error);
+        }
+    };
+
+    let mut ctx = MigrationContext::new(config_dir);
+    let report = migrate::migrate_table(&mut table, &mut ctx);
+
+    let config: Config = match toml::Value::Table(table.clone()).try_into() {
+        Ok(c) => c,
+        Err(e) => {
+            warn!("Error parsing config file {}: {}", path.display(), e);
+            let error = format!("has invalid settings ({})", e.message().trim());
+            return LoadedConfig::failed(diagnostics, error);
+        }
+    };
 
-    if changed {
-        // Errors here are already logged by write_config_file
-        let _ = write_config_file(&path, &config);
+    let mut error = None;
+    if report.changed() {
+        if exists {
+            match migrate::backup_config_file(path, report.from) {
+                Ok(backup) => info!("Backed up config to {}", backup.display()),
+                Err(e) => {
+                    warn!("Not migrating {}: backup failed: {}", path.display(), e);
+                    error = Some(format!("could not be backed up before migrating ({})", e));
+                }
+            }
+        }
+        if error.is_none() {
+            match write_config_file(path, &config) {
+                Ok(()) => {
+                    for consumed in ctx.consumed_files {
+                        let _ = fs::remove_file(consumed);
+                    }
+                }
+                Err(e) => {
+                    warn!("Error writing migrated config {}: {}", path.display(), e);
+                    error = Some(format!("could not be migrated ({})", e));
+                }
+            }
+        }
     }
 
-    config
+    let mut loaded = LoadedConfig {
+        config,
+        diagnostics,
+        error,
+    };
+    if overrides.is_empty() {
+        return loaded;
+    }
+    for e in overrides::apply(&mut table, overrides) {
+        warn!("Ignoring {}", e);
+        loaded.diagnostics.push(Diagnostic::error(None, e));
+    }
+    match toml::Value::Table(table).try_into::<Config>() {
+        Ok(mut effective) => {
+            // Like --units: the bands stay in the file's units
+            if overrides.iter().any(|o| o.path == ["units"]) {
+                effective.units_override = Some(effective.units);
+                effective.units = loaded.config.units;
+            }
+            loaded.config = effective
+        }
+        Err(e) => {
+            let msg = format!("overrides ignored: {}", e.message().trim());
+            warn!("{}", msg);
+            loaded.diagnostics.push(Diagnostic::error(None, msg));
+        }
+    }
+    loaded
 }
 
-/// Updates the active_preset in the config file
-pub fn update_active_preset(preset_name: &str) -> anyhow::Result<()> {
-    let home = env::var("HOME").context("HOME environment variable not set")?;
-    let path = PathBuf::from(home).join(CONFIG_FILE);
+/// Loads the config file (without overrides), applies `f` and writes it back
+///
+/// Overrides are deliberately skipped so per-instance settings never get persisted.
+/// Refuses to write if the existing file could not be read, parsed or migrated,
+/// since that would replace the user's config with defaults.
+pub fn update_config<F>(f: F) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut Config),
+{
+    try_update_config(|config| {
+        f(config);
+        Ok(())
+    })
+}
 
-    // Load current config
-    let mut config = load_config();
+/// Like [`update_config`], but `f` may fail, in which case nothing is written
+pub fn try_update_config<F, T>(f: F) -> anyhow::Result<T>
+where
+    F: FnOnce(&mut Config) -> anyhow::Result<T>,
+{
+    update_config_file(&config_path()?, paths::config_dir().as_deref(), f)
+}
 
-    // Update active_preset
-    config.active_preset = Some(preset_name.to_string());
+fn update_config_file<F, T>(path: &Path, config_dir: Option<&Path>, f: F) -> anyhow::Result<T>
+where
+    F: FnOnce(&mut Config) -> anyhow::Result<T>,
+{
+    let loaded = load_config_from(path, config_dir, &[]);
+    if let Some(error) = loaded.error {
+        anyhow::bail!(
+            "Not saving: {} {}; run `redweather config check`",
+            path.display(),
+            error
+        );
+    }
+    let mut config = loaded.config;
+    let result = f(&mut config)?;
+    write_config_file(path, &config)?;
+    Ok(result)
+}
 
-    write_config_file(&path, &config)?;
-    Ok(())
+/// Updates the active_preset in the config file
+pub fn update_active_preset(preset_name: &str) -> anyhow::Result<()> {
+    update_config(|config| config.active_preset = Some(preset_name.to_string()))
 }
 
 /// Adds or updates a location preset in the config file
@@ -477,47 +891,116 @@ pub fn save_location_preset(
     lon: f64,
     label: &str,
 ) -> anyhow::Result<()> {
-    let home = env::var("HOME").context("HOME environment variable not set")?;
-    let path = PathBuf::from(home).join(CONFIG_FILE);
-
-    // Load current config
-    let mut config = load_config();
-
-    // Create new preset
-    let new_preset = LocationPreset {
-        name: preset_name.to_string(),
-        lat,
-        lon,
-        label: label.to_string(),
-    };
-
-    // Add or update preset
-    if let Some(presets) = &mut config.location_presets {
-        // Find and replace existing preset with same name
-        if let Some(existing) = presets.iter_mut().find(|p| p.name == preset_name) {
-            *existing = new_preset;
+    update_config(|config| {
+        // Add or update preset, keeping any per-preset settings
+        if let Some(presets) = &mut config.location_presets {
+            // Find and update existing preset with same name
+            if let Some(existing) = presets.iter_mut().find(|p| p.name == preset_name) {
+                existing.lat = lat;
+                existing.lon = lon;
+                existing.label = label.to_string();
+            } else {
+                // Add new preset
+                presets.push(LocationPreset::new(preset_name, lat, lon, label));
+            }
         } else {
-            // Add new preset
-            presets.push(new_preset);
+            // Create new presets list
+            config.location_presets = Some(vec![LocationPreset::new(preset_name, lat, lon, label)]);
         }
-    } else {
-        // Create new presets list
-        config.location_presets = Some(vec![new_preset]);
-    }
 
-    // Set as active preset
-    config.active_preset = Some(preset_name.to_string());
+        // Set as active preset
+        config.active_preset = Some(preset_name.to_string());
+    })
+}
 
-    write_config_file(&path, &config)?;
-    Ok(())
+/// Removes a preset from the config file (see [`Config::remove_preset`])
+pub fn remove_preset(preset_name: &str) -> anyhow::Result<()> {
+    try_update_config(|config| config.remove_preset(preset_name))
 }
 
-/// Saves weather data to cache
-pub fn save_config(config: &Config) -> anyhow::Result<()> {
-    let home = env::var("HOME").context("HOME environment variable not set")?;
-    let path = PathBuf::from(home).join(CONFIG_FILE);
-    write_config_file(&path, config)?;
-    Ok(())
+/// Renames a preset in the config file (see [`Config::rename_preset`])
+pub fn rename_preset(old_name: &str, new_name: &str) -> anyhow::Result<()> {
+    try_update_config(|config| config.rename_preset(old_name, new_name))
+}
+
+/// Cycles the active preset in the config file (see [`Config::cycle_active_preset`])
+pub fn cycle_active_preset(step: isize) -> anyhow::Result<LocationPreset> {
+    try_update_config(|config| config.cycle_active_preset(step))
+}
+
+impl Config {
+    /// Parses config.toml contents, migrating older versions in memory
+    ///
+    /// Unlike [`load_config`], nothing is read or written besides `contents`,
+    /// and environment and `--set` overrides are not applied.
+    pub fn from_toml(contents: &str) -> anyhow::Result<Config> {
+        let mut table: toml::Table = toml::from_str(contents)?;
+        migrate::migrate_table(&mut table, &mut MigrationContext::new(None));
+        Ok(toml::Value::Table(table).try_into()?)
+    }
+
+    /// Returns the index of the preset in use: the active one, else the first
+    pub fn active_preset_index(&self) -> Option<usize> {
+        let presets = self.location_presets.as_deref().unwrap_or(&[]);
+        if presets.is_empty() {
+            return None;
+        }
+        Some(
+            self.active_preset
+                .as_ref()
+                .and_then(|a| presets.iter().position(|p| &p.name == a))
+                .unwrap_or(0),
+        )
+    }
+
+    /// Removes a preset; if it was active, the first remaining one becomes active
+    pub fn remove_preset(&mut self, preset_name: &str) -> anyhow::Result<()> {
+        let presets = self.location_presets.get_or_insert_with(Vec::new);
+        let idx = presets
+            .iter()
+            .position(|p| p.name == preset_name)
+            .with_context(|| format!("No preset named \"{}\"", preset_name))?;
+        presets.remove(idx);
+
+        if self.active_preset.as_deref() == Some(preset_name) {
+            self.active_preset = presets.first().map(|p| p.name.clone());
+        }
+        if presets.is_empty() {
+            self.location_presets = None;
+        }
+        Ok(())
+    }
+
+    /// Renames a preset, keeping it active if it was
+    pub fn rename_preset(&mut self, old_name: &str, new_name: &str) -> anyhow::Result<()> {
+        let presets = self.location_presets.get_or_insert_with(Vec::new);
+        if presets.iter().any(|p| p.name == new_name) {
+            anyhow::bail!("A preset named \"{}\" already exists", new_name);
+        }
+        let preset = presets
+            .iter_mut()
+            .find(|p| p.name == old_name)
+            .with_context(|| format!("No preset named \"{}\"", old_name))?;
+        preset.name = new_name.to_string();
+
+        if self.active_preset.as_deref() == Some(old_name) {
+            self.active_preset = Some(new_name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Moves the active preset `step` places through the list, wrapping around,
+    /// and returns the newly active preset
+    pub fn cycle_active_preset(&mut self, step: isize) -> anyhow::Result<LocationPreset> {
+        let current = self
+            .active_preset_index()
+            .context("No presets configured")?;
+        let presets = self.location_presets.as_deref().unwrap_or(&[]);
+        let next = (current as isize + step).rem_euclid(presets.len() as isize) as usize;
+        let preset = presets[next].clone();
+        self.active_preset = Some(preset.name.clone());
+        Ok(preset)
+    }
 }
 
 #[cfg(test)]
@@ -554,6 +1037,241 @@ mod tests {
         assert_eq!(colors.text, "#abcdef");
     }
 
+    #[test]
+    fn test_load_config_migrates_legacy_files() {
+        let dir = std::env::temp_dir().join("redweather_test_load_config_migration");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let legacy = "units = \"metric\"\n\n[location]\nlat = 1.0\nlon = 2.0\n";
+        fs::write(&path, legacy).unwrap();
+        fs::write(
+            dir.join("home_location.json"),
+            r#"{"lat": 37.5, "lon": -97.2}"#,
+        )
+        .unwrap();
+
+        let LoadedConfig {
+            config,
+            diagnostics,
+            ..
+        } = load_config_from(&path, Some(&dir), &[]);
+        assert!(diagnostics.is_empty());
+        assert_eq!(config.config_version, CONFIG_VERSION);
+        assert_eq!(config.units, Units::Metric);
+        assert_eq!(config.active_preset.as_deref(), Some("home"));
+        let presets = config.location_presets.unwrap();
+        assert_eq!(presets.len(), 1);
+        assert_eq!(presets[0].label, "Home");
+
+        // Original kept as a backup, file rewritten, side file consumed
+        assert_eq!(
+            fs::read_to_string(dir.join("config.toml.v0.bak")).unwrap(),
+            legacy
+        );
+        let rewritten = fs::read_to_string(&path).unwrap();
+        assert!(rewritten.contains(&format!("config_version = {}", CONFIG_VERSION)));
+        assert!(!rewritten.contains("[location]"));
+        assert!(!dir.join("home_location.json").exists());
+
+        // A second load is a no-op
+        let again = load_config_from(&path, Some(&dir), &[]).config;
+        assert_eq!(again.active_preset.as_deref(), Some("home"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), rewritten);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_config_from_toml() {
+        let config =
+            Config::from_toml("units = \"metric\"\n[location]\nlat = 1.0\nlon = 2.0\n").unwrap();
+        assert_eq!(config.config_version, CONFIG_VERSION);
+        assert_eq!(config.units, Units::Metric);
+        assert_eq!(config.active_preset.as_deref(), Some("imported"));
+        assert!(Config::from_toml("units = metric").is_err());
+    }
+
+    #[test]
+    fn test_load_config_leaves_unparseable_file_alone() {
+        let dir = std::env::temp_dir().join("redweather_test_load_config_broken");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, "units = metric\n[location]\nlat = 1.0\nlon = 2.0\n").unwrap();
+
+        let LoadedConfig {
+            config,
+            diagnostics,
+            ..
+        } = load_config_from(&path, Some(&dir), &[]);
+        assert!(config.location_presets.is_none());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, Some(1));
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("units = metric"));
+        assert!(!dir.join("config.toml.v0.bak").exists());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_update_config_leaves_invalid_file_alone() {
+        let dir = std::env::temp_dir().join("redweather_test_update_config_invalid");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let contents = "config_version = 1\nunits = \"celsius\"\nactive_preset = \"home\"\n\n\
+                        [[location_presets]]\nname = \"home\"\nlat = 1.0\nlon = 2.0\nlabel = \"Home\"\n";
+        fs::write(&path, contents).unwrap();
+
+        let err = update_config_file(&path, Some(&dir), |c| {
+            c.units = Units::Metric;
+            Ok(())
+        })
+        .unwrap_err();
+        assert!(err.to_string().starts_with("Not saving"));
+        assert_eq!(fs::read(&path).unwrap(), contents.as_bytes());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_load_config_applies_overrides_without_saving() {
+        let dir = std::env::temp_dir().join("redweather_test_load_config_overrides");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let contents = "config_version = 1\nunits = \"imperial\"\n";
+        fs::write(&path, contents).unwrap();
+
+        let overrides = vec![
+            crate::overrides::parse_set("units=metric").unwrap(),
+            crate::overrides::parse_set("units.nested=1").unwrap(),
+        ];
+        let LoadedConfig {
+            config,
+            diagnostics,
+            ..
+        } = load_config_from(&path, Some(&dir), &overrides);
+        // The bands stay in the file's units
+        assert_eq!(config.units, Units::Imperial);
+        assert_eq!(config.units_override, Some(Units::Metric));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_location_settings_layer_preset_over_global() {
+        let config: Config = toml::from_str(
+            r##"
+units = "imperial"
+
+[time]
+timezone = "local"
+
+[[location_presets]]
+name = "home"
+lat = 40.7
+lon = -74.0
+label = "Home"
+
+[[location_presets]]
+name = "berlin"
+lat = 52.5
+lon = 13.4
+label = "Berlin"
+units = "metric"
+api_key = "colleague-key"
+
+[location_presets.time]
+clock = "24h"
+"##,
+        )
+        .unwrap();
+        let presets = config.location_presets.as_ref().unwrap();
+
+        let home = LocationSettings::resolve(&config, Some(&presets[0]), "global-key");
+        assert_eq!(home.units, Units::Imperial);
+        assert_eq!(home.api_key, "global-key");
+        assert_eq!(home.time.clock, ClockFormat::H12);
+        assert_eq!(home.time.timezone, TimeZoneDisplay::Local);
+        assert_eq!(home.temp_bands[0].max, 59.0);
+
+        let berlin = LocationSettings::resolve(&config, Some(&presets[1]), "global-key");
+        assert_eq!(berlin.units, Units::Metric);
+        assert_eq!(berlin.api_key, "colleague-key");
+        assert_eq!(berlin.time.clock, ClockFormat::H24);
+        assert_eq!(berlin.time.timezone, TimeZoneDisplay::Local);
+        // Default palette is converted to °C
+        assert_eq!(berlin.temp_bands[0].max, 15.0);
+
+        // Global bands are in the global units, so they are converted too
+        let with_bands = Config {
+            temp_bands: Some(vec![
+                TempBand {
+                    max: 50.0,
+                    color: "#8caaee".into(),
+                },
+                TempBand {
+                    max: 500.0,
+                    color: "#e78284".into(),
+                },
+            ]),
+            ..config.clone()
+        };
+        let berlin = LocationSettings::resolve(&with_bands, Some(&presets[1]), "global-key");
+        assert_eq!(berlin.temp_bands[0].max, 10.0);
+        assert_eq!(berlin.temp_bands[1].max, 260.0);
+        let home = LocationSettings::resolve(&with_bands, Some(&presets[0]), "global-key");
+        assert_eq!(home.temp_bands[0].max, 50.0);
+
+        let time = TimeSettings {
+            clock: ClockFormat::H24,
+            timezone: TimeZoneDisplay::Location,
+        };
+        assert_eq!(time.time(1_684_929_490, 3600), "12:58");
+        assert_eq!(time.hour(1_684_929_490, 3600), "12:00");
+        let time = TimeSettings::default();
+        assert_eq!(time.time(1_684_929_490, 3600), "12:58 PM");
+        assert_eq!(time.hour(1_684_929_490, 3600), "12PM");
+    }
+
+    #[test]
+    fn test_preset_editing() {
+        let mut config = Config {
+            location_presets: Some(vec![
+                LocationPreset::new("home", 1.0, 1.0, "Home"),
+                LocationPreset::new("work", 2.0, 2.0, "Work"),
+                LocationPreset::new("cabin", 3.0, 3.0, "Cabin"),
+            ]),
+            ..Config::default()
+        };
+
+        // No active preset means the first one is in use
+        assert_eq!(config.active_preset_index(), Some(0));
+        assert_eq!(config.cycle_active_preset(-1).unwrap().name, "cabin");
+        assert_eq!(config.cycle_active_preset(1).unwrap().name, "home");
+        assert_eq!(config.cycle_active_preset(1).unwrap().name, "work");
+
+        config.rename_preset("work", "office").unwrap();
+        assert_eq!(config.active_preset.as_deref(), Some("office"));
+        assert!(config.rename_preset("home", "cabin").is_err());
+        assert!(config.rename_preset("nope", "x").is_err());
+
+        config.remove_preset("office").unwrap();
+        assert_eq!(config.active_preset.as_deref(), Some("home"));
+        assert!(config.remove_preset("office").is_err());
+        config.remove_preset("home").unwrap();
+        config.remove_preset("cabin").unwrap();
+        assert!(config.location_presets.is_none());
+        assert!(config.active_preset.is_none());
+        assert!(config.cycle_active_preset(1).is_err());
+    }
+
     #[test]
     fn test_temp_bands_defaults() {
         let bands = TempBand::from_config(&None);
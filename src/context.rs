@@ -0,0 +1,226 @@
+//! Loading the API key, config and location for the weather commands
+//!
+//! The one-shot commands and the daemon go through the same steps. Each step
+//! that can fail returns a [`Failure`] carrying both the exit code and the
+//! fallback the bar shows instead of the weather.
+
+use serde_json::Value;
+use tracing::error;
+
+use crate::cli::{GlobalArgs, EXIT_FAILURE, EXIT_USAGE};
+use crate::config::{
+    load_config_with_diagnostics, load_key, ColorsResolved, Config, UiConfigResolved,
+};
+use crate::formatting::format_config_diagnostics;
+use crate::paths;
+use crate::utils::escape_pango;
+use crate::validate::{has_errors, Diagnostic};
+use crate::waybar::{self, Fallback};
+use crate::weather::{resolve_bar_locations, resolve_location, BarLocation, ResolvedLocation};
+
+/// Why a weather command could not run; already logged to stderr
+#[derive(Debug, Clone)]
+pub struct Failure {
+    pub fallback: Fallback,
+    /// Plain-text description
+    pub message: String,
+    /// Bar text
+    pub text: &'static str,
+    /// Tooltip markup
+    pub tooltip: String,
+    /// Exit code for commands other than `bar`
+    pub code: i32,
+}
+
+impl Failure {
+    fn new(fallback: Fallback, text: &'static str, msg: &str, code: i32) -> Self {
+        error!("{}", msg);
+        Failure {
+            fallback,
+            message: msg.to_string(),
+            text,
+            tooltip: format!("<span foreground='#f4b8e4'>{}</span>", escape_pango(msg)),
+            code,
+        }
+    }
+
+    /// Waybar JSON for this failure
+    pub fn json(&self) -> Value {
+        waybar::fallback_json(self.text, &self.tooltip, self.fallback)
+    }
+}
+
+/// Config and location for one weather update
+pub struct WeatherContext {
+    pub cfg: Config,
+    pub diagnostics: Vec<Diagnostic>,
+    pub resolved: ResolvedLocation,
+    /// Locations for the bar from `bar_presets`; empty to show just `resolved`
+    pub bar_locations: Vec<BarLocation>,
+}
+
+/// Loads the API key from the environment or the key file
+pub fn require_key() -> Result<String, Failure> {
+    load_key().ok_or_else(|| {
+        let key_file = paths::key_file()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "~/.config/redweather/apikey".into());
+        Failure::new(
+            Fallback::NoKey,
+            "| ❓ N/A",
+            &format!("Missing OWM_API_KEY (env or {})", key_file),
+            EXIT_FAILURE,
+        )
+    })
+}
+
+/// Loads the config, applies `--preset`/`--units` and resolves the location
+///
+/// `location` is a one-off ZIP code or city that replaces the active preset.
+pub async fn load_context(
+    key: &str,
+    global: &GlobalArgs,
+    location: Option<&str>,
+) -> Result<WeatherContext, Failure> {
+    let (mut cfg, diagnostics) = load_config_with_diagnostics();
+    if let Err(msg) = apply_cli_selection(&mut cfg, global) {
+        return Err(Failure::new(
+            Fallback::UnknownPreset,
+            "| ❓ Preset",
+            &msg,
+            EXIT_USAGE,
+        ));
+    }
+
+    let resolved = match resolve_location(key, location, &cfg).await {
+        Ok(Some(r)) => r,
+        Ok(None) => {
+            if let Some(query) = location {
+                return Err(Failure::new(
+                    Fallback::NotFound,
+                    "| ❓ Error",
+                    &format!("No location found for \"{}\"", query),
+                    EXIT_FAILURE,
+                ));
+            }
+            if has_errors(&diagnostics) {
+                // A broken config.toml looks exactly like an empty one; say so
+                let message = "Config file has errors; run `redweather config check`.";
+                error!("{}", message);
+                let colors = ColorsResolved::from_config(&cfg.colors);
+                let ui_resolved = UiConfigResolved::from_config(&cfg.ui);
+                let tooltip =
+                    format_config_diagnostics(&diagnostics, &colors, ui_resolved.font_size);
+                return Err(Failure {
+                    fallback: Fallback::ConfigError,
+                    message: message.into(),
+                    text: "| ⚠ Config",
+                    tooltip: tooltip.join("\n"),
+                    code: EXIT_FAILURE,
+                });
+            }
+            let message = "No location configured. Run `redweather settings` to set your location.";
+            error!("{}", message);
+            return Err(Failure {
+                fallback: Fallback::NoLocation,
+                message: message.into(),
+                text: "| ❓ Setup",
+                tooltip: "<span foreground='#f4b8e4'>Right-click to set your location</span>"
+                    .into(),
+                code: EXIT_FAILURE,
+            });
+        }
+        Err(e) => {
+            return Err(Failure::new(
+                Fallback::LocationError,
+                "| ❓ Error",
+                &format!("Location error: {}", e),
+                EXIT_FAILURE,
+            ))
+        }
+    };
+
+    // Asking for one location on the command line overrides the list
+    let bar_locations = if location.is_none() && global.preset.is_none() {
+        resolve_bar_locations(&cfg, key)
+    } else {
+        Vec::new()
+    };
+
+    Ok(WeatherContext {
+        cfg,
+        diagnostics,
+        resolved,
+        bar_locations,
+    })
+}
+
+/// Failure for a weather fetch that failed with nothing cached
+pub fn fetch_failure(e: &anyhow::Error) -> Failure {
+    Failure::new(
+        Fallback::FetchError,
+        "| ❓ N/A",
+        &format!("Weather error: {}", e),
+        EXIT_FAILURE,
+    )
+}
+
+/// Applies `--preset` and `--units` to the loaded config
+fn apply_cli_selection(cfg: &mut Config, global: &GlobalArgs) -> Result<(), String> {
+    if let Some(name) = &global.preset {
+        if !cfg
+            .location_presets
+            .iter()
+            .flatten()
+            .any(|p| &p.name == name)
+        {
+            return Err(format!(
+                "Unknown preset \"{}\" (see `redweather presets list`)",
+                name
+            ));
+        }
+        cfg.active_preset = Some(name.clone());
+    }
+    if let Some(units) = global.units {
+        // Wins over per-preset units as well; see LocationSettings::resolve
+        cfg.units_override = Some(units.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use crate::config::{LocationSettings, Units};
+    use clap::Parser;
+
+    #[test]
+    fn test_units_flag_converts_global_bands() {
+        let mut cfg: Config = toml::from_str(
+            r##"
+units = "imperial"
+temp_bands = [{ max = 50.0, color = "#8caaee" }, { max = 500.0, color = "#e78284" }]
+
+[[location_presets]]
+name = "cabin"
+lat = 1.0
+lon = 2.0
+label = "Cabin"
+units = "imperial"
+"##,
+        )
+        .unwrap();
+        let cli = Cli::parse_from(["redweather", "--units", "metric", "bar"]);
+        apply_cli_selection(&mut cfg, &cli.global).unwrap();
+
+        let global = LocationSettings::resolve(&cfg, None, "key");
+        assert_eq!(global.units, Units::Metric);
+        assert_eq!(global.temp_bands[0].max, 10.0);
+        assert_eq!(global.temp_bands[1].max, 260.0);
+        let preset = &cfg.location_presets.as_ref().unwrap()[0];
+        let cabin = LocationSettings::resolve(&cfg, Some(preset), "key");
+        assert_eq!(cabin.units, Units::Metric);
+        assert_eq!(cabin.temp_bands[0].max, 10.0);
+    }
+}
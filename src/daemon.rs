@@ -0,0 +1,544 @@
+//! `redweather daemon`: a long-running bar module for Waybar's continuous mode
+//!
+//! Instead of Waybar starting a process every interval, the daemon keeps the
+//! config and location in memory and prints a JSON line whenever the bar
+//! should change, including once a minute as the data's age moves on. Signals:
+//! - `SIGUSR1` refreshes now, skipping the cache
+//! - `SIGUSR2` switches to the next preset (and saves it, like `presets next`)
+//! - `SIGHUP` re-reads the config file and API key
+//!
+//! With `--output i3bar` it speaks the i3bar protocol instead, and clicks do
+//! the same (see [`crate::i3bar`]).
+//!
+//! If `redweather serve` is running, the daemon relays its updates instead of
+//! fetching on its own, and forwards the signals to it as requests.
+
+use anyhow::Context;
+use chrono::Utc;
+use serde_json::Value;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::time::Duration;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::{error, info, warn};
+
+use crate::cli::{Cli, DaemonArgs, GlobalArgs, EXIT_FAILURE, EXIT_OK};
+use crate::config::{cycle_active_preset, load_config_file, update_active_preset, LocationPreset};
+use crate::context::{fetch_failure, load_context, require_key, WeatherContext};
+use crate::i3bar;
+use crate::ipc::{self, Request};
+use crate::output::{self, OutputFormat};
+use crate::waybar;
+use crate::weather::{load_weather, load_weather_all, FetchedWeather};
+
+/// Shortest allowed refresh interval, to stay well inside the API's rate limits
+pub const MIN_INTERVAL_SECS: u64 = 60;
+/// First retry delay after a failure; doubles with each failure in a row
+const RETRY_BASE_SECS: u64 = 30;
+/// Scheduled refreshes are moved by up to this fraction of the delay
+const JITTER: f64 = 0.1;
+/// How often the bar is redrawn between refreshes, so "updated … ago" and
+/// the stale marker keep up with the data's age
+const RERENDER_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The signals the daemon and server react to
+pub struct Signals {
+    pub refresh: Signal,
+    pub next_preset: Signal,
+    pub reload: Signal,
+}
+
+impl Signals {
+    /// Installs handlers for `SIGUSR1`, `SIGUSR2` and `SIGHUP`
+    pub fn install() -> anyhow::Result<Self> {
+        let install = |kind| signal(kind).context("Cannot install signal handlers");
+        Ok(Signals {
+            refresh: install(SignalKind::user_defined1())?,
+            next_preset: install(SignalKind::user_defined2())?,
+            reload: install(SignalKind::hangup())?,
+        })
+    }
+}
+
+/// Something a signal or an i3bar click asks the daemon to do
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Fetch now, skipping the cache
+    Refresh,
+    /// Move through the preset list and save the result as active
+    Cycle(isize),
+    /// Re-read the config file and API key
+    Reload,
+}
+
+impl Action {
+    /// The same action as a request to `redweather serve`
+    fn request(self) -> Request {
+        match self {
+            Action::Refresh => Request::Refresh,
+            Action::Cycle(step) => Request::Preset {
+                name: None,
+                step: Some(step),
+            },
+            Action::Reload => Request::Reload,
+        }
+    }
+}
+
+/// Signals, plus click events with `--output i3bar`
+struct Inputs {
+    signals: Signals,
+    clicks: Option<UnboundedReceiver<Action>>,
+}
+
+impl Inputs {
+    async fn next(&mut self) -> Action {
+        tokio::select! {
+            _ = self.signals.refresh.recv() => Action::Refresh,
+            _ = self.signals.next_preset.recv() => Action::Cycle(1),
+            _ = self.signals.reload.recv() => Action::Reload,
+            Some(action) = next_click(&mut self.clicks) => action,
+        }
+    }
+}
+
+async fn next_click(clicks: &mut Option<UnboundedReceiver<Action>>) -> Option<Action> {
+    match clicks {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Runs the daemon until stdout is closed; returns the process exit code
+pub async fn run_daemon(cli: &Cli, args: &DaemonArgs) -> i32 {
+    let signals = match Signals::install() {
+        Ok(s) => s,
+        Err(e) => {
+            error!("{:#}", e);
+            return EXIT_FAILURE;
+        }
+    };
+    let format = cli.global.output;
+    let clicks = (format == OutputFormat::I3bar).then(|| i3bar::spawn_click_reader(&cli.global));
+    let mut inputs = Inputs { signals, clicks };
+    let mut out = LineWriter::new(format);
+
+    if ipc::can_attach(cli) {
+        if let Some(updates) = ipc::spawn_subscriber() {
+            info!("Attached to redweather serve");
+            if !relay(updates, &mut inputs, &mut out).await {
+                return EXIT_OK;
+            }
+            warn!("redweather serve went away; fetching directly");
+        }
+    }
+
+    let mut updater = Updater::new(cli.global.clone(), cli.location(), args.interval);
+    let mut force = cli.global.reload;
+    loop {
+        let update = updater.update(force).await;
+        force = false;
+        if !out.write(&update.bar) {
+            // The bar went away
+            return EXIT_OK;
+        }
+
+        let next_update = tokio::time::Instant::now() + update.delay;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(next_update) => break,
+                _ = tokio::time::sleep(RERENDER_INTERVAL) => {
+                    // Unchanged lines are not written again
+                    if let Some(bar) = updater.render() {
+                        if !out.write(&bar) {
+                            return EXIT_OK;
+                        }
+                    }
+                }
+                action = inputs.next() => {
+                    match action {
+                        Action::Refresh => force = true,
+                        Action::Cycle(step) => {
+                            if let Err(e) = updater.select_preset(PresetChange::Cycle(step)) {
+                                warn!("Cannot switch preset: {}", e);
+                            }
+                        }
+                        Action::Reload => {
+                            info!("Reloading config");
+                            updater.reset();
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Prints the bar from a server's updates until it disconnects
+///
+/// Returns false once stdout is closed.
+async fn relay(
+    mut updates: UnboundedReceiver<ipc::Snapshot>,
+    inputs: &mut Inputs,
+    out: &mut LineWriter,
+) -> bool {
+    loop {
+        let request = tokio::select! {
+            snapshot = updates.recv() => match snapshot {
+                Some(s) => {
+                    if !out.write(&s.bar) {
+                        return false;
+                    }
+                    continue;
+                }
+                None => return true,
+            },
+            action = inputs.next() => action.request(),
+        };
+        // The server answers once the resulting update is published
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = ipc::request(&request) {
+                warn!("redweather serve: {}", e);
+            }
+        });
+    }
+}
+
+/// Writes the bar to stdout, skipping output identical to the last
+struct LineWriter {
+    format: OutputFormat,
+    last: String,
+}
+
+impl LineWriter {
+    fn new(format: OutputFormat) -> Self {
+        LineWriter {
+            format,
+            last: String::new(),
+        }
+    }
+
+    /// Returns false if stdout is closed
+    fn write(&mut self, json: &Value) -> bool {
+        let line = output::render(json, self.format);
+        if line == self.last {
+            return true;
+        }
+        let mut out = std::io::stdout().lock();
+        let written = if self.format == OutputFormat::I3bar {
+            // One status line per element of an endless array
+            let prefix = if self.last.is_empty() {
+                format!("{}\n[\n", i3bar::HEADER)
+            } else {
+                ",".to_string()
+            };
+            writeln!(out, "{}[{}]", prefix, line)
+        } else {
+            writeln!(out, "{}", line)
+        };
+        if written.and_then(|_| out.flush()).is_err() {
+            return false;
+        }
+        self.last = line;
+        true
+    }
+}
+
+/// A preset switch requested by a signal or a client
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresetChange {
+    Activate(String),
+    Cycle(isize),
+}
+
+/// The result of one update
+pub struct Update {
+    /// Waybar JSON, a fallback if there is no weather
+    pub bar: Value,
+    pub weather: Option<FetchedWeather>,
+    /// Why there is no weather, or why some location has none
+    pub error: Option<String>,
+    /// How long to wait before the next update
+    pub delay: Duration,
+}
+
+/// The weather the bar was last built from
+enum Shown {
+    One(Box<FetchedWeather>),
+    Several(Vec<anyhow::Result<FetchedWeather>>),
+}
+
+/// Keeps the config and location loaded and decides when to refetch
+pub struct Updater {
+    global: GlobalArgs,
+    /// One-off location from the command line, dropped when switching presets
+    location: Option<String>,
+    interval: u64,
+    /// Loaded on first use and after `reset`
+    context: Option<WeatherContext>,
+    /// When the data currently shown was fetched
+    fetched_at: Option<i64>,
+    /// What the bar shows, to redraw it between updates
+    shown: Option<Shown>,
+    /// Failed updates in a row, for the back-off
+    failures: u32,
+}
+
+impl Updater {
+    pub fn new(global: GlobalArgs, location: Option<&str>, interval: u64) -> Self {
+        Updater {
+            global,
+            location: location.map(str::to_string),
+            interval,
+            context: None,
+            fetched_at: None,
+            shown: None,
+            failures: 0,
+        }
+    }
+
+    /// The loaded config and location, if loading succeeded
+    pub fn context(&self) -> Option<&WeatherContext> {
+        self.context.as_ref()
+    }
+
+    /// Name of the preset being shown, unless a one-off location is
+    pub fn preset_name(&self) -> Option<&str> {
+        if self.location.is_some() {
+            return None;
+        }
+        let cfg = &self.context.as_ref()?.cfg;
+        let presets = cfg.location_presets.as_deref()?;
+        Some(presets[cfg.active_preset_index()?].name.as_str())
+    }
+
+    /// Loads or fetches the weather and builds the bar JSON
+    ///
+    /// `force` skips the cache. Otherwise the cache is used until the data is
+    /// `interval` seconds old.
+    pub async fn update(&mut self, force: bool) -> Update {
+        if self.context.is_none() {
+            let loaded = match require_key() {
+                Ok(key) => load_context(&key, &self.global, self.location.as_deref()).await,
+                Err(f) => Err(f),
+            };
+            match loaded {
+                Ok(ctx) => self.context = Some(ctx),
+                Err(f) => {
+                    self.shown = None;
+                    return self.failed(f.json(), Some(f.message), None);
+                }
+            }
+        }
+        let Some(ctx) = &self.context else {
+            unreachable!("context was loaded above");
+        };
+
+        let now = Utc::now().timestamp();
+        let due = self
+            .fetched_at
+            .is_some_and(|t| now - t >= self.interval as i64);
+        let reload = force || due;
+        if !ctx.bar_locations.is_empty() {
+            return self.update_several(reload, now).await;
+        }
+        match load_weather(&ctx.resolved, reload).await {
+            Ok(fetched) => {
+                let bar = waybar::bar_json(&fetched, &ctx.resolved, &ctx.cfg, &ctx.diagnostics);
+                self.fetched_at = Some(fetched.fetched_at);
+                self.shown = Some(Shown::One(Box::new(fetched.clone())));
+                if fetched.stale {
+                    return self.failed(bar, None, Some(fetched));
+                }
+                let fetched_at = fetched.fetched_at;
+                self.succeeded(bar, Some(fetched), fetched_at, now)
+            }
+            Err(e) => {
+                self.shown = None;
+                let f = fetch_failure(&e);
+                self.failed(f.json(), Some(f.message), None)
+            }
+        }
+    }
+
+    /// Builds the bar again from the weather last shown, without fetching
+    pub fn render(&self) -> Option<Value> {
+        let ctx = self.context.as_ref()?;
+        Some(match self.shown.as_ref()? {
+            Shown::One(fetched) => {
+                waybar::bar_json(fetched, &ctx.resolved, &ctx.cfg, &ctx.diagnostics)
+            }
+            Shown::Several(results) => {
+                waybar::multi_bar_json(&ctx.bar_locations, results, &ctx.cfg, &ctx.diagnostics)
+            }
+        })
+    }
+
+    /// Updates every location in `bar_presets` at once
+    async fn update_several(&mut self, reload: bool, now: i64) -> Update {
+        let Some(ctx) = &self.context else {
+            unreachable!("only called with a loaded context");
+        };
+        let results = load_weather_all(&ctx.bar_locations, reload).await;
+        let bar = waybar::multi_bar_json(&ctx.bar_locations, &results, &ctx.cfg, &ctx.diagnostics);
+
+        // Clients such as the dashboard get the active preset's weather
+        let primary = ctx.resolved.cache_key();
+        let weather = match ctx
+            .bar_locations
+            .iter()
+            .zip(&results)
+            .find(|(l, _)| l.resolved.cache_key() == primary)
+        {
+            Some((_, r)) => r.as_ref().ok().cloned(),
+            None => load_weather(&ctx.resolved, reload).await.ok(),
+        };
+        let errors: Vec<String> = results
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .map(|e| fetch_failure(e).message)
+            .collect();
+        let stale = results.iter().any(|r| r.as_ref().is_ok_and(|f| f.stale));
+
+        // The next refresh is due when the oldest data expires
+        let oldest = results
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .map(|f| f.fetched_at)
+            .min();
+        self.fetched_at = oldest;
+        self.shown = Some(Shown::Several(results));
+        match oldest {
+            Some(fetched_at) if errors.is_empty() && !stale => {
+                self.succeeded(bar, weather, fetched_at, now)
+            }
+            _ => self.failed(bar, errors.into_iter().next(), weather),
+        }
+    }
+
+    /// Resets the back-off and schedules a refresh for when the data expires
+    fn succeeded(
+        &mut self,
+        bar: Value,
+        weather: Option<FetchedWeather>,
+        fetched_at: i64,
+        now: i64,
+    ) -> Update {
+        self.failures = 0;
+        let age = (now - fetched_at).max(0) as u64;
+        let wait = self.interval.saturating_sub(age).max(RETRY_BASE_SECS);
+        Update {
+            bar,
+            weather,
+            error: None,
+            delay: jittered(Duration::from_secs(wait), random()),
+        }
+    }
+
+    /// Records a failure and schedules a retry with back-off
+    fn failed(
+        &mut self,
+        bar: Value,
+        error: Option<String>,
+        weather: Option<FetchedWeather>,
+    ) -> Update {
+        self.failures += 1;
+        let delay = jittered(
+            Duration::from_secs(backoff_secs(self.failures, self.interval)),
+            random(),
+        );
+        warn!(
+            "Update failed ({} in a row): {}; retrying in {} s",
+            self.failures,
+            error.as_deref().unwrap_or("showing cached weather"),
+            delay.as_secs()
+        );
+        Update {
+            bar,
+            weather,
+            error,
+            delay,
+        }
+    }
+
+    /// Forgets the loaded config and location so the next update re-reads them
+    pub fn reset(&mut self) {
+        self.context = None;
+        self.fetched_at = None;
+        self.shown = None;
+        self.failures = 0;
+    }
+
+    /// Saves a new active preset; command-line location choices no longer apply
+    pub fn select_preset(&mut self, change: PresetChange) -> anyhow::Result<LocationPreset> {
+        let preset = match change {
+            PresetChange::Cycle(step) => cycle_active_preset(step)?,
+            PresetChange::Activate(name) => {
+                let preset = load_config_file()
+                    .location_presets
+                    .into_iter()
+                    .flatten()
+                    .find(|p| p.name == name)
+                    .with_context(|| {
+                        format!(
+                            "Unknown preset \"{}\" (see `redweather presets list`)",
+                            name
+                        )
+                    })?;
+                update_active_preset(&name)?;
+                preset
+            }
+        };
+        info!("Switched to preset \"{}\"", preset.name);
+        self.global.preset = None;
+        self.location = None;
+        self.reset();
+        Ok(preset)
+    }
+}
+
+/// Seconds to wait after `failures` failed updates in a row
+fn backoff_secs(failures: u32, interval: u64) -> u64 {
+    let exp = failures.saturating_sub(1).min(16);
+    (RETRY_BASE_SECS << exp).min(interval)
+}
+
+/// Moves `delay` by up to ±[`JITTER`], using `rand` as the random source
+///
+/// Keeps several bars (or machines) started together from refreshing in step.
+fn jittered(delay: Duration, rand: u64) -> Duration {
+    let unit = rand as f64 / u64::MAX as f64 * 2.0 - 1.0;
+    delay.mul_f64(1.0 + unit * JITTER)
+}
+
+/// A random number from the standard library's per-process hash keys
+fn random() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(Utc::now().timestamp_nanos_opt().unwrap_or_default() as u128);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_interval() {
+        let delays: Vec<u64> = (1..=6).map(|n| backoff_secs(n, 600)).collect();
+        assert_eq!(delays, vec![30, 60, 120, 240, 480, 600]);
+        assert_eq!(backoff_secs(100, 600), 600);
+        assert_eq!(backoff_secs(1, 60), 30);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let base = Duration::from_secs(600);
+        assert_eq!(jittered(base, 0), Duration::from_secs(540));
+        assert_eq!(jittered(base, u64::MAX), Duration::from_secs(660));
+        let mid = jittered(base, u64::MAX / 2);
+        assert!(mid.abs_diff(base) < Duration::from_millis(1));
+    }
+}
@@ -7,20 +7,32 @@ use gtk::{
     Application, ApplicationWindow, Box as GtkBox, Button, DrawingArea, FlowBox, GestureDrag,
     HeaderBar, Label, Orientation, ScrolledWindow, Separator, Spinner,
 };
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
+use tracing::warn;
 
-use crate::config::{load_config, Config, DashboardConfigResolved, Units};
+use crate::config::{
+    load_config, Config, DashboardConfigResolved, LocationSettings, TimeSettings, Units,
+    MAX_FORECAST_DAYS, MIN_FORECAST_DAYS,
+};
 use crate::gauges::{create_arc_gauge, create_compass_gauge};
 use crate::graph::{create_hourly_graph_plot, create_hourly_y_axis, YAxisMetrics};
+use crate::ipc;
 use crate::ui::show_location_dialog;
-use crate::utils::{deg_to_dir, fmt_time, is_night, moon_phase_icon, pick_icon};
-use crate::weather::{fetch_weather_for_loc, resolve_location, ApiResponse, Location, WeatherDesc};
+use crate::utils::{
+    deg_to_dir, fmt_time, format_ago, is_night, is_night_at, moon_phase_icon, pick_icon,
+    SECONDS_PER_DAY,
+};
+use crate::weather::{
+    load_weather, resolve_location, ApiResponse, FetchedWeather, Location, ResolvedLocation,
+    WeatherDesc,
+};
 // Constants
 const MAX_UVI: f64 = 11.0;
+/// Seconds between refresh attempts while the dashboard shows offline data
+const OFFLINE_RETRY_SECS: u32 = 60;
 const SPINNER_SIZE: i32 = 64;
 const SECONDS_PER_HOUR: f64 = 3600.0;
-const SECONDS_PER_DAY: i64 = 86_400;
 
 // Humidity thresholds
 const HUMIDITY_MUGGY: u8 = 70;
@@ -31,6 +43,11 @@ const UVI_VERY_HIGH: f64 = 8.0;
 const UVI_HIGH: f64 = 6.0;
 const UVI_MODERATE: f64 = 3.0;
 
+thread_local! {
+    /// Counts `spawn_weather_fetch` calls, so only the latest updates the window
+    static FETCH_GENERATION: Cell<u64> = const { Cell::new(0) };
+}
+
 /// Helper to get color based on temperature
 fn get_temp_color(temp: f64, units: Units) -> &'static str {
     let temp_f = if units == Units::Metric {
@@ -54,49 +71,87 @@ fn get_temp_color(temp: f64, units: Units) -> &'static str {
 
 
 /// Spawns an async task to fetch weather data and update the UI
+///
+/// If the fetch fails, cached data is shown under an offline banner, as long
+/// as it is recent enough for the bar to show it too. Until a fetch succeeds,
+/// it is retried every `OFFLINE_RETRY_SECS`; a later call stops the retries.
 fn spawn_weather_fetch(
-    api_key: Rc<String>,
-    location: Rc<Location>,
-    units: Units,
+    resolved: Rc<ResolvedLocation>,
     scroll_weak: glib::WeakRef<ScrolledWindow>,
     config: Rc<Config>,
 ) {
-    let api_key = api_key.to_string();
-    let location = (*location).clone();
+    let resolved = (*resolved).clone();
     let config_clone = (*config).clone(); // Config needs to be cloneable
+    let generation = FETCH_GENERATION.with(|g| {
+        g.set(g.get() + 1);
+        g.get()
+    });
 
     glib::spawn_future_local(async move {
-        // Offload network request to Tokio thread pool to avoid blocking GTK loop
-        let api_key_for_fetch = api_key.clone();
-        let location_for_fetch = location.clone();
-
-        let fetch_result = tokio::spawn(async move {
-            fetch_weather_for_loc(&api_key_for_fetch, &location_for_fetch, units).await
-        })
-        .await;
-
-        match fetch_result {
-            Ok(Ok(new_data)) => {
-                if let Some(scroll) = scroll_weak.upgrade() {
-                    let config_rc = Rc::new(config_clone);
-                    refresh_content(&scroll, Some(&new_data), &location, units, &config_rc);
-                }
+        let config_rc = Rc::new(config_clone);
+        loop {
+            // Offload network request to Tokio thread pool to avoid blocking GTK loop
+            let resolved_for_fetch = resolved.clone();
+            let fetch_result =
+                tokio::spawn(async move { load_weather(&resolved_for_fetch, true).await }).await;
+
+            // A newer fetch (e.g. for another location) owns the window now
+            if FETCH_GENERATION.with(Cell::get) != generation {
+                break;
             }
-            Ok(Err(e)) => {
-                if let Some(scroll) = scroll_weak.upgrade() {
-                    show_error_ui(&scroll, &e.to_string());
+            let Some(scroll) = scroll_weak.upgrade() else {
+                break;
+            };
+            match fetch_result {
+                Ok(Ok(fetched)) => {
+                    refresh_content(&scroll, Some(&fetched), &resolved, &config_rc);
+                    if !fetched.is_outdated(resolved.settings.cache.ttl_secs) {
+                        break;
+                    }
                 }
-            }
-            Err(e) => {
-                // Join error (Tokio panic)
-                if let Some(scroll) = scroll_weak.upgrade() {
+                Ok(Err(e)) => show_error_ui(&scroll, &e.to_string()),
+                Err(e) => {
+                    // Join error (Tokio panic)
                     show_error_ui(&scroll, &format!("Internal thread error: {}", e));
+                    break;
                 }
             }
+            drop(scroll);
+            glib::timeout_future_seconds(OFFLINE_RETRY_SECS).await;
         }
     });
 }
 
+/// Shows the updates of a running `redweather serve`, if there is one
+///
+/// Returns false if no server is running.
+fn follow_server(api_key: String, scroll_weak: glib::WeakRef<ScrolledWindow>) -> bool {
+    let Some(mut updates) = ipc::spawn_subscriber() else {
+        return false;
+    };
+
+    glib::spawn_future_local(async move {
+        while let Some(snapshot) = updates.recv().await {
+            let Some(scroll) = scroll_weak.upgrade() else {
+                break;
+            };
+            match &snapshot.weather {
+                Some(weather) => {
+                    // Re-read the config: the update may follow a preset change
+                    let config = load_config();
+                    let resolved = weather.resolve(&config, &api_key);
+                    refresh_content(&scroll, Some(&weather.fetched()), &resolved, &config);
+                }
+                None => show_error_ui(
+                    &scroll,
+                    snapshot.error.as_deref().unwrap_or("redweather serve has no weather"),
+                ),
+            }
+        }
+    });
+    true
+}
+
 /// Handles location change by resolving new location and fetching weather
 fn handle_location_change(api_key: Rc<String>, scroll_weak: glib::WeakRef<ScrolledWindow>) {
     let api_key_str = api_key.to_string();
@@ -104,9 +159,7 @@ fn handle_location_change(api_key: Rc<String>, scroll_weak: glib::WeakRef<Scroll
     glib::spawn_future_local(async move {
         let new_config = load_config();
         let config_clone_for_resolve = new_config.clone();
-        let config_clone_for_fetch = new_config.clone();
         let api_key_for_resolve = api_key_str.clone();
-        let api_key_for_fetch = api_key_str.clone();
 
         // Offload location resolution
         let resolve_result = tokio::spawn(async move {
@@ -118,51 +171,9 @@ fn handle_location_change(api_key: Rc<String>, scroll_weak: glib::WeakRef<Scroll
             Ok(Ok(Some(new_location))) => {
                 // Show loading state immediately
                 if let Some(scroll) = scroll_weak.upgrade() {
-                    refresh_content(
-                        &scroll,
-                        None,
-                        &new_location,
-                        new_config.units,
-                        &Rc::new(new_config.clone()),
-                    );
-                }
-
-                let location_clone = new_location.clone();
-
-                // Offload weather fetch
-                let fetch_result = tokio::spawn(async move {
-                    fetch_weather_for_loc(
-                        &api_key_for_fetch,
-                        &location_clone,
-                        config_clone_for_fetch.units,
-                    )
-                    .await
-                })
-                .await;
-
-                match fetch_result {
-                    Ok(Ok(new_data)) => {
-                        if let Some(scroll) = scroll_weak.upgrade() {
-                            refresh_content(
-                                &scroll,
-                                Some(&new_data),
-                                &new_location,
-                                new_config.units,
-                                &Rc::new(new_config),
-                            );
-                        }
-                    }
-                    Ok(Err(e)) => {
-                        if let Some(scroll) = scroll_weak.upgrade() {
-                            show_error_ui(&scroll, &e.to_string());
-                        }
-                    }
-                    Err(e) => {
-                        if let Some(scroll) = scroll_weak.upgrade() {
-                            show_error_ui(&scroll, &format!("Thread error: {}", e));
-                        }
-                    }
+                    refresh_content(&scroll, None, &new_location, &Rc::new(new_config.clone()));
                 }
+                spawn_weather_fetch(Rc::new(new_location), scroll_weak, Rc::new(new_config));
             }
             Ok(Ok(None)) => {
                 if let Some(scroll) = scroll_weak.upgrade() {
@@ -183,19 +194,20 @@ fn handle_location_change(api_key: Rc<String>, scroll_weak: glib::WeakRef<Scroll
     });
 }
 
+/// Opens the dashboard; with `attach`, it follows `redweather serve` when running
 pub fn run_dashboard(
-    data: Option<ApiResponse>,
-    loc: Location,
-    units: Units,
+    data: Option<FetchedWeather>,
+    resolved: ResolvedLocation,
     key: String,
     cfg: Config,
+    attach: bool,
 ) {
     let app = Application::builder()
         .application_id("com.shaun.redweather.dashboard")
         .build();
 
     let data = Rc::new(data);
-    let loc = Rc::new(loc);
+    let resolved = Rc::new(resolved);
     let key = Rc::new(key);
     let cfg = Rc::new(cfg);
 
@@ -203,10 +215,10 @@ pub fn run_dashboard(
         build_ui(
             app,
             data.clone(),
-            loc.clone(),
-            units,
+            resolved.clone(),
             key.clone(),
             cfg.clone(),
+            attach,
         );
     });
 
@@ -215,11 +227,11 @@ pub fn run_dashboard(
 
 fn build_ui(
     app: &Application,
-    data: Rc<Option<ApiResponse>>,
-    loc: Rc<Location>,
-    units: Units,
+    data: Rc<Option<FetchedWeather>>,
+    resolved: Rc<ResolvedLocation>,
     key: Rc<String>,
     cfg: Rc<Config>,
+    attach: bool,
 ) {
     let dashboard_config = DashboardConfigResolved::from_config(&cfg.dashboard);
 
@@ -235,16 +247,17 @@ fn build_ui(
         let width = win.width();
         let height = win.height();
 
-        // Load fresh config to ensure we don't overwrite other changes
-        let mut current_cfg = load_config();
-        let dash_cfg = current_cfg
-            .dashboard
-            .get_or_insert_with(crate::config::DashboardConfig::default);
-        dash_cfg.window_width = Some(width);
-        dash_cfg.window_height = Some(height);
-
-        if let Err(e) = crate::config::save_config(&current_cfg) {
-            eprintln!("Failed to save window state: {}", e);
+        // Update the file config so other changes (and overrides) aren't clobbered
+        let result = crate::config::update_config(|cfg| {
+            let dash_cfg = cfg
+                .dashboard
+                .get_or_insert_with(crate::config::DashboardConfig::default);
+            dash_cfg.window_width = Some(width);
+            dash_cfg.window_height = Some(height);
+        });
+
+        if let Err(e) = result {
+            warn!("Failed to save window state: {}", e);
         }
 
         gtk::glib::Propagation::Proceed
@@ -283,16 +296,13 @@ fn build_ui(
 
     // Initial Draw
 
-    refresh_content(&main_scroll, data.as_ref().as_ref(), &loc, units, &cfg);
+    refresh_content(&main_scroll, data.as_ref().as_ref(), &resolved, &cfg);
 
-    // Spawn background refresh
-    spawn_weather_fetch(
-        key.clone(),
-        loc.clone(),
-        units,
-        main_scroll.downgrade(),
-        cfg.clone(),
-    );
+    // Follow the shared server, else refresh in the background
+    let attached = attach && follow_server(key.to_string(), main_scroll.downgrade());
+    if !attached {
+        spawn_weather_fetch(resolved.clone(), main_scroll.downgrade(), cfg.clone());
+    }
 
     // Settings Button Logic
     let window_weak = window.downgrade();
@@ -307,7 +317,10 @@ fn build_ui(
             let api_key_for_closure = api_key.clone();
 
             show_location_dialog(&win, &api_key, &config, move || {
-                handle_location_change(api_key_for_closure.clone(), scroll_weak.clone());
+                // The dialog already told the server, which pushes the new location
+                if !attached {
+                    handle_location_change(api_key_for_closure.clone(), scroll_weak.clone());
+                }
             });
         }
     });
@@ -336,14 +349,11 @@ fn build_current_weather_section(data: &ApiResponse, units: Units) -> GtkBox {
     current_box.set_halign(gtk::Align::Center);
     current_box.add_css_class("hero-block");
 
-    let current_desc = data.current.weather.get(0).cloned().unwrap_or(WeatherDesc {
+    let current_desc = data.current.weather.first().cloned().unwrap_or(WeatherDesc {
         main: Some("Clear".into()),
         description: Some("Clear".into()),
     });
-    let (sr, ss) = sun_window_for(data.current.dt, data)
-        .or_else(|| data.current.sunrise.zip(data.current.sunset))
-        .unwrap_or((0, 0));
-    let is_night_now = is_night(data.current.dt, Some(sr), Some(ss));
+    let is_night_now = is_night_at(data.current.dt, data);
     let moon_icon = Some(moon_phase_icon(data.current.dt, data.timezone_offset));
     let icon = pick_icon(&current_desc, is_night_now, moon_icon);
 
@@ -384,7 +394,8 @@ fn build_current_weather_section(data: &ApiResponse, units: Units) -> GtkBox {
 }
 
 /// Builds the gauges section with humidity, UV, wind, and daylight gauges
-fn build_gauges_section(data: &ApiResponse, units: Units) -> GtkBox {
+fn build_gauges_section(data: &ApiResponse, settings: &LocationSettings) -> GtkBox {
+    let units = settings.units;
     let section_box = GtkBox::new(Orientation::Vertical, 10);
 
     let gauges_label = Label::new(Some("Live Gauges"));
@@ -471,8 +482,8 @@ fn build_gauges_section(data: &ApiResponse, units: Units) -> GtkBox {
     let sunrise = data.current.sunrise.unwrap_or(0);
     let sunset = data.current.sunset.unwrap_or(0);
     let daylight_caption = if sunrise > 0 && sunset > 0 && sunset > sunrise {
-        let rise = fmt_time(sunrise, data.timezone_offset, "%I:%M %p");
-        let set = fmt_time(sunset, data.timezone_offset, "%I:%M %p");
+        let rise = settings.time.time(sunrise, data.timezone_offset);
+        let set = settings.time.time(sunset, data.timezone_offset);
         format!("↑ {}  |  ↓ {}", rise, set)
     } else {
         "Sun times unavailable".into()
@@ -520,6 +531,7 @@ fn build_gauges_section(data: &ApiResponse, units: Units) -> GtkBox {
 fn build_hourly_forecast_section(
     data: &ApiResponse,
     dashboard_config: &DashboardConfigResolved,
+    time: &TimeSettings,
 ) -> GtkBox {
     let section_box = GtkBox::new(Orientation::Vertical, 10);
 
@@ -586,18 +598,15 @@ fn build_hourly_forecast_section(
             let card = GtkBox::new(Orientation::Vertical, 5);
             card.add_css_class("forecast-card");
 
-            let time_str = fmt_time(h.dt, data.timezone_offset, "%H:%M");
+            let time_str = time.time(h.dt, data.timezone_offset);
             let time_lbl = Label::new(Some(&time_str));
             time_lbl.add_css_class("forecast-time");
 
             let icon_str = h
                 .weather
-                .get(0)
+                .first()
                 .map(|desc| {
-                    let (sr, ss) = sun_window_for(h.dt, data)
-                        .or_else(|| data.current.sunrise.zip(data.current.sunset))
-                        .unwrap_or((0, 0));
-                    let night = is_night(h.dt, Some(sr), Some(ss));
+                    let night = is_night_at(h.dt, data);
                     pick_icon(
                         desc,
                         night,
@@ -664,7 +673,9 @@ fn build_daily_forecast_section(
     daily_box.set_margin_start(2);
     daily_box.set_margin_end(2);
 
-    let forecast_days = dashboard_config.forecast_days.max(5).min(12);
+    let forecast_days = dashboard_config
+        .forecast_days
+        .clamp(MIN_FORECAST_DAYS, MAX_FORECAST_DAYS);
     for (i, d) in data.daily.iter().take(forecast_days).enumerate() {
         let day_str = fmt_time(d.dt, data.timezone_offset, "%a").to_uppercase();
         let mid_dt = d.dt + 43_200; // midday heuristic
@@ -672,7 +683,7 @@ fn build_daily_forecast_section(
         let night = is_night(mid_dt, Some(sr), Some(ss));
         let icon_str = d
             .weather
-            .get(0)
+            .first()
             .map(|desc| {
                 pick_icon(
                     desc,
@@ -699,6 +710,26 @@ fn build_daily_forecast_section(
     section_box
 }
 
+/// The "offline — showing data from 09:12" banner above outdated data
+///
+/// It only says offline when the last fetch failed.
+fn build_offline_banner(fetched: &FetchedWeather, time: &TimeSettings) -> Label {
+    let when = time.time(fetched.fetched_at, fetched.data.timezone_offset);
+    let text = if fetched.stale {
+        format!("⚠ Offline — showing data from {}", when)
+    } else {
+        format!("⚠ Showing data from {}", when)
+    };
+    let banner = Label::new(Some(&text));
+    banner.set_tooltip_text(Some(&format!(
+        "Updated {}; trying again every minute",
+        format_ago(fetched.age_secs())
+    )));
+    banner.set_wrap(true);
+    banner.add_css_class("offline-banner");
+    banner
+}
+
 fn show_error_ui(scroll: &ScrolledWindow, msg: &str) {
     if let Some(_child) = scroll.child() {
         scroll.set_child(gtk::Widget::NONE);
@@ -735,13 +766,19 @@ fn show_error_ui(scroll: &ScrolledWindow, msg: &str) {
     scroll.set_child(Some(&vbox));
 }
 
+/// Shows the weather, or a spinner while there is none yet
+///
+/// Data older than the cache TTL, from cache or a failed fetch, gets an
+/// offline banner; the next successful refresh replaces it.
 fn refresh_content(
     scroll: &ScrolledWindow,
-    data_opt: Option<&ApiResponse>,
-    loc: &Location,
-    units: Units,
+    data_opt: Option<&FetchedWeather>,
+    resolved: &ResolvedLocation,
     cfg: &Config,
 ) {
+    let loc = &resolved.location;
+    let settings = &resolved.settings;
+    let units = settings.units;
     // Clear existing children
     if let Some(_child) = scroll.child() {
         scroll.set_child(gtk::Widget::NONE);
@@ -759,7 +796,7 @@ fn refresh_content(
     vbox.add_css_class("main-container");
 
     // Show spinner if no data
-    let data = match data_opt {
+    let fetched = match data_opt {
         Some(d) => d,
         None => {
             let spinner = Spinner::new();
@@ -780,15 +817,24 @@ fn refresh_content(
         }
     };
 
+    let data = &fetched.data;
+    if fetched.is_outdated(settings.cache.ttl_secs) {
+        vbox.append(&build_offline_banner(fetched, &settings.time));
+    }
+
     // Build and append all sections
     vbox.append(&build_header_section(loc, data));
     vbox.append(&build_current_weather_section(data, units));
 
     vbox.append(&create_section_divider());
-    vbox.append(&build_gauges_section(data, units));
+    vbox.append(&build_gauges_section(data, settings));
 
     vbox.append(&create_section_divider());
-    vbox.append(&build_hourly_forecast_section(data, &dashboard_config));
+    vbox.append(&build_hourly_forecast_section(
+        data,
+        &dashboard_config,
+        &settings.time,
+    ));
 
     // Forecast stays scrollable horizontally; omit extra divider to save vertical space
     vbox.append(&build_daily_forecast_section(data, &dashboard_config, units));
@@ -796,19 +842,6 @@ fn refresh_content(
     scroll.set_child(Some(&vbox));
 }
 
-fn sun_window_for(dt: i64, data: &ApiResponse) -> Option<(i64, i64)> {
-    let bucket = (dt + data.timezone_offset) / SECONDS_PER_DAY;
-    for d in &data.daily {
-        let day_bucket = (d.dt + data.timezone_offset) / SECONDS_PER_DAY;
-        if day_bucket == bucket {
-            if let (Some(sr), Some(ss)) = (d.sunrise, d.sunset) {
-                return Some((sr, ss));
-            }
-        }
-    }
-    data.current.sunrise.zip(data.current.sunset)
-}
-
 fn create_gauge_card(title: &str, gauge: DrawingArea, caption: &str, detail: &str) -> GtkBox {
     let card = GtkBox::new(Orientation::Vertical, 6);
     card.add_css_class("gauge-item");
@@ -1108,6 +1141,15 @@ const STYLE_CSS: &str = r#"
     
     .detail-title { font-size: 0.75rem; color: #9ca3af; }
 
+    .offline-banner {
+        background-color: rgba(229, 200, 144, 0.15);
+        color: #e5c890;
+        border-radius: 0.5rem;
+        padding: 0.35rem 0.75rem;
+        font-size: 0.85rem;
+        font-weight: 600;
+    }
+
     .gauge-title {
         font-size: 0.8rem;
         font-weight: 750;
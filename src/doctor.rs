@@ -0,0 +1,592 @@
+//! `redweather doctor`: checks the setup and suggests fixes
+//!
+//! Waybar swallows stderr, so when the bar shows "❓ N/A" there is no way to
+//! see why. This runs the same steps as the bar one at a time and reports
+//! each, plus the things the bar cannot see: the Waybar module config and
+//! whether the dashboard can open a window.
+
+use chrono::Utc;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::{EXIT_FAILURE, EXIT_OK};
+use crate::config::{config_path, load_config_with_diagnostics, load_key, Config};
+use crate::paths;
+use crate::utils::format_ago;
+use crate::validate::Severity;
+use crate::waybar::MAX_SIGNAL;
+use crate::weather::{cached_at, check_api_key, Location, ResolvedLocation};
+
+/// Cached data older than this many refresh periods suggests fetches are failing
+const STALE_PERIODS: i64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// The outcome of one check
+#[derive(Debug)]
+struct Check {
+    status: Status,
+    title: String,
+    detail: String,
+    /// Extra lines, e.g. each config problem
+    notes: Vec<String>,
+    /// What to do about it
+    fix: Option<String>,
+}
+
+impl Check {
+    fn new(status: Status, title: impl Into<String>, detail: impl Into<String>) -> Self {
+        Check {
+            status,
+            title: title.into(),
+            detail: detail.into(),
+            notes: Vec::new(),
+            fix: None,
+        }
+    }
+
+    fn ok(title: impl Into<String>, detail: impl Into<String>) -> Self {
+        Check::new(Status::Ok, title, detail)
+    }
+
+    fn warn(title: impl Into<String>, detail: impl Into<String>) -> Self {
+        Check::new(Status::Warn, title, detail)
+    }
+
+    fn fail(title: impl Into<String>, detail: impl Into<String>) -> Self {
+        Check::new(Status::Fail, title, detail)
+    }
+
+    fn fix(mut self, fix: impl Into<String>) -> Self {
+        self.fix = Some(fix.into());
+        self
+    }
+
+    fn print(&self) {
+        let mark = match self.status {
+            Status::Ok => "✓",
+            Status::Warn => "!",
+            Status::Fail => "✗",
+        };
+        println!("{} {}: {}", mark, self.title, self.detail);
+        for note in &self.notes {
+            println!("    {}", note);
+        }
+        if let Some(fix) = &self.fix {
+            println!("    → {}", fix);
+        }
+    }
+}
+
+/// Runs every check, printing each as it completes; returns the exit code
+pub async fn run_doctor() -> i32 {
+    let mut checks = Vec::new();
+    let mut report = |check: Check| {
+        check.print();
+        checks.push(check.status);
+    };
+
+    let (cfg, config_check) = check_config();
+    report(config_check);
+
+    let key = load_key();
+    report(check_key_source(key.is_some()));
+    let active = active_location(&cfg, key.as_deref().unwrap_or_default());
+    report(match &active {
+        Some(r) => Check::ok("Location", r.location.label.clone()),
+        None => Check::fail("Location", "none configured")
+            .fix("Run `redweather presets add home <zip|city>` or `redweather settings`"),
+    });
+    if let Some(key) = &key {
+        // A preset may bring its own key; check the one the bar uses
+        let key = active
+            .as_ref()
+            .map_or(key.as_str(), |r| &r.settings.api_key);
+        let loc = active.as_ref().map_or(
+            Location {
+                lat: 0.0,
+                lon: 0.0,
+                label: String::new(),
+            },
+            |r| r.location.clone(),
+        );
+        report(check_key_validity(key, &loc).await);
+    }
+
+    report(check_cache_dir());
+    for check in check_cache_ages(&cfg, key.as_deref().unwrap_or_default()) {
+        report(check);
+    }
+    report(check_display());
+    for check in check_waybar(&cfg) {
+        report(check);
+    }
+
+    let failed = checks.iter().filter(|s| **s == Status::Fail).count();
+    let warned = checks.iter().filter(|s| **s == Status::Warn).count();
+    println!();
+    if failed + warned == 0 {
+        println!("All checks passed.");
+    } else {
+        println!("{} problem(s), {} warning(s)", failed, warned);
+    }
+    if failed > 0 {
+        EXIT_FAILURE
+    } else {
+        EXIT_OK
+    }
+}
+
+/// Loads the config the way the bar does and reports its diagnostics
+fn check_config() -> (Config, Check) {
+    let path = match config_path() {
+        Ok(p) => p,
+        Err(e) => {
+            let check = Check::fail("Config", e.to_string()).fix("Set HOME or XDG_CONFIG_HOME");
+            return (Config::default(), check);
+        }
+    };
+    let (cfg, diagnostics) = load_config_with_diagnostics();
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let mut check = if !path.exists() {
+        Check::ok(
+            "Config",
+            format!("{} not found, using defaults", path.display()),
+        )
+    } else if errors > 0 {
+        Check::fail(
+            "Config",
+            format!("{} has {} error(s)", path.display(), errors),
+        )
+        .fix("Fix the lines above; the bar falls back to defaults for them")
+    } else if !diagnostics.is_empty() {
+        Check::warn(
+            "Config",
+            format!("{} has {} warning(s)", path.display(), diagnostics.len()),
+        )
+    } else {
+        Check::ok("Config", format!("{} is valid", path.display()))
+    };
+    check.notes = diagnostics.iter().map(|d| d.to_string()).collect();
+    (cfg, check)
+}
+
+fn check_key_source(found: bool) -> Check {
+    let key_file = paths::key_file()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| "~/.config/redweather/apikey".into());
+    if env::var("OWM_API_KEY").is_ok_and(|k| !k.trim().is_empty()) {
+        Check::ok("API key", "from OWM_API_KEY")
+    } else if found {
+        Check::ok("API key", format!("from {}", key_file))
+    } else {
+        Check::fail("API key", "not found").fix(format!(
+            "Get a key at https://home.openweathermap.org/api_keys and set OWM_API_KEY or save it to {}",
+            key_file
+        ))
+    }
+}
+
+/// Asks the provider about the key with one request
+async fn check_key_validity(key: &str, loc: &Location) -> Check {
+    const TITLE: &str = "API key check";
+    let (status, message) = match check_api_key(key, loc).await {
+        Ok(r) => r,
+        Err(e) => {
+            return Check::warn(TITLE, format!("could not reach OpenWeatherMap: {}", e))
+                .fix("Check the network connection; the bar shows cached data meanwhile")
+        }
+    };
+    let detail = match &message {
+        Some(m) => format!("{}: {}", status, m),
+        None => status.to_string(),
+    };
+    match status.as_u16() {
+        200..=299 => Check::ok(TITLE, "accepted by OpenWeatherMap (One Call 3.0)"),
+        401 => Check::fail(TITLE, format!("rejected ({})", detail)).fix(
+            "Check the key at https://home.openweathermap.org/api_keys; One Call 3.0 needs \
+             the \"One Call by Call\" subscription, and new keys take up to a few hours to activate",
+        ),
+        429 => Check::warn(TITLE, format!("rate limited ({})", detail))
+            .fix("Raise the refresh interval or run one `redweather serve` for all bars"),
+        _ => Check::warn(TITLE, format!("unexpected response ({})", detail)),
+    }
+}
+
+/// The location the bar shows, with its settings
+fn active_location(cfg: &Config, key: &str) -> Option<ResolvedLocation> {
+    let presets = cfg.location_presets.as_deref().unwrap_or(&[]);
+    let preset = cfg
+        .active_preset_index()
+        .and_then(|i| presets.get(i))
+        .or_else(|| presets.first())?;
+    Some(ResolvedLocation::from_preset(cfg, preset, key))
+}
+
+fn check_cache_dir() -> Check {
+    const TITLE: &str = "Cache directory";
+    let Some(dir) = paths::cache_dir() else {
+        return Check::fail(TITLE, "cannot determine it").fix("Set HOME or XDG_CACHE_HOME");
+    };
+    let probe = dir.join(".doctor-probe");
+    let result = fs::create_dir_all(&dir).and_then(|()| fs::write(&probe, b"ok"));
+    let _ = fs::remove_file(&probe);
+    match result {
+        Ok(()) => Check::ok(TITLE, format!("{} is writable", dir.display())),
+        Err(e) => Check::fail(TITLE, format!("cannot write to {}: {}", dir.display(), e))
+            .fix("Fix the directory's permissions or point XDG_CACHE_HOME elsewhere"),
+    }
+}
+
+/// Reports how old each preset's cached weather is
+fn check_cache_ages(cfg: &Config, key: &str) -> Vec<Check> {
+    let now = Utc::now().timestamp();
+    cfg.location_presets
+        .iter()
+        .flatten()
+        .map(|p| {
+            let title = format!("Cache for \"{}\"", p.name);
+            let resolved = ResolvedLocation::from_preset(cfg, p, key);
+            let policy = resolved.settings.cache;
+            let stale_after = STALE_PERIODS
+                .saturating_mul(policy.ttl_secs)
+                .min(policy.max_stale_secs);
+            match cached_at(&resolved.cache_key()) {
+                None => Check::warn(title, "nothing cached yet").fix(format!(
+                    "Run `redweather bar --preset {}` to fetch it",
+                    p.name
+                )),
+                Some(t) if now - t > stale_after => {
+                    let mut detail = format!("updated {}", format_ago(now - t));
+                    if now - t > policy.max_stale_secs {
+                        detail.push_str(", too old to show");
+                    }
+                    Check::warn(title, detail).fix(
+                        "If the bar is running, fetches are failing; see the API key check above",
+                    )
+                }
+                Some(t) => Check::ok(title, format!("updated {}", format_ago(now - t))),
+            }
+        })
+        .collect()
+}
+
+/// Checks for a display the dashboard and settings window can open on
+fn check_display() -> Check {
+    const TITLE: &str = "Display";
+    if !cfg!(feature = "gui") {
+        return Check::ok(
+            TITLE,
+            "not needed; built without the dashboard (`gui` feature)",
+        );
+    }
+    if let Some(name) = env::var_os("WAYLAND_DISPLAY").filter(|d| !d.is_empty()) {
+        let socket = match env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) => Path::new(&dir).join(&name),
+            None => PathBuf::from(&name),
+        };
+        return if socket.exists() {
+            Check::ok(TITLE, format!("Wayland ({})", name.to_string_lossy()))
+        } else {
+            Check::fail(
+                TITLE,
+                format!("Wayland socket {} is missing", socket.display()),
+            )
+            .fix("Run from the desktop session, or fix WAYLAND_DISPLAY and XDG_RUNTIME_DIR")
+        };
+    }
+    match env::var("DISPLAY") {
+        Ok(d) if !d.is_empty() => Check::ok(TITLE, format!("X11 ({})", d)),
+        _ => Check::warn(TITLE, "none; the dashboard and settings window cannot open")
+            .fix("Run them from the desktop session, where WAYLAND_DISPLAY or DISPLAY is set"),
+    }
+}
+
+/// Finds the Waybar config and checks every module that runs redweather
+fn check_waybar(cfg: &Config) -> Vec<Check> {
+    const TITLE: &str = "Waybar config";
+    let Some(path) = paths::waybar_config_files()
+        .into_iter()
+        .find(|p| p.exists())
+    else {
+        return vec![Check::warn(TITLE, "not found")
+            .fix("Add a custom module as shown in the README's Waybar Integration section")];
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) => {
+            return vec![Check::fail(
+                TITLE,
+                format!("cannot read {}: {}", path.display(), e),
+            )]
+        }
+    };
+    let config: Value = match serde_json::from_str(&strip_jsonc(&contents)) {
+        Ok(v) => v,
+        Err(e) => {
+            return vec![Check::warn(
+                TITLE,
+                format!("cannot parse {}: {}", path.display(), e),
+            )]
+        }
+    };
+    let signal = cfg.waybar.as_ref().and_then(|w| w.signal);
+    let checks: Vec<Check> = waybar_modules(&config)
+        .iter()
+        .map(|m| check_module(m, signal))
+        .collect();
+    if checks.is_empty() {
+        return vec![Check::warn(
+            TITLE,
+            format!("{} has no module that runs redweather", path.display()),
+        )
+        .fix("Add a custom module as shown in the README's Waybar Integration section")];
+    }
+    let mut all = vec![Check::ok(TITLE, path.display().to_string())];
+    all.extend(checks);
+    all
+}
+
+/// A custom module that runs redweather
+#[derive(Debug, Clone, PartialEq)]
+struct WaybarModule {
+    name: String,
+    exec: String,
+    json: bool,
+    interval: bool,
+    signal: Option<u64>,
+    /// Whether some bar lists it in `modules-left`/`center`/`right`
+    shown: bool,
+}
+
+/// Collects the modules running redweather from a config with one bar or several
+fn waybar_modules(config: &Value) -> Vec<WaybarModule> {
+    let bars: Vec<&Value> = match config {
+        Value::Array(bars) => bars.iter().collect(),
+        bar => vec![bar],
+    };
+    let shown = |name: &str| {
+        bars.iter().any(|bar| {
+            ["modules-left", "modules-center", "modules-right"]
+                .iter()
+                .filter_map(|k| bar.get(k)?.as_array())
+                .flatten()
+                .any(|m| m == name)
+        })
+    };
+    let mut modules = Vec::new();
+    for bar in &bars {
+        let Some(obj) = bar.as_object() else {
+            continue;
+        };
+        for (name, module) in obj {
+            let Some(exec) = module.get("exec").and_then(Value::as_str) else {
+                continue;
+            };
+            if !name.starts_with("custom/") || !exec.contains("redweather") {
+                continue;
+            }
+            if modules.iter().any(|m: &WaybarModule| &m.name == name) {
+                continue;
+            }
+            modules.push(WaybarModule {
+                name: name.clone(),
+                exec: exec.to_string(),
+                json: module.get("return-type").and_then(Value::as_str) == Some("json"),
+                interval: module.get("interval").is_some(),
+                signal: module.get("signal").and_then(Value::as_u64),
+                shown: shown(name),
+            });
+        }
+    }
+    modules
+}
+
+fn check_module(m: &WaybarModule, signal: Option<u32>) -> Check {
+    let title = format!("Waybar module {}", m.name);
+    let detail = format!("runs `{}`", m.exec);
+    if !m.json {
+        return Check::fail(title, detail).fix("Set \"return-type\": \"json\"");
+    }
+    if let Some(problem) = missing_command(&m.exec) {
+        return Check::fail(title, problem)
+            .fix("Use the full path to redweather, e.g. ~/.local/bin/redweather");
+    }
+    let continuous = m.exec.split_whitespace().any(|a| a == "daemon");
+    if continuous && m.interval {
+        return Check::warn(title, detail)
+            .fix("Remove \"interval\" so Waybar keeps `redweather daemon` running");
+    }
+    if !continuous && !m.interval {
+        return Check::warn(title, detail)
+            .fix("Add \"interval\": 600, or run `redweather daemon` to keep one process running");
+    }
+    match (m.signal, signal) {
+        (Some(a), Some(b)) if a != u64::from(b) => {
+            return Check::warn(title, detail).fix(format!(
+            "The module's \"signal\" is {} but waybar.signal in config.toml is {}; make them match",
+            a, b
+        ))
+        }
+        (Some(a), None) if a <= u64::from(MAX_SIGNAL) && !continuous => {
+            return Check::warn(title, detail).fix(format!(
+                "Set signal = {} under [waybar] in config.toml so changes refresh just this module",
+                a
+            ))
+        }
+        _ => {}
+    }
+    if !m.shown {
+        return Check::warn(title, detail).fix(format!(
+            "Add \"{}\" to modules-left, modules-center or modules-right",
+            m.name
+        ));
+    }
+    Check::ok(title, detail)
+}
+
+/// Describes why the command an `exec` line runs cannot be found, if it can't
+fn missing_command(exec: &str) -> Option<String> {
+    let program = exec.split_whitespace().next()?;
+    let path = match program.strip_prefix("~/") {
+        Some(rest) => PathBuf::from(env::var_os("HOME")?).join(rest),
+        None => PathBuf::from(program),
+    };
+    if program.contains('/') {
+        return (!path.exists()).then(|| format!("{} does not exist", path.display()));
+    }
+    let on_path = env::var_os("PATH")
+        .is_some_and(|p| env::split_paths(&p).any(|dir| dir.join(program).exists()));
+    (!on_path).then(|| format!("{} is not on PATH", program))
+}
+
+/// Turns Waybar's JSON-with-comments into plain JSON
+fn strip_jsonc(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut chars = src.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for n in chars.by_ref() {
+                    if n == '\n' {
+                        out.push(n);
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for n in chars.by_ref() {
+                    if prev == '*' && n == '/' {
+                        break;
+                    }
+                    prev = n;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    strip_trailing_commas(&out)
+}
+
+/// Drops commas directly before `}` or `]`
+fn strip_trailing_commas(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in src.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = src[i + 1..].trim_start().chars().next();
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_jsonc() {
+        let src = r#"{
+            // the bar
+            "layer": "top", /* inline */
+            "url": "https://example.com/a//b", // not a comment inside strings
+            "modules-right": ["custom/weather",],
+        }"#;
+        let v: Value = serde_json::from_str(&strip_jsonc(src)).unwrap();
+        assert_eq!(v["url"], "https://example.com/a//b");
+        assert_eq!(v["modules-right"][0], "custom/weather");
+    }
+
+    #[test]
+    fn test_waybar_modules() {
+        let config = serde_json::json!([
+            {
+                "modules-right": ["clock", "custom/weather"],
+                "custom/weather": {
+                    "exec": "redweather daemon",
+                    "return-type": "json",
+                    "signal": 8
+                },
+                "custom/other": {"exec": "date"}
+            },
+            {
+                "custom/cabin": {"exec": "redweather bar --preset cabin", "interval": 600}
+            }
+        ]);
+        let modules = waybar_modules(&config);
+        assert_eq!(modules.len(), 2);
+        assert_eq!(
+            modules[0],
+            WaybarModule {
+                name: "custom/weather".into(),
+                exec: "redweather daemon".into(),
+                json: true,
+                interval: false,
+                signal: Some(8),
+                shown: true,
+            }
+        );
+        assert!(!modules[1].json && modules[1].interval && !modules[1].shown);
+        assert_eq!(check_module(&modules[1], None).status, Status::Fail);
+    }
+}
@@ -0,0 +1,387 @@
+//! Machine-readable weather for `redweather json`
+//!
+//! The structs here are the public JSON schema, documented in README.md. They
+//! are built from the provider data rather than serializing the cache, so the
+//! output stays the same if the cache format or provider changes. Bump
+//! [`SCHEMA_VERSION`] when renaming or removing a field; adding fields is fine.
+
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::config::Units;
+use crate::utils::{deg_to_dir, is_night, is_night_at, moon_phase_icon, pick_icon};
+use crate::weather::{FetchedWeather, ResolvedLocation, WeatherDesc, PROVIDER};
+
+/// Version of the JSON schema below
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The document `redweather json` prints
+#[derive(Debug, Serialize)]
+pub struct WeatherExport {
+    pub schema_version: u32,
+    pub location: ExportLocation,
+    pub units: ExportUnits,
+    pub current: ExportCurrent,
+    pub hourly: Vec<ExportHour>,
+    pub daily: Vec<ExportDay>,
+    pub fetch: ExportFetch,
+}
+
+/// Where the forecast is for
+#[derive(Debug, Serialize)]
+pub struct ExportLocation {
+    pub label: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// Offset of the location's time zone from UTC
+    pub utc_offset_secs: i64,
+}
+
+/// Unit labels for every measurement in the document
+#[derive(Debug, Serialize)]
+pub struct ExportUnits {
+    pub system: &'static str,
+    pub temperature: &'static str,
+    pub speed: &'static str,
+    pub distance: &'static str,
+    pub pressure: &'static str,
+}
+
+/// A weather condition as the provider describes it
+#[derive(Debug, Serialize)]
+pub struct ExportCondition {
+    pub main: Option<String>,
+    pub description: Option<String>,
+    pub icon: String,
+}
+
+/// Wind speed, gusts and direction
+#[derive(Debug, Serialize)]
+pub struct ExportWind {
+    pub speed: Option<f64>,
+    /// Gust speed, if the provider reports gusts
+    pub gust: Option<f64>,
+    pub direction_deg: Option<i64>,
+    /// Compass point such as "SW"
+    pub direction: Option<String>,
+}
+
+/// Current conditions
+#[derive(Debug, Serialize)]
+pub struct ExportCurrent {
+    pub time: String,
+    pub temp: f64,
+    pub feels_like: Option<f64>,
+    pub humidity: Option<u8>,
+    pub pressure: Option<i64>,
+    pub uv_index: Option<f64>,
+    pub visibility: Option<f64>,
+    pub wind: ExportWind,
+    /// Chance of precipitation in the coming hour, 0-100
+    pub precipitation_chance: Option<u8>,
+    pub condition: ExportCondition,
+    pub sunrise: Option<String>,
+    pub sunset: Option<String>,
+    pub is_night: bool,
+}
+
+/// One hour of the hourly forecast
+#[derive(Debug, Serialize)]
+pub struct ExportHour {
+    pub time: String,
+    pub temp: f64,
+    pub humidity: Option<u8>,
+    pub pressure: Option<i64>,
+    pub uv_index: Option<f64>,
+    pub precipitation_chance: Option<u8>,
+    pub wind: ExportWind,
+    pub condition: ExportCondition,
+}
+
+/// One day of the daily forecast
+#[derive(Debug, Serialize)]
+pub struct ExportDay {
+    /// Calendar date at the location, YYYY-MM-DD
+    pub date: String,
+    pub temp_min: Option<f64>,
+    pub temp_max: Option<f64>,
+    pub temp_day: Option<f64>,
+    pub humidity: Option<u8>,
+    pub pressure: Option<i64>,
+    pub uv_index: Option<f64>,
+    pub precipitation_chance: Option<u8>,
+    pub sunrise: Option<String>,
+    pub sunset: Option<String>,
+    pub condition: ExportCondition,
+}
+
+/// Where the data came from and when
+#[derive(Debug, Serialize)]
+pub struct ExportFetch {
+    pub provider: &'static str,
+    /// When the data was fetched from the provider, in UTC
+    pub fetched_at: String,
+    pub age_secs: i64,
+    /// True when the last fetch failed and older cached data is shown, or the
+    /// data is older than the cache TTL
+    pub stale: bool,
+}
+
+/// Builds the export document; `now` is the current Unix time
+pub fn weather_export(
+    fetched: &FetchedWeather,
+    resolved: &ResolvedLocation,
+    now: i64,
+) -> WeatherExport {
+    let data = &fetched.data;
+    let units = resolved.settings.units;
+    let tz = FixedOffset::east_opt(data.timezone_offset as i32)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).expect("zero offset is valid"));
+    let time = |dt: i64| local_time(dt, &tz);
+    let cur = &data.current;
+    let night = is_night(cur.dt, cur.sunrise, cur.sunset);
+
+    let current = ExportCurrent {
+        time: time(cur.dt),
+        temp: cur.temp,
+        feels_like: cur.feels_like,
+        humidity: cur.humidity,
+        pressure: cur.pressure,
+        uv_index: cur.uvi,
+        visibility: cur.visibility.map(|m| {
+            let d = match units {
+                Units::Imperial => m as f64 / 1609.34,
+                Units::Metric => m as f64 / 1000.0,
+            };
+            (d * 10.0).round() / 10.0
+        }),
+        wind: wind(cur.wind_speed, cur.wind_gust, cur.wind_deg),
+        precipitation_chance: percent(data.hourly.first().and_then(|h| h.pop)),
+        condition: condition(
+            &cur.weather,
+            night,
+            Some(moon_phase_icon(cur.dt, data.timezone_offset)),
+        ),
+        sunrise: cur.sunrise.map(time),
+        sunset: cur.sunset.map(time),
+        is_night: night,
+    };
+
+    let hourly = data
+        .hourly
+        .iter()
+        .map(|h| ExportHour {
+            time: time(h.dt),
+            temp: h.temp,
+            humidity: h.humidity,
+            pressure: h.pressure,
+            uv_index: h.uvi,
+            precipitation_chance: percent(h.pop),
+            wind: wind(h.wind_speed, h.wind_gust, h.wind_deg),
+            condition: condition(&h.weather, is_night_at(h.dt, data), None),
+        })
+        .collect();
+
+    let daily = data
+        .daily
+        .iter()
+        .map(|d| ExportDay {
+            date: DateTime::from_timestamp(d.dt, 0)
+                .map(|t| t.with_timezone(&tz).format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            temp_min: d.temp.min,
+            temp_max: d.temp.max,
+            temp_day: d.temp.day,
+            humidity: d.humidity,
+            pressure: d.pressure,
+            uv_index: d.uvi,
+            precipitation_chance: percent(d.pop),
+            sunrise: d.sunrise.map(time),
+            sunset: d.sunset.map(time),
+            condition: condition(&d.weather, false, None),
+        })
+        .collect();
+
+    let (temperature, speed, distance) = match units {
+        Units::Imperial => ("°F", "mph", "mi"),
+        Units::Metric => ("°C", "m/s", "km"),
+    };
+
+    WeatherExport {
+        schema_version: SCHEMA_VERSION,
+        location: ExportLocation {
+            label: resolved.location.label.clone(),
+            lat: resolved.location.lat,
+            lon: resolved.location.lon,
+            utc_offset_secs: data.timezone_offset,
+        },
+        units: ExportUnits {
+            system: units.as_str(),
+            temperature,
+            speed,
+            distance,
+            pressure: "hPa",
+        },
+        current,
+        hourly,
+        daily,
+        fetch: ExportFetch {
+            provider: PROVIDER,
+            fetched_at: DateTime::<Utc>::from_timestamp(fetched.fetched_at, 0)
+                .unwrap_or_default()
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            age_secs: (now - fetched.fetched_at).max(0),
+            stale: fetched.stale || now - fetched.fetched_at > resolved.settings.cache.ttl_secs,
+        },
+    }
+}
+
+/// Formats a Unix timestamp as RFC 3339 in the location's time zone
+fn local_time(dt: i64, tz: &FixedOffset) -> String {
+    DateTime::from_timestamp(dt, 0)
+        .unwrap_or_default()
+        .with_timezone(tz)
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Converts a 0.0-1.0 probability to a whole percentage
+fn percent(pop: Option<f64>) -> Option<u8> {
+    pop.map(|p| (p.clamp(0.0, 1.0) * 100.0).round() as u8)
+}
+
+fn wind(speed: Option<f64>, gust: Option<f64>, deg: Option<i64>) -> ExportWind {
+    ExportWind {
+        speed,
+        gust,
+        direction_deg: deg,
+        direction: deg.map(|d| deg_to_dir(Some(d))),
+    }
+}
+
+fn condition(weather: &[WeatherDesc], night: bool, moon: Option<&str>) -> ExportCondition {
+    let desc = weather.first().cloned().unwrap_or(WeatherDesc {
+        main: None,
+        description: None,
+    });
+    ExportCondition {
+        icon: pick_icon(&desc, night, moon),
+        main: desc.main,
+        description: desc.description,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, LocationSettings};
+    use crate::weather::{ApiResponse, Location};
+
+    fn sample() -> (FetchedWeather, ResolvedLocation) {
+        let data: ApiResponse = serde_json::from_value(serde_json::json!({
+            "timezone_offset": -14400,
+            "current": {
+                "dt": 1_684_929_490, "temp": 72.4, "feels_like": 70.0, "humidity": 53,
+                "visibility": 10000, "wind_speed": 9.0, "wind_deg": 225, "wind_gust": 17.0,
+                "sunrise": 1_684_905_000, "sunset": 1_684_958_000,
+                "weather": [{"main": "Clouds", "description": "scattered clouds"}]
+            },
+            "hourly": [{
+                "dt": 1_684_929_600, "temp": 72.0, "pop": 0.2,
+                "weather": [{"main": "Rain", "description": "light rain"}]
+            }],
+            "daily": [{
+                "dt": 1_684_944_000, "temp": {"min": 58.0, "max": 75.0}, "pop": 0.35,
+                "weather": []
+            }]
+        }))
+        .unwrap();
+        let resolved = ResolvedLocation {
+            location: Location {
+                lat: 40.713,
+                lon: -74.006,
+                label: "New York".into(),
+            },
+            settings: LocationSettings::resolve(&Config::default(), None, "k"),
+        };
+        let fetched = FetchedWeather {
+            data,
+            fetched_at: 1_684_929_500,
+            stale: true,
+        };
+        (fetched, resolved)
+    }
+
+    #[test]
+    fn test_export_schema() {
+        let (fetched, resolved) = sample();
+        let json =
+            serde_json::to_value(weather_export(&fetched, &resolved, 1_684_929_620)).unwrap();
+
+        let keys: Vec<&str> = json
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "current",
+                "daily",
+                "fetch",
+                "hourly",
+                "location",
+                "schema_version",
+                "units"
+            ]
+        );
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["location"]["label"], "New York");
+        assert_eq!(json["units"]["temperature"], "°F");
+        assert_eq!(json["current"]["time"], "2023-05-24T07:58:10-04:00");
+        assert_eq!(json["current"]["visibility"], 6.2);
+        assert_eq!(json["current"]["wind"]["direction"], "SW");
+        assert_eq!(json["current"]["wind"]["gust"], 17.0);
+        assert_eq!(json["hourly"][0]["wind"]["gust"], serde_json::Value::Null);
+        assert_eq!(json["current"]["precipitation_chance"], 20);
+        assert_eq!(json["current"]["pressure"], serde_json::Value::Null);
+        assert_eq!(json["hourly"][0]["condition"]["main"], "Rain");
+        assert_eq!(json["daily"][0]["date"], "2023-05-24");
+        assert_eq!(json["daily"][0]["precipitation_chance"], 35);
+        assert_eq!(
+            json["daily"][0]["condition"]["main"],
+            serde_json::Value::Null
+        );
+        assert_eq!(json["fetch"]["provider"], "openweathermap");
+        assert_eq!(json["fetch"]["fetched_at"], "2023-05-24T11:58:20Z");
+        assert_eq!(json["fetch"]["age_secs"], 120);
+        assert_eq!(json["fetch"]["stale"], true);
+    }
+
+    #[test]
+    fn test_hourly_icons_use_each_days_sun_times() {
+        let (mut fetched, resolved) = sample();
+        let clear = serde_json::json!([{"main": "Clear", "description": "clear sky"}]);
+        // Noon and 11 PM on the next day, in local time (UTC-4)
+        for dt in [1_685_030_400, 1_685_070_000] {
+            fetched.data.hourly.push(
+                serde_json::from_value(
+                    serde_json::json!({"dt": dt, "temp": 70.0, "weather": clear}),
+                )
+                .unwrap(),
+            );
+        }
+        fetched.data.daily.push(
+            serde_json::from_value(serde_json::json!({
+                "dt": 1_685_030_400, "temp": {"min": 58.0, "max": 75.0},
+                "sunrise": 1_684_991_360, "sunset": 1_685_044_460, "weather": clear
+            }))
+            .unwrap(),
+        );
+
+        let json =
+            serde_json::to_value(weather_export(&fetched, &resolved, 1_684_929_620)).unwrap();
+        assert_eq!(json["hourly"][1]["condition"]["icon"], "☀️");
+        assert_eq!(json["hourly"][2]["condition"]["icon"], "🌙");
+    }
+}
@@ -3,207 +3,453 @@
 //! This module handles formatting weather data into text and tooltip displays
 //! for Waybar integration.
 
-use crate::config::{ColorsResolved, TempBand, UiConfigResolved, Units};
+use tracing::warn;
+
+use crate::config::{
+    ColorsResolved, FormatConfig, LocationSettings, SectionKind, TooltipSection, UiConfigResolved,
+    Units,
+};
+use crate::template::{self, Node, Value, Values};
 use crate::utils::{
-    deg_to_dir, escape_pango, fmt_time, is_night, moon_phase_icon, pick_icon, short_desc,
-    sparkline, temp_color, uvi_color,
+    deg_to_dir, escape_pango, fmt_time, format_ago, is_night, moon_phase_icon, pick_icon,
+    short_desc, sparkline, temp_color, uvi_color,
 };
-use crate::weather::{ApiResponse, Daily, Hourly, WeatherDesc};
+use crate::validate::{Diagnostic, Severity};
+use crate::weather::{ApiResponse, Daily, FetchedWeather, Hourly, WeatherDesc};
 
-/// Formats the current weather section
-pub fn format_current_weather(
-    data: &ApiResponse,
+/// Bar text used when `format.bar` is not set
+pub const DEFAULT_BAR_FORMAT: &str = "| {icon} {temp}{?stale} {stale}{/stale}";
+
+/// Bar text used with `bar_presets` when `format.bar` is not set
+pub const DEFAULT_MULTI_BAR_FORMAT: &str = "| {locations}";
+/// Each location's part of `{locations}` when `format.location_bar` is not set
+pub const DEFAULT_LOCATION_BAR_FORMAT: &str = "{place} {temp}{?stale} {stale}{/stale}";
+/// Text between locations when `format.location_separator` is not set
+pub const DEFAULT_LOCATION_SEPARATOR: &str = " │ ";
+/// Each location's tooltip block when `format.location_tooltip` is not set
+pub const DEFAULT_LOCATION_TOOLTIP_FORMAT: &str = concat!(
+    "{place} <b>{location}</b>  {icon} {temp}  {desc}\n",
+    "↑{hi} ↓{lo} • Wind {wind}{?pop} • 💧{pop}{/pop}{?stale} • {stale} updated {updated}{/stale}",
+);
+/// Extra placeholder in the location templates: the preset's icon or name
+pub const PLACE_FIELD: &str = "place";
+/// Extra placeholder in the bar with `bar_presets`: every location's part
+pub const LOCATIONS_FIELD: &str = "locations";
+/// What `{stale}` shows when the data is older than the cache TTL, and
+/// `{offline}` when the last fetch failed
+pub const STALE_MARKER: &str = "⚠";
+
+/// Placeholders available in the bar and the `current` section
+pub const CURRENT_FIELDS: &[&str] = &[
+    "location",
+    "icon",
+    "temp",
+    "feels",
+    "desc",
+    "humidity",
+    "uv",
+    "wind",
+    "pop",
+    "visibility",
+    "pressure",
+    "sunrise",
+    "sunset",
+    "hi",
+    "lo",
+    "updated",
+    "stale",
+    "offline",
+];
+/// Placeholders available in each `hourly` row
+pub const HOURLY_FIELDS: &[&str] = &[
+    "time", "icon", "temp", "desc", "pop", "wind", "humidity", "uv",
+];
+/// Placeholders available in each `daily` row
+pub const DAILY_FIELDS: &[&str] = &[
+    "day", "date", "icon", "hi", "lo", "desc", "pop", "humidity", "uv", "sunrise", "sunset",
+];
+
+/// Default layout and limits of a tooltip section
+pub struct SectionDefaults {
+    pub title: &'static str,
+    pub template: &'static str,
+    pub count: usize,
+    /// Most hours or days the forecast provides
+    pub max_count: usize,
+    pub fields: &'static [&'static str],
+    pub title_fields: &'static [&'static str],
+}
+
+impl SectionKind {
+    /// Returns the built-in layout for this kind of section
+    pub fn defaults(self) -> SectionDefaults {
+        match self {
+            SectionKind::Current => SectionDefaults {
+                title: "",
+                template: "🌍 <b>{location}</b>\n\
+                           {icon} <span size='large'>{temp}</span>  {desc}\n\
+                           Feels like {feels} • Hum {humidity} • UV {uv}\n\
+                           Wind {wind} • Rain {pop}\n\
+                           🌅 {sunrise}  🌇 {sunset} • 👁️ {visibility} • 🌪️ {pressure}\n\
+                           🕒 Updated {updated}{?offline} {offline} couldn't refresh{/offline}",
+                count: 1,
+                max_count: 1,
+                fields: CURRENT_FIELDS,
+                title_fields: CURRENT_FIELDS,
+            },
+            SectionKind::Hourly => SectionDefaults {
+                title: "HOURS {spark}",
+                template: "{time:4} {temp} {icon} {desc:12} {?pop}💧{pop}{/pop}",
+                count: 12,
+                max_count: 48,
+                fields: HOURLY_FIELDS,
+                title_fields: &["spark", "count"],
+            },
+            SectionKind::Daily => SectionDefaults {
+                title: "DAYS",
+                template: "{day:3} {hi}/{lo} {icon} {desc:12} {?pop}💧{pop}{/pop}",
+                count: 5,
+                max_count: 8,
+                fields: DAILY_FIELDS,
+                title_fields: &["count"],
+            },
+        }
+    }
+}
+
+/// The tooltip sections shown when `format.tooltip` is not set
+fn default_sections() -> Vec<TooltipSection> {
+    [
+        SectionKind::Current,
+        SectionKind::Hourly,
+        SectionKind::Daily,
+    ]
+    .into_iter()
+    .map(|section| TooltipSection {
+        section,
+        title: None,
+        count: None,
+        template: None,
+    })
+    .collect()
+}
+
+/// Parses a user template, falling back to the default if it is invalid
+///
+/// `redweather config check` reports the problem with its line number.
+fn parse_template(src: Option<&str>, default: &str, what: &str) -> Vec<Node> {
+    if let Some(src) = src {
+        match template::parse(src) {
+            Ok(nodes) => return nodes,
+            Err(e) => warn!("Invalid {} template ({}); using the default", what, e),
+        }
+    }
+    template::parse(default).expect("default templates are valid")
+}
+
+fn temp_unit(units: Units) -> &'static str {
+    match units {
+        Units::Imperial => "°F",
+        Units::Metric => "°C",
+    }
+}
+
+/// A temperature in its band color: `73°F`, or `73°` in forecast rows where the
+/// unit would only add clutter
+fn temp_value(temp: f64, settings: &LocationSettings) -> Value {
+    temp_value_with(temp, settings, temp_unit(settings.units))
+}
+
+fn row_temp_value(temp: f64, settings: &LocationSettings) -> Value {
+    temp_value_with(temp, settings, "°")
+}
+
+fn temp_value_with(temp: f64, settings: &LocationSettings, unit: &str) -> Value {
+    let t = temp.round();
+    let text = format!("{:.0}{}", t, unit);
+    Value::marked(
+        format!(
+            "<span foreground='{}'>{}</span>",
+            temp_color(t, &settings.temp_bands),
+            text
+        ),
+        &text,
+    )
+}
+
+/// A chance of precipitation, counted as missing for `{?pop}` when it is 0%
+fn pop_value(pop: Option<f64>) -> Value {
+    let pop = pop.map(|p| (p * 100.0).round() as i64).unwrap_or(0);
+    Value::plain(format!("{}%", pop)).present_if(pop > 0)
+}
+
+fn uv_value(uvi: f64) -> Value {
+    let text = format!("{}", uvi.round());
+    Value::marked(
+        format!("<span foreground='{}'>{}</span>", uvi_color(uvi), text),
+        &text,
+    )
+}
+
+fn desc_value(weather: &[WeatherDesc], max_len: Option<usize>) -> Value {
+    let desc = weather
+        .first()
+        .and_then(|w| w.description.as_deref())
+        .map(|s| match max_len {
+            Some(n) => short_desc(s, n),
+            None => s.to_string(),
+        });
+    match desc {
+        Some(d) => Value::marked(escape_pango(&d), &d),
+        None => Value::plain("—").present_if(false),
+    }
+}
+
+/// Placeholder values for the bar and the `current` section
+pub fn current_values(
+    fetched: &FetchedWeather,
     location_label: &str,
-    temp_bands: &[TempBand],
-    units: Units,
-) -> (String, Vec<String>) {
-    let (temp_unit, speed_unit, dist_unit) = match units {
-        Units::Imperial => ("°F", "mph", "mi"),
-        Units::Metric => ("°C", "m/s", "km"),
+    settings: &LocationSettings,
+) -> Values {
+    let data = &fetched.data;
+    let units = settings.units;
+    let (speed_unit, dist_unit) = match units {
+        Units::Imperial => ("mph", "mi"),
+        Units::Metric => ("m/s", "km"),
     };
-    let current_desc = data.current.weather.get(0).cloned().unwrap_or(WeatherDesc {
+    let cur = &data.current;
+    let current_desc = cur.weather.first().cloned().unwrap_or(WeatherDesc {
         main: Some("Clear".into()),
         description: Some("Clear".into()),
     });
-    let night = is_night(data.current.dt, data.current.sunrise, data.current.sunset);
-    let moon_icon = Some(moon_phase_icon(data.current.dt, data.timezone_offset));
-    let icon = pick_icon(&current_desc, night, moon_icon);
-    let temp = data.current.temp.round();
-    let feels = data.current.feels_like.map(|t| t.round());
-    let humidity = data.current.humidity.unwrap_or(0);
-    let wind_speed = data.current.wind_speed.unwrap_or(0.0).round();
-    let wind_dir = deg_to_dir(data.current.wind_deg);
-    let uvi = data.current.uvi.unwrap_or(0.0);
-    let pressure = data.current.pressure.unwrap_or(0);
-    let vis_meters = data.current.visibility.unwrap_or(10000);
+    let night = is_night(cur.dt, cur.sunrise, cur.sunset);
+    let moon_icon = Some(moon_phase_icon(cur.dt, data.timezone_offset));
+    let vis_meters = cur.visibility.unwrap_or(10000);
     let visibility = match units {
         Units::Imperial => (vis_meters as f64 / 1609.34).round(),
         Units::Metric => (vis_meters as f64 / 1000.0).round(),
     };
-    let pop = data
-        .hourly
-        .get(0)
-        .and_then(|h| h.pop)
-        .map(|p| (p * 100.0).round() as i64)
-        .unwrap_or(0);
-
-    let sunrise = data
-        .current
-        .sunrise
-        .map(|t| fmt_time(t, data.timezone_offset, "%H:%M"))
-        .unwrap_or_else(|| "-:-".into());
-    let sunset = data
-        .current
-        .sunset
-        .map(|t| fmt_time(t, data.timezone_offset, "%H:%M"))
-        .unwrap_or_else(|| "-:-".into());
-
-    let temp_col = temp_color(temp, temp_bands);
-    let text = format!(
-        "| {} <span foreground='{}'>{:.0}{}</span>",
-        icon, temp_col, temp, temp_unit
-    );
-
-    let safe_loc = escape_pango(location_label);
-    let safe_desc = escape_pango(current_desc.description.as_deref().unwrap_or("N/A"));
-
-    let mut current_lines = vec![
-        format!("🌍 <b>{}</b>", safe_loc),
-        format!(
-            "{} <span foreground='{tc}' size='large'>{:.0}{}</span>  {}",
-            icon,
-            temp,
-            temp_unit,
-            safe_desc,
-            tc = temp_col
-        ),
-    ];
-
-    let feels_str = feels
-        .map(|f| format!("{:.0}°", f))
-        .unwrap_or_else(|| "—".into());
-
-    // Row 1: Feels like, Humidity, UV
-    current_lines.push(format!(
-        "Feels like {} • Hum {}% • UV <span foreground='{}'>{}</span>",
-        feels_str,
-        humidity,
-        uvi_color(uvi),
-        uvi.round()
-    ));
+    let clock = |t: Option<i64>| {
+        t.map(|t| Value::plain(settings.time.time(t, data.timezone_offset)))
+            .unwrap_or_else(|| Value::plain("-:-").present_if(false))
+    };
+    let today = data.daily.first();
+    let missing = || Value::plain("—").present_if(false);
 
-    // Row 2: Wind, Precip
-    current_lines.push(format!(
-        "Wind {:.0} {} {} • Rain {}%",
-        wind_speed, speed_unit, wind_dir, pop
-    ));
+    let mut v = Values::new();
+    v.insert(
+        "location",
+        Value::marked(escape_pango(location_label), location_label),
+    );
+    v.insert(
+        "icon",
+        Value::plain(pick_icon(&current_desc, night, moon_icon)),
+    );
+    v.insert("temp", temp_value(cur.temp, settings));
+    v.insert(
+        "feels",
+        cur.feels_like
+            .map(|t| temp_value(t, settings))
+            .unwrap_or_else(missing),
+    );
+    v.insert("desc", desc_value(&[current_desc], None));
+    v.insert(
+        "humidity",
+        Value::plain(format!("{}%", cur.humidity.unwrap_or(0))),
+    );
+    v.insert("uv", uv_value(cur.uvi.unwrap_or(0.0)));
+    v.insert(
+        "wind",
+        Value::plain(format!(
+            "{:.0} {} {}",
+            cur.wind_speed.unwrap_or(0.0).round(),
+            speed_unit,
+            deg_to_dir(cur.wind_deg)
+        )),
+    );
+    v.insert("pop", pop_value(data.hourly.first().and_then(|h| h.pop)));
+    v.insert(
+        "visibility",
+        Value::plain(format!("{:.0}{}", visibility, dist_unit)),
+    );
+    v.insert(
+        "pressure",
+        Value::plain(format!("{}hPa", cur.pressure.unwrap_or(0))),
+    );
+    v.insert("sunrise", clock(cur.sunrise));
+    v.insert("sunset", clock(cur.sunset));
+    v.insert(
+        "hi",
+        today
+            .and_then(|d| d.temp.max.or(d.temp.day))
+            .map(|t| temp_value(t, settings))
+            .unwrap_or_else(missing),
+    );
+    v.insert(
+        "lo",
+        today
+            .and_then(|d| d.temp.min)
+            .map(|t| temp_value(t, settings))
+            .unwrap_or_else(missing),
+    );
+    v.insert("updated", Value::plain(format_ago(fetched.age_secs())));
+    v.insert(
+        "stale",
+        Value::plain(STALE_MARKER).present_if(fetched.is_outdated(settings.cache.ttl_secs)),
+    );
+    v.insert("offline", Value::plain(STALE_MARKER).present_if(fetched.stale));
+    v
+}
 
-    // Row 3: Astro & Atmos
-    current_lines.push(format!(
-        "🌅 {}  🌇 {} • 👁️ {:.0}{} • 🌪️ {}hPa",
-        sunrise, sunset, visibility, dist_unit, pressure
-    ));
+/// Placeholder values for one `hourly` row
+fn hour_values(
+    h: &Hourly,
+    timezone_offset: i64,
+    ui: &UiConfigResolved,
+    settings: &LocationSettings,
+) -> Values {
+    let speed_unit = match settings.units {
+        Units::Imperial => "mph",
+        Units::Metric => "m/s",
+    };
+    let local_hour = ((h.dt + timezone_offset) / 3600) % 24;
+    let night = !(6..18).contains(&local_hour);
+    let icon = h
+        .weather
+        .first()
+        .map(|d| pick_icon(d, night, Some(moon_phase_icon(h.dt, timezone_offset))))
+        .unwrap_or_else(|| "❓".into());
 
-    (text, current_lines)
+    let mut v = Values::new();
+    v.insert(
+        "time",
+        Value::plain(settings.time.hour(h.dt, timezone_offset)),
+    );
+    v.insert("icon", Value::plain(icon));
+    v.insert("temp", row_temp_value(h.temp, settings));
+    v.insert("desc", desc_value(&h.weather, Some(ui.max_desc_len)));
+    v.insert("pop", pop_value(h.pop));
+    v.insert(
+        "wind",
+        Value::plain(format!(
+            "{:.0} {} {}",
+            h.wind_speed.unwrap_or(0.0).round(),
+            speed_unit,
+            deg_to_dir(h.wind_deg)
+        )),
+    );
+    v.insert(
+        "humidity",
+        Value::plain(format!("{}%", h.humidity.unwrap_or(0))),
+    );
+    v.insert("uv", uv_value(h.uvi.unwrap_or(0.0)));
+    v
 }
 
-/// Formats hourly forecast lines
-pub fn format_hourly_forecast(
-    hourly: &[Hourly],
+/// Placeholder values for one `daily` row
+fn day_values(
+    d: &Daily,
     timezone_offset: i64,
     ui: &UiConfigResolved,
-    temp_bands: &[TempBand],
-) -> Vec<String> {
-    hourly
-        .iter()
-        .take(12)
-        .map(|h| {
-            let label = fmt_time(h.dt, timezone_offset, "%-I%p");
-            let h_temp = h.temp.round();
-            let local_hour = ((h.dt + timezone_offset) / 3600) % 24;
-            let night = local_hour < 6 || local_hour >= 18;
-            let icon_h = h
-                .weather
-                .get(0)
-                .map(|d| pick_icon(d, night, Some(moon_phase_icon(h.dt, timezone_offset))))
-                .unwrap_or_else(|| "❓".into());
-            let pop = h.pop.map(|p| (p * 100.0).round() as i64).unwrap_or(0);
-            let precip_str = if pop > 0 {
-                format!("💧{}%", pop)
-            } else {
-                "".to_string()
-            };
-
-            let raw_desc = h
-                .weather
-                .get(0)
-                .and_then(|w| w.description.as_ref())
-                .map(|s| short_desc(s, ui.max_desc_len))
-                .unwrap_or_else(|| "—".into());
-            let desc = escape_pango(&raw_desc);
-
-            format!(
-                "{: <4} <span foreground='{temp_col}' font='{fs}'>{:.0}°</span> {icon} {: <12} {}",
-                label,
-                h_temp,
-                desc,
-                precip_str,
-                temp_col = temp_color(h_temp, temp_bands),
-                fs = ui.font_size,
-                icon = icon_h,
-            )
-        })
-        .collect()
+    settings: &LocationSettings,
+) -> Values {
+    let icon = d
+        .weather
+        .first()
+        .map(|desc| pick_icon(desc, false, None))
+        .unwrap_or_else(|| "❓".into());
+    let clock = |t: Option<i64>| {
+        t.map(|t| Value::plain(settings.time.time(t, timezone_offset)))
+            .unwrap_or_else(|| Value::plain("-:-").present_if(false))
+    };
+
+    let mut v = Values::new();
+    v.insert("day", Value::plain(fmt_time(d.dt, timezone_offset, "%a")));
+    v.insert(
+        "date",
+        Value::plain(fmt_time(d.dt, timezone_offset, "%-d %b")),
+    );
+    v.insert("icon", Value::plain(icon));
+    v.insert(
+        "hi",
+        row_temp_value(d.temp.max.or(d.temp.day).unwrap_or(0.0), settings),
+    );
+    v.insert("lo", row_temp_value(d.temp.min.unwrap_or(0.0), settings));
+    v.insert("desc", desc_value(&d.weather, Some(ui.max_desc_len)));
+    v.insert("pop", pop_value(d.pop));
+    v.insert(
+        "humidity",
+        Value::plain(format!("{}%", d.humidity.unwrap_or(0))),
+    );
+    v.insert("uv", uv_value(d.uvi.unwrap_or(0.0)));
+    v.insert("sunrise", clock(d.sunrise));
+    v.insert("sunset", clock(d.sunset));
+    v
 }
 
-/// Formats daily forecast lines
-pub fn format_daily_forecast(
-    daily: &[Daily],
-    timezone_offset: i64,
+/// Renders one tooltip section: its title (if any) followed by its lines
+fn format_section(
+    section: &TooltipSection,
+    data: &ApiResponse,
+    current: &Values,
     ui: &UiConfigResolved,
-    temp_bands: &[TempBand],
+    colors: &ColorsResolved,
+    settings: &LocationSettings,
 ) -> Vec<String> {
-    daily
-        .iter()
-        .take(5)
-        .map(|d| {
-            let day = fmt_time(d.dt, timezone_offset, "%a");
-            let hi = d.temp.max.or(d.temp.day).unwrap_or(0.0).round();
-            let lo = d.temp.min.unwrap_or(0.0).round();
-            let icon_d = d
-                .weather
-                .get(0)
-                .map(|desc| pick_icon(desc, false, None))
-                .unwrap_or_else(|| "❓".into());
-            let pop = d.pop.map(|p| (p * 100.0).round() as i64).unwrap_or(0);
-            let precip_str = if pop > 0 {
-                format!("💧{}%", pop)
-            } else {
-                "".to_string()
-            };
-
-            let raw_desc = d
-                .weather
-                .get(0)
-                .and_then(|w| w.description.as_ref())
-                .map(|s| short_desc(s, ui.max_desc_len))
-                .unwrap_or_else(|| "—".into());
-            let desc = escape_pango(&raw_desc);
-
-            format!(
-                "{: <3} <span foreground='{hi_col}' font='{fs}'>{:.0}°</span>/<span foreground='{lo_col}' font='{fs}'>{:.0}°</span> {icon} {: <12} {}",
-                day,
-                hi,
-                lo,
-                desc,
-                precip_str,
-                hi_col = temp_color(hi, temp_bands),
-                lo_col = temp_color(lo, temp_bands),
-                fs = ui.font_size,
-                icon = icon_d,
-            )
-        })
-        .collect()
+    let defaults = section.section.defaults();
+    let what = match section.section {
+        SectionKind::Current => "current",
+        SectionKind::Hourly => "hourly",
+        SectionKind::Daily => "daily",
+    };
+    let row = parse_template(section.template.as_deref(), defaults.template, what);
+    let title = parse_template(section.title.as_deref(), defaults.title, what);
+    let count = section
+        .count
+        .unwrap_or(defaults.count)
+        .min(defaults.max_count);
+    let tz = data.timezone_offset;
+
+    let (mut title_values, body) = match section.section {
+        SectionKind::Current => (current.clone(), template::render(&row, current)),
+        SectionKind::Hourly => {
+            let hours = &data.hourly[..count.min(data.hourly.len())];
+            let temps: Vec<f64> = hours.iter().map(|h| h.temp).collect();
+            let mut tv = Values::new();
+            tv.insert("spark", Value::plain(sparkline(&temps)));
+            let rows: Vec<String> = hours
+                .iter()
+                .map(|h| template::render(&row, &hour_values(h, tz, ui, settings)))
+                .collect();
+            (tv, rows.join("\n"))
+        }
+        SectionKind::Daily => {
+            let days = &data.daily[..count.min(data.daily.len())];
+            let rows: Vec<String> = days
+                .iter()
+                .map(|d| template::render(&row, &day_values(d, tz, ui, settings)))
+                .collect();
+            (Values::new(), rows.join("\n"))
+        }
+    };
+    title_values
+        .entry("count")
+        .or_insert_with(|| Value::plain(count.to_string()));
+
+    let mut lines = Vec::new();
+    let title = template::render(&title, &title_values);
+    if !title.trim().is_empty() {
+        lines.push(format!(
+            "<span foreground='{hdr}' font='{fs}' weight='bold'>{title}</span>",
+            hdr = colors.header,
+            fs = ui.font_size,
+        ));
+    }
+    if !body.is_empty() {
+        let body_lines = body.split('\n').map(str::to_string).collect();
+        lines.extend(wrap_with_style(body_lines, &colors.text, ui.font_size));
+    }
+    lines
 }
 
 /// Wraps lines with color and font styling
@@ -214,45 +460,297 @@ fn wrap_with_style(lines: Vec<String>, color: &str, font_size: u8) -> Vec<String
         .collect()
 }
 
+/// Formats config diagnostics as a tooltip section
+pub fn format_config_diagnostics(
+    diagnostics: &[Diagnostic],
+    colors: &ColorsResolved,
+    font_size: u8,
+) -> Vec<String> {
+    let mut lines = vec![format!(
+        "<span foreground='{hdr}' font='{fs}' weight='bold'>⚠ CONFIG</span>",
+        hdr = colors.header,
+        fs = font_size
+    )];
+    lines.extend(diagnostics.iter().map(|d| {
+        let icon = match d.severity {
+            Severity::Error => "❌",
+            Severity::Warning => "⚠️",
+        };
+        format!(
+            "<span foreground='{color}' font='{font_size}'>{icon} {}</span>",
+            escape_pango(&d.to_string()),
+            color = colors.text
+        )
+    }));
+    lines.push(format!(
+        "<span foreground='{color}' font='{font_size}'>Run `redweather config check` for details</span>",
+        color = colors.text
+    ));
+    lines
+}
+
+/// One location in a bar showing several (`bar_presets`)
+pub struct LocationEntry<'a> {
+    pub place: &'a str,
+    pub label: &'a str,
+    pub settings: &'a LocationSettings,
+    /// The weather, or why there is none
+    pub weather: Result<&'a FetchedWeather, String>,
+}
+
+/// Formats the bar text and tooltip for several locations
+///
+/// Placeholders other than `{locations}` in the bar refer to the first
+/// location with weather. The tooltip has one compact block per location.
+pub fn format_locations_text(
+    entries: &[LocationEntry],
+    ui: &UiConfigResolved,
+    colors: &ColorsResolved,
+    format: Option<&FormatConfig>,
+) -> (String, String) {
+    let item = parse_template(
+        format.and_then(|f| f.location_bar.as_deref()),
+        DEFAULT_LOCATION_BAR_FORMAT,
+        "location_bar",
+    );
+    let block = parse_template(
+        format.and_then(|f| f.location_tooltip.as_deref()),
+        DEFAULT_LOCATION_TOOLTIP_FORMAT,
+        "location_tooltip",
+    );
+    let separator = format
+        .and_then(|f| f.location_separator.as_deref())
+        .unwrap_or(DEFAULT_LOCATION_SEPARATOR);
+
+    let mut items = Vec::new();
+    let mut blocks = Vec::new();
+    let mut first: Option<Values> = None;
+    for entry in entries {
+        let place = Value::marked(escape_pango(entry.place), entry.place);
+        match &entry.weather {
+            Ok(fetched) => {
+                let mut values = current_values(fetched, entry.label, entry.settings);
+                values.insert(PLACE_FIELD, place);
+                items.push(template::render(&item, &values));
+                blocks.push(template::render(&block, &values));
+                first.get_or_insert(values);
+            }
+            Err(msg) => {
+                items.push(format!("{} ❓", place.markup));
+                blocks.push(format!(
+                    "{} <b>{}</b>  ❓ {}",
+                    place.markup,
+                    escape_pango(entry.label),
+                    escape_pango(msg)
+                ));
+            }
+        }
+    }
+
+    let mut values = first.unwrap_or_default();
+    values.insert(
+        LOCATIONS_FIELD,
+        Value {
+            markup: items.join(separator),
+            width: 0,
+            present: !items.is_empty(),
+        },
+    );
+    let bar = parse_template(
+        format.and_then(|f| f.bar.as_deref()),
+        DEFAULT_MULTI_BAR_FORMAT,
+        "bar",
+    );
+    let text = template::render(&bar, &values);
+
+    let tooltip: Vec<String> = blocks
+        .into_iter()
+        .map(|b| {
+            let lines = b.split('\n').map(str::to_string).collect();
+            wrap_with_style(lines, &colors.text, ui.font_size).join("\n")
+        })
+        .collect();
+    (text, tooltip.join("\n\n"))
+}
+
 /// Formats complete popup text and tooltip for Waybar
 pub fn format_popup_text(
-    data: &ApiResponse,
+    fetched: &FetchedWeather,
     location_label: &str,
     ui: &UiConfigResolved,
     colors: &ColorsResolved,
-    temp_bands: &[TempBand],
-    units: Units,
+    settings: &LocationSettings,
+    format: Option<&FormatConfig>,
 ) -> (String, String) {
-    let (text, current_lines) = format_current_weather(data, location_label, temp_bands, units);
-    let hourly_lines = format_hourly_forecast(&data.hourly, data.timezone_offset, ui, temp_bands);
-    let daily_lines = format_daily_forecast(&data.daily, data.timezone_offset, ui, temp_bands);
+    let current = current_values(fetched, location_label, settings);
+    let bar = parse_template(
+        format.and_then(|f| f.bar.as_deref()),
+        DEFAULT_BAR_FORMAT,
+        "bar",
+    );
+    let text = template::render(&bar, &current);
 
-    // Generate Sparkline
-    let hourly_temps: Vec<f64> = data.hourly.iter().take(12).map(|h| h.temp).collect();
-    let spark = sparkline(&hourly_temps);
+    let sections = format
+        .and_then(|f| f.tooltip.clone())
+        .unwrap_or_else(default_sections);
+    let tooltip_lines: Vec<String> = sections
+        .iter()
+        .map(|s| format_section(s, &fetched.data, &current, ui, colors, settings))
+        .filter(|lines| !lines.is_empty())
+        .collect::<Vec<_>>()
+        .join(&String::new());
 
-    // Build compact tooltip: stacked sections
-    let mut tooltip_lines = Vec::new();
+    (text, tooltip_lines.join("\n"))
+}
 
-    // Header is built into current_lines now, but let's keep section structure
-    tooltip_lines.extend(wrap_with_style(current_lines, &colors.text, ui.font_size));
-    tooltip_lines.push(String::new());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
 
-    tooltip_lines.push(format!(
-        "<span foreground='{hdr}' font='{fs}' weight='bold'>HOURS {spark}</span>",
-        hdr = colors.header,
-        fs = ui.font_size,
-        spark = spark
-    ));
-    tooltip_lines.extend(wrap_with_style(hourly_lines, &colors.text, ui.font_size));
-    tooltip_lines.push(String::new());
+    fn sample() -> ApiResponse {
+        serde_json::from_value(serde_json::json!({
+            "timezone_offset": 0,
+            "current": {
+                "dt": 1_684_929_490, "temp": 72.6, "feels_like": 70.0, "humidity": 53,
+                "sunrise": 1_684_905_000, "sunset": 1_684_958_000,
+                "weather": [{"main": "Clouds", "description": "scattered clouds"}]
+            },
+            "hourly": (0..24).map(|i| serde_json::json!({
+                "dt": 1_684_929_600 + i * 3600, "temp": 70.0, "pop": 0.0,
+                "weather": [{"main": "Clear", "description": "clear sky"}]
+            })).collect::<Vec<_>>(),
+            "daily": (0..8).map(|i| serde_json::json!({
+                "dt": 1_684_929_600 + i * 86400, "temp": {"min": 58.0, "max": 75.0},
+                "pop": 0.4, "weather": [{"main": "Rain", "description": "rain"}]
+            })).collect::<Vec<_>>()
+        }))
+        .unwrap()
+    }
 
-    tooltip_lines.push(format!(
-        "<span foreground='{hdr}' font='{fs}' weight='bold'>DAYS</span>",
-        hdr = colors.header,
-        fs = ui.font_size
-    ));
-    tooltip_lines.extend(wrap_with_style(daily_lines, &colors.text, ui.font_size));
+    fn fetched(data: ApiResponse, age_secs: i64, stale: bool) -> FetchedWeather {
+        FetchedWeather {
+            data,
+            fetched_at: chrono::Utc::now().timestamp() - age_secs,
+            stale,
+        }
+    }
 
-    (text, tooltip_lines.join("\n"))
+    fn popup(format: Option<&FormatConfig>) -> (String, String) {
+        let cfg = Config::default();
+        format_popup_text(
+            &fetched(sample(), 0, false),
+            "Home & Away",
+            &UiConfigResolved::from_config(&None),
+            &ColorsResolved::from_config(&None),
+            &LocationSettings::resolve(&cfg, None, "k"),
+            format,
+        )
+    }
+
+    #[test]
+    fn test_default_layout() {
+        let (text, tooltip) = popup(None);
+        assert!(text.starts_with("| ☁️ <span foreground='#"));
+        assert!(text.ends_with("'>73°F</span>"));
+        assert!(tooltip.contains("Home &amp; Away"));
+        assert!(tooltip.contains(">HOURS "));
+        assert!(tooltip.contains(">DAYS</span>"));
+        assert!(tooltip.contains("'>70°</span> ☀️ clear sky"));
+        assert!(tooltip.contains("🕒 Updated just now</span>"));
+        // 6 current lines, 12 hours and 5 days, 2 titles and 2 blank lines
+        assert_eq!(tooltip.lines().count(), 6 + 1 + 12 + 1 + 5 + 2);
+        assert_eq!(tooltip.matches("💧40%").count(), 5);
+    }
+
+    #[test]
+    fn test_outdated_data_is_marked() {
+        let cfg = Config::default();
+        let settings = LocationSettings::resolve(&cfg, None, "k");
+        let ui = UiConfigResolved::from_config(&None);
+        let colors = ColorsResolved::from_config(&None);
+        // Older than the 10 minute TTL, even though no fetch failed
+        let old = fetched(sample(), 14 * 60, false);
+        let (text, tooltip) = format_popup_text(&old, "Home", &ui, &colors, &settings, None);
+        assert!(text.ends_with("73°F</span> ⚠"));
+        assert!(tooltip.contains("🕒 Updated 14 min ago<"));
+        assert!(!tooltip.contains("couldn't refresh"));
+
+        // Only a failed fetch says so
+        let failed = fetched(sample(), 14 * 60, true);
+        let (_, tooltip) = format_popup_text(&failed, "Home", &ui, &colors, &settings, None);
+        assert!(tooltip.contains("🕒 Updated 14 min ago ⚠ couldn't refresh"));
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        let format: FormatConfig = toml::from_str(
+            r#"
+bar = "{temp}{?pop} {pop}{/pop}{!pop} dry{/pop}"
+
+[[tooltip]]
+section = "daily"
+count = 20
+title = ""
+template = "{day} {lo}..{hi}"
+
+[[tooltip]]
+section = "hourly"
+count = 3
+title = "{count}h"
+"#,
+        )
+        .unwrap();
+        let (text, tooltip) = popup(Some(&format));
+        assert!(text.ends_with("73°F</span> dry"));
+
+        let lines: Vec<&str> = tooltip.lines().collect();
+        // 8 days (all the forecast has), a blank line, a title and 3 hours
+        assert_eq!(lines.len(), 8 + 1 + 1 + 3);
+        assert!(lines[0].contains("Wed <span"));
+        assert!(lines[9].contains(">3h</span>"));
+    }
+
+    #[test]
+    fn test_several_locations() {
+        let cfg = Config::default();
+        let settings = LocationSettings::resolve(&cfg, None, "k");
+        let data = fetched(sample(), 0, false);
+        let old = fetched(sample(), 3 * 3600, true);
+        let entries = [
+            LocationEntry {
+                place: "🏠",
+                label: "Home",
+                settings: &settings,
+                weather: Ok(&data),
+            },
+            LocationEntry {
+                place: "cabin",
+                label: "Cabin",
+                settings: &settings,
+                weather: Err("Weather error: offline".into()),
+            },
+            LocationEntry {
+                place: "work",
+                label: "Work",
+                settings: &settings,
+                weather: Ok(&old),
+            },
+        ];
+        let (text, tooltip) = format_locations_text(
+            &entries,
+            &UiConfigResolved::from_config(&None),
+            &ColorsResolved::from_config(&None),
+            None,
+        );
+        assert!(text.starts_with("| 🏠 <span"));
+        assert!(text.contains("73°F</span> │ cabin ❓ │ work"));
+        assert!(text.ends_with("73°F</span> ⚠"));
+        let blocks: Vec<&str> = tooltip.split("\n\n").collect();
+        assert_eq!(blocks.len(), 3);
+        assert!(blocks[0].contains("<b>Home</b>"));
+        assert!(!blocks[0].contains("updated"));
+        assert!(blocks[1].contains("Weather error: offline"));
+        assert!(blocks[2].contains("⚠ updated 3 h ago"));
+    }
 }
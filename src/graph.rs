@@ -173,7 +173,7 @@ pub fn create_hourly_graph_plot( // Renamed to clearly indicate it's the plot ar
                 let time = fmt_time(h.dt, tz_offset as i64, "%I:%M %p");
                 let temp = h.temp.round();
                 let pop = h.pop.unwrap_or(0.0) * 100.0;
-                let desc = h.weather.get(0).and_then(|w| w.description.as_deref()).unwrap_or("");
+                let desc = h.weather.first().and_then(|w| w.description.as_deref()).unwrap_or("");
                 
                 let txt = format!("<b>{}</b>\n{:.0}°\nPrecip: {:.0}%\n<i>{}</i>", time, temp, pop, desc);
                 tooltip.set_markup(Some(&txt));
@@ -256,7 +256,7 @@ pub fn create_hourly_graph_plot( // Renamed to clearly indicate it's the plot ar
             for i in 0..count {
                 let hour_local = data_for_draw[i].dt + tz_offset as i64;
                 let hour_of_day = ((hour_local / 3600) % 24) as i32;
-                let is_night = hour_of_day < 6 || hour_of_day >= 18;
+                let is_night = !(6..18).contains(&hour_of_day);
                 let x0 = xs[i];
                 let x1 = x0 + shade_dx;
                 if is_night { c.set_source_rgba(0.08, 0.10, 0.18, 0.16); } 
@@ -408,20 +408,19 @@ pub fn create_hourly_graph_plot( // Renamed to clearly indicate it's the plot ar
             let _ = ctx.fill();
 
             // Precip Bars
-            for i in 0..count {
+            for (i, &x_center) in xs.iter().enumerate().take(count) {
                 let pop = st.prev_pop[i] + (st.curr_pop[i] - st.prev_pop[i]) * t_interp;
                 if pop <= 0.0 { continue; }
                 let bar_width = 8.0;
                 let bar_max_height = (precip_bottom - precip_top).max(1.0);
                 let bar_height = bar_max_height * pop;
-                let x_center = xs[i];
                 let x_left = x_center - bar_width / 2.0;
                 let bar_top_y = precip_bottom - bar_height;
                 
                 let (r, g, b, a) = if pop <= 0.2 { (110./255., 130./255., 170./255., 0.45) }
                 else if pop <= 0.5 { (130./255., 165./255., 220./255., 0.55) }
                 else if pop <= 0.8 { (135./255., 206./255., 250./255., 0.75) }
-                else { (135./255., 240./255., 255./255., 0.90) };
+                else { (135./255., 240./255., 1.0, 0.90) };
                 
                 ctx.set_source_rgba(r, g, b, a);
                 ctx.rectangle(x_left, bar_top_y, bar_width, bar_height);
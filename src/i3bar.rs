@@ -0,0 +1,290 @@
+//! The i3bar/swaybar protocol for `redweather daemon --output i3bar`
+//!
+//! The daemon prints a header, opens an endless JSON array and appends a
+//! one-block status line whenever the bar changes. i3bar writes click events
+//! to our stdin as another endless array, one object per line. Each button
+//! runs a named action (see [`crate::actions`]), set in `[i3bar]`:
+//! - left click opens the dashboard
+//! - right click refreshes, skipping the cache
+//! - scrolling switches to the previous or next preset
+//!
+//! `redweather bar --output i3bar` prints just the block, for status
+//! generators that merge blocks from several commands.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::ffi::OsString;
+use std::io::BufRead;
+use std::process::{Command, Stdio};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::{error, warn};
+
+use crate::actions::all_actions;
+use crate::cli::GlobalArgs;
+use crate::config::{load_config, ActionConfig, Config};
+use crate::daemon::Action;
+
+/// First line of the protocol
+pub const HEADER: &str = r#"{"version":1,"click_events":true}"#;
+/// The block's `name`, sent back in its click events
+pub const BLOCK_NAME: &str = "redweather";
+
+/// A click event; fields we don't use are ignored
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Click {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub button: u32,
+}
+
+/// What a click does
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClickAction {
+    /// Handled by the daemon's loop
+    Daemon(Action),
+    /// Run as `redweather action <name>`; with `reload`, the daemon re-reads
+    /// the config once it finishes
+    Run { name: String, reload: bool },
+}
+
+/// Builds the block for Waybar JSON; i3bar and swaybar render pango markup too
+pub fn block(bar: &Value) -> Value {
+    let text = bar["text"].as_str().unwrap_or_default();
+    json!({
+        "name": BLOCK_NAME,
+        "full_text": text,
+        // The leading "| " only separates modules in Waybar
+        "short_text": text.trim_start_matches('|').trim_start(),
+        "markup": "pango",
+    })
+}
+
+/// Parses one line of the click event array
+///
+/// The array's opening `[` and the `,` between events may share a line with
+/// an event. Returns `None` for anything else.
+pub fn parse_click(line: &str) -> Option<Click> {
+    let event = line.trim().trim_start_matches(['[', ',']).trim();
+    if event.is_empty() {
+        return None;
+    }
+    serde_json::from_str(event).ok()
+}
+
+/// The action a button runs unless `[i3bar]` says otherwise
+fn default_button_action(button: u32) -> Option<&'static str> {
+    match button {
+        1 => Some("dashboard"),
+        3 => Some("refresh"),
+        4 => Some("prev"),
+        5 => Some("next"),
+        _ => None,
+    }
+}
+
+/// The name of the action bound to a button
+pub fn button_action(cfg: &Config, button: u32) -> Option<String> {
+    let configured = cfg.i3bar.as_ref().and_then(|b| match button {
+        1 => b.left.as_ref(),
+        2 => b.middle.as_ref(),
+        3 => b.right.as_ref(),
+        4 => b.scroll_up.as_ref(),
+        5 => b.scroll_down.as_ref(),
+        _ => None,
+    });
+    match configured {
+        Some(name) => Some(name.clone()).filter(|n| !n.is_empty()),
+        None => default_button_action(button).map(str::to_string),
+    }
+}
+
+/// Maps a click on our block to what it does
+///
+/// Refreshing and cycling presets happen in the daemon itself; other actions
+/// run as their own process.
+pub fn click_action(click: &Click, cfg: &Config) -> Option<ClickAction> {
+    if click.name.as_deref().is_some_and(|n| n != BLOCK_NAME) {
+        return None;
+    }
+    let name = button_action(cfg, click.button)?;
+    let Some(action) = all_actions(cfg).remove(&name) else {
+        warn!(
+            "i3bar button {} runs unknown action \"{}\"",
+            click.button, name
+        );
+        return None;
+    };
+    Some(match action {
+        ActionConfig::Refresh => ClickAction::Daemon(Action::Refresh),
+        ActionConfig::Preset { preset } if preset == "next" => {
+            ClickAction::Daemon(Action::Cycle(1))
+        }
+        ActionConfig::Preset { preset } if preset == "prev" => {
+            ClickAction::Daemon(Action::Cycle(-1))
+        }
+        ActionConfig::Preset { .. } | ActionConfig::Units => {
+            ClickAction::Run { name, reload: true }
+        }
+        _ => ClickAction::Run {
+            name,
+            reload: false,
+        },
+    })
+}
+
+/// Reads click events from stdin on a background thread
+///
+/// The config is read again for every click, so `[i3bar]` and `[actions]`
+/// changes apply right away. The channel closes when stdin does.
+pub fn spawn_click_reader(global: &GlobalArgs) -> UnboundedReceiver<Action> {
+    let global = global.clone();
+    let (tx, rx) = unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            let Some(click) = parse_click(&line) else {
+                continue;
+            };
+            match click_action(&click, &load_config()) {
+                Some(ClickAction::Daemon(action)) if tx.send(action).is_err() => return,
+                Some(ClickAction::Run { name, reload }) => {
+                    run_action(&action_args(&global, &name), reload.then(|| tx.clone()))
+                }
+                _ => {}
+            }
+        }
+    });
+    rx
+}
+
+/// Arguments for `redweather action <name>` with the config the daemon was
+/// started with
+fn action_args(global: &GlobalArgs, name: &str) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["action".into(), name.into()];
+    if let Some(config) = &global.config {
+        args.extend(["--config".into(), config.clone().into_os_string()]);
+    }
+    for set in &global.set {
+        args.extend(["--set".into(), set.into()]);
+    }
+    if let Some(units) = global.units {
+        let units: crate::config::Units = units.into();
+        args.extend(["--units".into(), units.as_str().into()]);
+    }
+    args
+}
+
+/// Starts `redweather action ...` without waiting; with `reload`, tells the
+/// daemon to re-read the config once the action has finished
+fn run_action(args: &[OsString], reload: Option<UnboundedSender<Action>>) {
+    let exe = std::env::current_exe().unwrap_or_else(|_| "redweather".into());
+    // Our stdout is the status line; the action must not write to it
+    let spawned = Command::new(exe)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn();
+    let mut child = match spawned {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Cannot run action {:?}: {}", args.get(1), e);
+            return;
+        }
+    };
+    std::thread::spawn(move || {
+        let _ = child.wait();
+        if let Some(tx) = reload {
+            let _ = tx.send(Action::Reload);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_click() {
+        let click = |button| Click {
+            name: Some(BLOCK_NAME.into()),
+            button,
+        };
+        assert_eq!(parse_click("["), None);
+        assert_eq!(
+            parse_click(r#"[{"name":"redweather","button":1,"x":10}"#),
+            Some(click(1))
+        );
+        assert_eq!(
+            parse_click(r#",{"name":"redweather","button":4}"#),
+            Some(click(4))
+        );
+        let cfg = Config::default();
+        assert_eq!(
+            click_action(&click(5), &cfg),
+            Some(ClickAction::Daemon(Action::Cycle(1)))
+        );
+        assert_eq!(
+            click_action(&click(1), &cfg),
+            Some(ClickAction::Run {
+                name: "dashboard".into(),
+                reload: false
+            })
+        );
+        assert_eq!(click_action(&click(2), &cfg), None);
+        let other = Click {
+            name: Some("clock".into()),
+            button: 1,
+        };
+        assert_eq!(click_action(&other, &cfg), None);
+    }
+
+    #[test]
+    fn test_buttons_run_configured_actions() {
+        let cfg: Config = toml::from_str(
+            r#"
+[i3bar]
+left = "windy"
+middle = "units"
+right = ""
+scroll_up = "nope"
+"#,
+        )
+        .unwrap();
+        let click = |button| Click { name: None, button };
+        assert_eq!(
+            click_action(&click(1), &cfg),
+            Some(ClickAction::Run {
+                name: "windy".into(),
+                reload: false
+            })
+        );
+        assert_eq!(
+            click_action(&click(2), &cfg),
+            Some(ClickAction::Run {
+                name: "units".into(),
+                reload: true
+            })
+        );
+        assert_eq!(click_action(&click(3), &cfg), None);
+        assert_eq!(click_action(&click(4), &cfg), None);
+        // Unset buttons keep their defaults
+        assert_eq!(
+            click_action(&click(5), &cfg),
+            Some(ClickAction::Daemon(Action::Cycle(1)))
+        );
+    }
+
+    #[test]
+    fn test_block() {
+        let bar = json!({"text": "| ☁️ <span foreground='#81c8be'>73°F</span>", "class": []});
+        let block = block(&bar);
+        assert_eq!(block["name"], BLOCK_NAME);
+        assert_eq!(block["markup"], "pango");
+        assert_eq!(
+            block["short_text"],
+            "☁️ <span foreground='#81c8be'>73°F</span>"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Line-delimited JSON protocol for `redweather serve`
+//!
+//! Clients connect to the Unix socket at [`paths::socket_path`] and write one
+//! request per line, e.g. `{"cmd":"get"}`. Each request gets one response line;
+//! `subscribe` instead streams an `update` line now and after every refresh
+//! until the client disconnects. See README.md for the full protocol.
+//!
+//! The client side is blocking so the bar, the dashboard and `reload_waybar`
+//! can use it without an async runtime.
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tracing::warn;
+
+use crate::cli::Cli;
+use crate::config::{Config, LocationSettings, Units};
+use crate::overrides::{self, Override};
+use crate::paths;
+use crate::weather::{ApiResponse, FetchedWeather, Location, ResolvedLocation};
+
+/// How long a client waits for a response; a refresh may include retries
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A request line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum Request {
+    /// The current state
+    Get,
+    /// Fetch now, skipping the cache
+    Refresh,
+    /// Make the named preset active, or move `step` places through the list
+    Preset {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        step: Option<isize>,
+    },
+    /// Re-read the config file and API key
+    Reload,
+    /// The current state, then every update
+    Subscribe,
+}
+
+/// A response line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Response {
+    Update(Box<Snapshot>),
+    /// The request was carried out and the resulting update published
+    Ok,
+    Error {
+        message: String,
+    },
+}
+
+/// What the bar shows, and the data behind it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Exactly what `redweather bar` prints
+    pub bar: Value,
+    /// Missing when there is no weather to show
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weather: Option<SnapshotWeather>,
+    /// Why there is no weather
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The weather behind a snapshot, and the location and units it is for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotWeather {
+    pub location: Location,
+    /// The preset shown, unless the server was started with `--location`
+    pub preset: Option<String>,
+    pub units: Units,
+    pub fetched_at: i64,
+    /// True when the last fetch failed and older cached data is shown
+    pub stale: bool,
+    /// The provider's response, as stored in the cache
+    pub data: ApiResponse,
+}
+
+impl SnapshotWeather {
+    /// The weather with when it was fetched, as the formatters take it
+    pub fn fetched(&self) -> FetchedWeather {
+        FetchedWeather {
+            data: self.data.clone(),
+            fetched_at: self.fetched_at,
+            stale: self.stale,
+        }
+    }
+
+    /// Resolves the settings for the server's location from the local config
+    pub fn resolve(&self, cfg: &Config, api_key: &str) -> ResolvedLocation {
+        let preset = self.preset.as_ref().and_then(|name| {
+            cfg.location_presets
+                .iter()
+                .flatten()
+                .find(|p| &p.name == name)
+        });
+        ResolvedLocation {
+            location: self.location.clone(),
+            settings: LocationSettings::resolve(cfg, preset, api_key).with_units(self.units),
+        }
+    }
+}
+
+/// Whether a command may use the server's data instead of its own
+///
+/// The server shows the active preset with the config's settings, so any
+/// option that picks something else means fetching directly. That includes
+/// `REDWEATHER_*` overrides, which may differ from the server's environment.
+pub fn can_attach(cli: &Cli) -> bool {
+    can_attach_with(cli, &overrides::collect().0)
+}
+
+fn can_attach_with(cli: &Cli, overrides: &[Override]) -> bool {
+    let g = &cli.global;
+    cli.location().is_none()
+        && g.preset.is_none()
+        && g.units.is_none()
+        && g.config.is_none()
+        && g.set.is_empty()
+        && !g.reload
+        && overrides.is_empty()
+}
+
+/// A connection to `redweather serve`
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    /// Connects to the server; fails quickly if none is running
+    pub fn connect() -> anyhow::Result<Self> {
+        let path = paths::socket_path().context("Cannot determine the socket path")?;
+        let stream = UnixStream::connect(&path)
+            .with_context(|| format!("Cannot connect to {}", path.display()))?;
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+        Ok(Client {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    /// Sends a request and reads its response
+    pub fn request(&mut self, request: &Request) -> anyhow::Result<Response> {
+        let line = serde_json::to_string(request)?;
+        writeln!(self.writer, "{}", line)?;
+        self.read()
+    }
+
+    fn read(&mut self) -> anyhow::Result<Response> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            bail!("Server closed the connection");
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+/// Sends one request on a new connection; `error` responses become errors
+pub fn request(request: &Request) -> anyhow::Result<Response> {
+    match Client::connect()?.request(request)? {
+        Response::Error { message } => bail!(message),
+        response => Ok(response),
+    }
+}
+
+/// Subscribes to a running server's updates on a background thread
+///
+/// Returns `None` if no server is running. The channel closes when the server
+/// goes away.
+pub fn spawn_subscriber() -> Option<UnboundedReceiver<Snapshot>> {
+    let mut client = Client::connect().ok()?;
+    let line = serde_json::to_string(&Request::Subscribe).ok()?;
+    writeln!(client.writer, "{}", line).ok()?;
+    // Updates arrive only as often as the server refreshes
+    client.reader.get_ref().set_read_timeout(None).ok()?;
+
+    let (tx, rx) = unbounded_channel();
+    std::thread::spawn(move || loop {
+        match client.read() {
+            Ok(Response::Update(snapshot)) => {
+                if tx.send(*snapshot).is_err() {
+                    return;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                warn!("redweather serve: {}", e);
+                return;
+            }
+        }
+    });
+    Some(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn test_overrides_prevent_attaching() {
+        let cli = Cli::parse_from(["redweather", "bar"]);
+        assert!(can_attach_with(&cli, &[]));
+        let env = overrides::from_env(
+            [("REDWEATHER_UNITS".to_string(), "metric".to_string())].into_iter(),
+        );
+        assert!(!can_attach_with(&cli, &env));
+        let cli = Cli::parse_from(["redweather", "--units", "metric", "bar"]);
+        assert!(!can_attach_with(&cli, &[]));
+    }
+
+    #[test]
+    fn test_request_lines() {
+        let parse = |s: &str| serde_json::from_str::<Request>(s).unwrap();
+        assert_eq!(parse(r#"{"cmd":"get"}"#), Request::Get);
+        assert_eq!(
+            parse(r#"{"cmd":"preset","name":"work"}"#),
+            Request::Preset {
+                name: Some("work".into()),
+                step: None
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&Request::Preset {
+                name: None,
+                step: Some(-1)
+            })
+            .unwrap(),
+            r#"{"cmd":"preset","step":-1}"#
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"cmd":"bogus"}"#).is_err());
+    }
+
+    #[test]
+    fn test_response_lines() {
+        let update = Response::Update(Box::new(Snapshot {
+            bar: serde_json::json!({"text": "| ☁️ 73°F"}),
+            weather: None,
+            error: Some("Weather error: offline".into()),
+        }));
+        let line = serde_json::to_string(&update).unwrap();
+        assert_eq!(
+            line,
+            r#"{"type":"update","bar":{"text":"| ☁️ 73°F"},"error":"Weather error: offline"}"#
+        );
+        let Response::Update(back) = serde_json::from_str(&line).unwrap() else {
+            panic!("expected an update");
+        };
+        assert_eq!(back.bar["text"], "| ☁️ 73°F");
+        assert_eq!(
+            serde_json::to_string(&Response::Ok).unwrap(),
+            r#"{"type":"ok"}"#
+        );
+    }
+}
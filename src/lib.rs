@@ -0,0 +1,80 @@
+//! RedWeather - OpenWeatherMap weather for Waybar and other status bars
+//!
+//! The `redweather` binary is a thin command line over this library, so other
+//! tools can fetch, cache and format weather the same way:
+//!
+//! - [`config`]: config.toml, presets and the settings resolved per location
+//! - [`weather`]: the OpenWeatherMap client, geocoding and the cache
+//! - [`formatting`] and [`template`]: bar text and tooltips
+//! - [`waybar`], [`output`], [`export`] and [`report`]: Waybar JSON, other
+//!   bars, versioned JSON and the terminal forecast
+//! - [`ipc`]: a client for `redweather serve`
+//!
+//! ```no_run
+//! use redweather::config::{Config, LocationPreset};
+//! use redweather::waybar::bar_json;
+//! use redweather::weather::{load_weather, ResolvedLocation};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let cfg = Config::from_toml("units = \"metric\"")?;
+//! let preset = LocationPreset::new("berlin", 52.52, 13.405, "Berlin, DE");
+//! let resolved = ResolvedLocation::from_preset(&cfg, &preset, "your-api-key");
+//! let weather = load_weather(&resolved, false).await?;
+//! println!("{}", bar_json(&weather, &resolved, &cfg, &[]));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Config, cache and log locations follow the XDG directories (see
+//! [`paths`]), shared with the binary.
+
+pub mod config;
+pub mod export;
+pub mod formatting;
+pub mod ipc;
+pub mod output;
+pub mod paths;
+pub mod report;
+pub mod template;
+pub mod validate;
+pub mod waybar;
+pub mod weather;
+
+mod actions;
+mod cache;
+mod cli;
+mod commands;
+mod context;
+mod daemon;
+mod doctor;
+mod i3bar;
+mod logging;
+mod migrate;
+mod overrides;
+mod presets;
+mod serve;
+mod utils;
+
+/// Runs the `redweather` command line on the process arguments and returns
+/// the exit code
+///
+/// This is the binary's `main`, not part of the library API: it installs the
+/// global logger and copies legacy config directories.
+#[doc(hidden)]
+pub async fn main_entry() -> i32 {
+    use clap::Parser;
+
+    commands::run(cli::Cli::parse()).await
+}
+
+// The dashboard and settings window (the `gui` feature)
+#[cfg(feature = "gui")]
+mod astro;
+#[cfg(feature = "gui")]
+mod dashboard;
+#[cfg(feature = "gui")]
+mod gauges;
+#[cfg(feature = "gui")]
+mod graph;
+#[cfg(feature = "gui")]
+mod ui;
@@ -0,0 +1,158 @@
+//! Diagnostics on stderr and in an optional log file
+//!
+//! Waybar discards a module's stderr, so `[log] file = true` also writes to
+//! `$XDG_STATE_HOME/redweather/redweather.YYYY-MM-DD.log`, starting a new file
+//! every day and keeping the last `keep_days`. The file has its own `level`.
+//!
+//! On stderr only warnings and errors are shown by default. `-q` leaves just
+//! errors, `-v` adds fetches and cache hits, `-vv` debug detail and `-vvv`
+//! everything, including the HTTP client. Without either flag, `RUST_LOG`
+//! takes a filter such as `redweather=debug`.
+
+use std::io::IsTerminal;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+
+use crate::config::LogConfig;
+use crate::paths;
+
+/// Level for the log file when `log.level` is not set
+pub const DEFAULT_FILE_LEVEL: &str = "info";
+/// Days of log files kept when `log.keep_days` is not set
+pub const DEFAULT_KEEP_DAYS: usize = 7;
+
+/// Installs the global logger; call once, before anything logs
+pub fn init(verbose: u8, quiet: bool, log: &LogConfig) {
+    let stderr = fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(
+            std::io::stderr().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        )
+        .without_time()
+        .with_target(false)
+        .with_filter(stderr_filter(
+            verbose,
+            quiet,
+            std::env::var("RUST_LOG").ok(),
+        ));
+
+    let file = log.file.unwrap_or(false).then(|| file_layer(log)).flatten();
+
+    tracing_subscriber::registry()
+        .with(stderr)
+        .with(file)
+        .init();
+}
+
+/// The rotating file layer, or `None` (with a note on stderr) if the state
+/// directory is unusable
+fn file_layer<S>(log: &LogConfig) -> Option<impl tracing_subscriber::Layer<S>>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    let dir = paths::state_dir()?;
+    // The logger is not installed yet, so problems go straight to stderr
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        eprintln!("Cannot create {}: {}", dir.display(), e);
+        return None;
+    }
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("redweather")
+        .filename_suffix("log")
+        .max_log_files(log.keep_days.unwrap_or(DEFAULT_KEEP_DAYS).max(1))
+        .build(&dir);
+    let appender = match appender {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("Cannot write logs to {}: {}", dir.display(), e);
+            return None;
+        }
+    };
+    let level = log.level.as_deref().unwrap_or(DEFAULT_FILE_LEVEL);
+    Some(
+        fmt::layer()
+            .with_writer(appender)
+            .with_ansi(false)
+            .with_filter(file_filter(level)),
+    )
+}
+
+/// The stderr filter for the given flags and `RUST_LOG`
+fn stderr_filter(verbose: u8, quiet: bool, rust_log: Option<String>) -> EnvFilter {
+    let directives = match (quiet, verbose) {
+        (true, _) => "error".to_string(),
+        (false, 0) => match rust_log.filter(|f| !f.trim().is_empty()) {
+            Some(filter) => return EnvFilter::new(filter),
+            None => "warn".to_string(),
+        },
+        (false, 1) => "warn,redweather=info".to_string(),
+        (false, 2) => "warn,redweather=debug".to_string(),
+        (false, _) => "trace".to_string(),
+    };
+    EnvFilter::new(directives)
+}
+
+/// The file filter: a bare level applies to redweather only, anything else
+/// is taken as `RUST_LOG` directives
+fn file_filter(level: &str) -> EnvFilter {
+    if level.parse::<tracing::Level>().is_ok() {
+        return EnvFilter::new(format!("warn,redweather={}", level));
+    }
+    EnvFilter::try_new(level).unwrap_or_else(|_| file_filter(DEFAULT_FILE_LEVEL))
+}
+
+/// Checks a `log.level` value, returning the parser's complaint
+pub fn check_level(level: &str) -> Result<(), String> {
+    if level.parse::<tracing::Level>().is_ok() {
+        return Ok(());
+    }
+    EnvFilter::try_new(level)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// An API key as it may appear in logs: its last four characters
+pub fn redact_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "****".into();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_key() {
+        assert_eq!(redact_key("0123456789abcdef0123456789abcdef"), "****cdef");
+        assert_eq!(redact_key("short"), "****");
+        assert_eq!(redact_key(""), "****");
+    }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(stderr_filter(0, false, None).to_string(), "warn");
+        assert_eq!(
+            stderr_filter(0, false, Some("redweather=trace".into())).to_string(),
+            "redweather=trace"
+        );
+        // Flags beat RUST_LOG
+        assert_eq!(
+            stderr_filter(0, true, Some("debug".into())).to_string(),
+            "error"
+        );
+        assert!(stderr_filter(2, false, None)
+            .to_string()
+            .contains("redweather=debug"));
+        assert!(check_level("debug").is_ok());
+        assert!(check_level("warn,redweather::weather=trace").is_ok());
+        assert!(check_level("redweather=loud").is_err());
+    }
+}
@@ -0,0 +1,397 @@
+//! Config schema versioning and migrations
+//!
+//! Every config file carries a `config_version` key (files written before it
+//! existed are version 0). On load, the raw TOML table is passed through each
+//! registered migration step from its version up to [`CONFIG_VERSION`] before it
+//! is deserialized into [`crate::config::Config`].
+//!
+//! To change the schema, bump [`CONFIG_VERSION`] and append a step to
+//! `MIGRATIONS` that upgrades from the previous version.
+
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+use tracing::{info, warn};
+
+/// Current config schema version
+pub const CONFIG_VERSION: u32 = 1;
+/// Legacy home location file (JSON) inside the config directory
+const HOME_LOCATION_FILE: &str = "home_location.json";
+
+/// Environment available to migration steps
+pub struct MigrationContext<'a> {
+    /// Directory holding legacy side files such as home_location.json
+    pub config_dir: Option<&'a Path>,
+    /// Files absorbed into the config; removed only once the config is written
+    pub consumed_files: Vec<PathBuf>,
+}
+
+impl<'a> MigrationContext<'a> {
+    pub fn new(config_dir: Option<&'a Path>) -> Self {
+        MigrationContext {
+            config_dir,
+            consumed_files: Vec::new(),
+        }
+    }
+}
+
+/// A single schema upgrade from `from` to `from + 1`
+struct Migration {
+    from: u32,
+    name: &'static str,
+    /// Applies the step and returns a human-readable line per change made
+    apply: fn(&mut Table, &mut MigrationContext) -> Vec<String>,
+}
+
+/// Ordered registry of migration steps; `MIGRATIONS[i].from == i`
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    name: "import legacy locations",
+    apply: import_legacy_locations,
+}];
+
+/// Summary of a migration run
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+    pub changes: Vec<String>,
+}
+
+impl MigrationReport {
+    /// True if the file content changed and should be rewritten
+    pub fn changed(&self) -> bool {
+        !self.changes.is_empty()
+    }
+}
+
+/// Reads `config_version` from a raw config table (missing means 0)
+pub fn table_version(table: &Table) -> u32 {
+    table
+        .get("config_version")
+        .and_then(Value::as_integer)
+        .and_then(|v| u32::try_from(v).ok())
+        .unwrap_or(0)
+}
+
+/// Runs every migration step the table still needs, logging each change
+///
+/// The table is stamped with [`CONFIG_VERSION`] afterwards. Tables from a newer
+/// release are left untouched.
+pub fn migrate_table(table: &mut Table, ctx: &mut MigrationContext) -> MigrationReport {
+    let from = table_version(table);
+    if from > CONFIG_VERSION {
+        warn!(
+            "Config version {} is newer than supported version {}; loading as-is",
+            from, CONFIG_VERSION
+        );
+        return MigrationReport {
+            from,
+            to: from,
+            changes: Vec::new(),
+        };
+    }
+
+    let mut changes = Vec::new();
+    for step in MIGRATIONS.iter().filter(|m| m.from >= from) {
+        for change in (step.apply)(table, ctx) {
+            info!(
+                "Config migration v{} -> v{} ({}): {}",
+                step.from,
+                step.from + 1,
+                step.name,
+                change
+            );
+            changes.push(change);
+        }
+    }
+
+    table.insert(
+        "config_version".to_string(),
+        Value::Integer(i64::from(CONFIG_VERSION)),
+    );
+
+    MigrationReport {
+        from,
+        to: CONFIG_VERSION,
+        changes,
+    }
+}
+
+/// Copies `path` aside before a migration rewrites it
+///
+/// The backup is named `<file>.v<version>.bak`; an existing backup is never
+/// overwritten, a timestamped name is used instead.
+pub fn backup_config_file(path: &Path, version: u32) -> std::io::Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "config.toml".to_string());
+    let mut backup = path.with_file_name(format!("{}.v{}.bak", file_name, version));
+    if backup.exists() {
+        let ts = chrono::Utc::now().timestamp();
+        backup = path.with_file_name(format!("{}.v{}.{}.bak", file_name, version, ts));
+    }
+    fs::copy(path, &backup)?;
+    Ok(backup)
+}
+
+/// v0 -> v1: imports home_location.json and the `[location]` table as presets
+///
+/// Before presets existed, the location lived either in a separate
+/// home_location.json file or in a single `[location]` table.
+fn import_legacy_locations(table: &mut Table, ctx: &mut MigrationContext) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if let Some(dir) = ctx.config_dir {
+        let path = dir.join(HOME_LOCATION_FILE);
+        if let Some(home) = read_legacy_home(&path) {
+            let label = home.label.unwrap_or_else(|| "Home".to_string());
+            if upsert_preset(table, "home", home.lat, home.lon, &label) {
+                changes.push(format!(
+                    "imported {} as preset \"home\"",
+                    HOME_LOCATION_FILE
+                ));
+                ctx.consumed_files.push(path);
+            }
+        }
+    }
+
+    if let Some(legacy) = table.remove("location") {
+        let lat = legacy.get("lat").and_then(as_float);
+        let lon = legacy.get("lon").and_then(as_float);
+        let has_presets = table
+            .get("location_presets")
+            .and_then(Value::as_array)
+            .is_some_and(|p| !p.is_empty());
+
+        match (lat, lon) {
+            // If presets already exist, don't let the old table override them
+            (Some(_), Some(_)) if has_presets => {
+                changes.push("dropped [location] table (presets already defined)".to_string())
+            }
+            (Some(lat), Some(lon)) => {
+                let label = legacy
+                    .get("label")
+                    .and_then(Value::as_str)
+                    .unwrap_or("Imported location")
+                    .to_string();
+                upsert_preset(table, "imported", lat, lon, &label);
+                changes.push("imported [location] table as preset \"imported\"".to_string());
+            }
+            _ => changes.push("dropped incomplete [location] table".to_string()),
+        }
+    }
+
+    changes
+}
+
+#[derive(Deserialize)]
+struct LegacyHome {
+    lat: f64,
+    lon: f64,
+    label: Option<String>,
+}
+
+fn read_legacy_home(path: &Path) -> Option<LegacyHome> {
+    let contents = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&contents) {
+        Ok(home) => Some(home),
+        Err(e) => {
+            warn!("Ignoring unreadable {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Accepts both `40` and `40.0` for coordinates
+fn as_float(v: &Value) -> Option<f64> {
+    v.as_float().or_else(|| v.as_integer().map(|i| i as f64))
+}
+
+/// Inserts a preset if none has the same name and sets active_preset if unset
+///
+/// Returns whether the preset was inserted.
+fn upsert_preset(table: &mut Table, name: &str, lat: f64, lon: f64, label: &str) -> bool {
+    let presets = table
+        .entry("location_presets")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    let presets = match presets.as_array_mut() {
+        Some(p) => p,
+        None => return false,
+    };
+
+    // Leave an existing entry intact to avoid overwriting user data
+    if presets
+        .iter()
+        .any(|p| p.get("name").and_then(Value::as_str) == Some(name))
+    {
+        return false;
+    }
+
+    let mut preset = Table::new();
+    preset.insert("name".into(), Value::String(name.into()));
+    preset.insert("lat".into(), Value::Float(lat));
+    preset.insert("lon".into(), Value::Float(lon));
+    preset.insert("label".into(), Value::String(label.into()));
+    presets.push(Value::Table(preset));
+
+    if !table.contains_key("active_preset") {
+        let first = table
+            .get("location_presets")
+            .and_then(Value::as_array)
+            .and_then(|p| p.first())
+            .and_then(|p| p.get("name"))
+            .cloned();
+        if let Some(first) = first {
+            table.insert("active_preset".into(), first);
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Table {
+        toml::from_str(s).expect("test TOML should parse")
+    }
+
+    fn preset_names(table: &Table) -> Vec<String> {
+        table
+            .get("location_presets")
+            .and_then(Value::as_array)
+            .map(|p| {
+                p.iter()
+                    .filter_map(|p| p.get("name").and_then(Value::as_str))
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_registry_is_contiguous() {
+        assert_eq!(MIGRATIONS.len() as u32, CONFIG_VERSION);
+        for (i, m) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(m.from, i as u32, "migration {} is out of order", m.name);
+        }
+    }
+
+    #[test]
+    fn test_legacy_location_section_becomes_preset() {
+        let mut table = parse(
+            r#"
+units = "metric"
+[location]
+lat = 52.52
+lon = 13
+label = "Berlin"
+"#,
+        );
+        let report = migrate_table(&mut table, &mut MigrationContext::new(None));
+
+        assert_eq!(report.from, 0);
+        assert_eq!(report.to, CONFIG_VERSION);
+        assert!(report.changed());
+        assert!(!table.contains_key("location"));
+        assert_eq!(preset_names(&table), vec!["imported"]);
+        assert_eq!(table["active_preset"].as_str(), Some("imported"));
+        assert_eq!(table["location_presets"][0]["lon"].as_float(), Some(13.0));
+        assert_eq!(
+            table["location_presets"][0]["label"].as_str(),
+            Some("Berlin")
+        );
+        assert_eq!(table_version(&table), CONFIG_VERSION);
+    }
+
+    #[test]
+    fn test_legacy_location_section_does_not_override_presets() {
+        let mut table = parse(
+            r#"
+active_preset = "work"
+[location]
+lat = 1.0
+lon = 2.0
+[[location_presets]]
+name = "work"
+lat = 3.0
+lon = 4.0
+label = "Work"
+"#,
+        );
+        let report = migrate_table(&mut table, &mut MigrationContext::new(None));
+
+        assert!(report.changed());
+        assert!(!table.contains_key("location"));
+        assert_eq!(preset_names(&table), vec!["work"]);
+        assert_eq!(table["active_preset"].as_str(), Some("work"));
+    }
+
+    #[test]
+    fn test_home_location_json_becomes_preset() {
+        let dir = std::env::temp_dir().join("redweather_test_migrate_home");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(HOME_LOCATION_FILE),
+            r#"{"lat": 37.5, "lon": -97.2, "label": "Wichita"}"#,
+        )
+        .unwrap();
+
+        let mut table = parse("units = \"imperial\"\n");
+        let mut ctx = MigrationContext::new(Some(&dir));
+        let report = migrate_table(&mut table, &mut ctx);
+
+        assert!(report.changed());
+        assert_eq!(preset_names(&table), vec!["home"]);
+        assert_eq!(table["active_preset"].as_str(), Some("home"));
+        // The JSON file is only recorded here; the caller removes it after writing
+        assert_eq!(ctx.consumed_files, vec![dir.join(HOME_LOCATION_FILE)]);
+        assert!(dir.join(HOME_LOCATION_FILE).exists());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_current_version_is_not_migrated_again() {
+        let mut table = parse(&format!(
+            "config_version = {}\n[location]\nlat = 1.0\nlon = 2.0\n",
+            CONFIG_VERSION
+        ));
+        let report = migrate_table(&mut table, &mut MigrationContext::new(None));
+        assert!(!report.changed());
+        assert!(table.contains_key("location"));
+    }
+
+    #[test]
+    fn test_unversioned_config_without_legacy_data_is_unchanged() {
+        let mut table = parse("units = \"metric\"\n");
+        let report = migrate_table(&mut table, &mut MigrationContext::new(None));
+        assert!(!report.changed());
+        assert_eq!(table_version(&table), CONFIG_VERSION);
+    }
+
+    #[test]
+    fn test_backup_never_overwrites() {
+        let dir = std::env::temp_dir().join("redweather_test_migrate_backup");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, "first").unwrap();
+
+        let first = backup_config_file(&path, 0).unwrap();
+        assert_eq!(first, dir.join("config.toml.v0.bak"));
+        fs::write(&path, "second").unwrap();
+        let second = backup_config_file(&path, 0).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(fs::read_to_string(first).unwrap(), "first");
+        assert_eq!(fs::read_to_string(second).unwrap(), "second");
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,239 @@
+//! Bar output for status bars other than Waybar
+//!
+//! Everything is formatted for Waybar first: pango markup in `text` and
+//! `tooltip`, plus `class`, `alt` and `percentage`. The other backends convert
+//! that JSON, so the bar, the daemon and clients of `redweather serve` all
+//! support every format. Markup the target cannot show is dropped; colors are
+//! kept where the target has a way to express them.
+
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use crate::i3bar;
+
+/// Output format for `bar` and `daemon`
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Waybar JSON with pango markup
+    #[default]
+    Waybar,
+    /// Text with polybar `%{F#rrggbb}` color tags
+    Polybar,
+    /// i3blocks lines: full_text, short_text and color
+    I3blocks,
+    /// JSON with plain text for yambar, eww and scripts
+    Json,
+    /// Plain UTF-8 text for tmux status lines and shell prompts
+    Plain,
+    /// An i3bar/swaybar block; `daemon` speaks the whole protocol
+    I3bar,
+}
+
+/// Renders Waybar JSON in the given format, without a trailing newline
+pub fn render(bar: &Value, format: OutputFormat) -> String {
+    let text = bar["text"].as_str().unwrap_or_default();
+    match format {
+        OutputFormat::Waybar => bar.to_string(),
+        OutputFormat::Polybar => polybar(text),
+        OutputFormat::I3blocks => {
+            let runs = spans(text);
+            let full = plain(&runs);
+            // The leading "| " only separates modules in Waybar
+            let short = full.trim_start_matches('|').trim();
+            let mut lines = format!("{}\n{}", full, short);
+            if let Some(color) = first_color(&runs) {
+                lines.push('\n');
+                lines.push_str(color);
+            }
+            lines
+        }
+        OutputFormat::Json => {
+            let runs = spans(text);
+            let tooltip = spans(bar["tooltip"].as_str().unwrap_or_default());
+            json!({
+                "text": plain(&runs),
+                "tooltip": plain(&tooltip),
+                "color": first_color(&runs),
+                "class": bar["class"],
+                "alt": bar["alt"],
+                "percentage": bar["percentage"],
+            })
+            .to_string()
+        }
+        OutputFormat::Plain => plain(&spans(text)),
+        OutputFormat::I3bar => i3bar::block(bar).to_string(),
+    }
+}
+
+/// A run of text and the innermost `foreground` around it
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    text: String,
+    color: Option<String>,
+}
+
+/// Splits pango markup into text runs, resolving entities
+fn spans(markup: &str) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    // One entry per open tag; `None` for tags without a color
+    let mut stack: Vec<Option<String>> = Vec::new();
+    let mut rest = markup;
+    while !rest.is_empty() {
+        if let Some(tag) = rest.strip_prefix('<') {
+            let Some(end) = tag.find('>') else {
+                break;
+            };
+            let tag = &tag[..end];
+            if tag.starts_with('/') {
+                stack.pop();
+            } else {
+                stack.push(tag_color(tag));
+            }
+            rest = &rest[end + 2..];
+            continue;
+        }
+        let end = rest.find('<').unwrap_or(rest.len());
+        let text = unescape(&rest[..end]);
+        rest = &rest[end..];
+        let color = stack.iter().rev().find_map(Clone::clone);
+        match spans.last_mut() {
+            Some(last) if last.color == color => last.text.push_str(&text),
+            _ => spans.push(Span { text, color }),
+        }
+    }
+    spans
+}
+
+/// The `foreground` (or `color`/`fgcolor`) attribute of a `<span>` tag
+fn tag_color(tag: &str) -> Option<String> {
+    let attrs = tag.strip_prefix("span")?;
+    ["foreground", "fgcolor", "color"].iter().find_map(|name| {
+        let start = attrs.find(&format!(" {}=", name))? + name.len() + 2;
+        let value = &attrs[start..];
+        let quote = value.chars().next().filter(|q| *q == '\'' || *q == '"')?;
+        let value = &value[1..];
+        Some(value[..value.find(quote)?].to_string())
+    })
+}
+
+/// Resolves the entities pango markup uses
+fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let resolved = entity.and_then(|(name, end)| {
+            let c = match name {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "apos" => '\'',
+                "quot" => '"',
+                _ => {
+                    let code = match name.strip_prefix("#x") {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => name.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end))
+        });
+        match resolved {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn plain(spans: &[Span]) -> String {
+    spans.iter().map(|s| s.text.as_str()).collect()
+}
+
+fn first_color(spans: &[Span]) -> Option<&str> {
+    spans.iter().find_map(|s| s.color.as_deref())
+}
+
+/// Polybar text: colors become `%{F#rrggbb}`…`%{F-}` and `%` is escaped
+fn polybar(markup: &str) -> String {
+    let mut out = String::new();
+    for span in spans(markup) {
+        let text = span.text.replace('%', "%%");
+        match span.color {
+            Some(c) => out.push_str(&format!("%{{F{}}}{}%{{F-}}", c, text)),
+            None => out.push_str(&text),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar() -> Value {
+        json!({
+            "text": "| ☁️ <span foreground='#81c8be'>73°F</span> 💧40%",
+            "tooltip": "<b>Home &amp; Away</b>\n<span size='large'>73°F</span>",
+            "markup": "pango",
+            "class": ["cloudy"],
+            "alt": "cloudy",
+            "percentage": 41,
+        })
+    }
+
+    #[test]
+    fn test_spans() {
+        let got = spans("a <b>b <span foreground=\"#fff\">c &lt;3</span></b>&#x263A;&bogus");
+        assert_eq!(
+            got,
+            vec![
+                Span {
+                    text: "a b ".into(),
+                    color: None
+                },
+                Span {
+                    text: "c <3".into(),
+                    color: Some("#fff".into())
+                },
+                Span {
+                    text: "☺&bogus".into(),
+                    color: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backends() {
+        let bar = bar();
+        assert_eq!(render(&bar, OutputFormat::Waybar), bar.to_string());
+        assert_eq!(render(&bar, OutputFormat::Plain), "| ☁️ 73°F 💧40%");
+        assert_eq!(
+            render(&bar, OutputFormat::Polybar),
+            "| ☁️ %{F#81c8be}73°F%{F-} 💧40%%"
+        );
+        assert_eq!(
+            render(&bar, OutputFormat::I3blocks),
+            "| ☁️ 73°F 💧40%\n☁️ 73°F 💧40%\n#81c8be"
+        );
+        let json: Value = serde_json::from_str(&render(&bar, OutputFormat::Json)).unwrap();
+        assert_eq!(json["text"], "| ☁️ 73°F 💧40%");
+        assert_eq!(json["tooltip"], "Home & Away\n73°F");
+        assert_eq!(json["color"], "#81c8be");
+        assert_eq!(json["percentage"], 41);
+    }
+}
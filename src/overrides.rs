@@ -0,0 +1,311 @@
+//! Environment and command-line overrides for config keys
+//!
+//! Any config key can be overridden without touching config.toml, which lets
+//! several bars share one file:
+//!
+//! - environment: `REDWEATHER_UNITS=metric`, `REDWEATHER_DASHBOARD__FORECAST_DAYS=7`
+//!   (`__` separates nested keys)
+//! - command line: `--set units=metric --set dashboard.forecast_days=7`
+//!
+//! Layers apply in that order on top of the file, so `--set` wins. Numeric path
+//! segments index into arrays (`location_presets.0.label`). Values are parsed as
+//! TOML when possible (`7`, `true`, `[...]`) and otherwise taken as a string.
+//! A `units` override becomes [`Config::units_override`](crate::config::Config::units_override),
+//! like `--units`, so temperature bands keep the file's units.
+
+use once_cell::sync::OnceCell;
+use std::env;
+use std::fmt;
+use toml::{Table, Value};
+
+/// Prefix for override environment variables
+pub const ENV_PREFIX: &str = "REDWEATHER_";
+
+/// Top-level `Config` keys that may be overridden
+///
+/// Other `REDWEATHER_*` variables (e.g. `REDWEATHER_CACHE_DIR`) are not config
+/// keys and are ignored here.
+pub const CONFIG_KEYS: &[&str] = &[
+    "units",
+    "location_presets",
+    "active_preset",
+    "bar_presets",
+    "ui",
+    "colors",
+    "temp_bands",
+    "time",
+    "format",
+    "waybar",
+    "i3bar",
+    "actions",
+    "dashboard",
+    "cache",
+    "log",
+];
+
+/// `--set key=value` arguments given on the command line
+static CLI_OVERRIDES: OnceCell<Vec<String>> = OnceCell::new();
+
+/// Records the `--set` arguments for the rest of the process
+///
+/// Only the first call has an effect.
+pub fn set_cli_overrides(args: Vec<String>) {
+    let _ = CLI_OVERRIDES.set(args);
+}
+
+/// A single key override
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    pub path: Vec<String>,
+    pub value: Value,
+    /// Where the override came from, for error messages
+    pub source: String,
+}
+
+impl fmt::Display for Override {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.path.join("."), self.source)
+    }
+}
+
+/// Collects all active overrides: environment first, then `--set`
+///
+/// Malformed `--set` arguments are returned as errors alongside the valid ones.
+pub fn collect() -> (Vec<Override>, Vec<String>) {
+    let mut overrides = from_env(env::vars());
+    let mut errors = Vec::new();
+    for arg in CLI_OVERRIDES.get().map(Vec::as_slice).unwrap_or(&[]) {
+        match parse_set(arg) {
+            Ok(o) => overrides.push(o),
+            Err(e) => errors.push(e),
+        }
+    }
+    (overrides, errors)
+}
+
+/// Extracts overrides from `REDWEATHER_*` environment variables
+pub fn from_env(vars: impl Iterator<Item = (String, String)>) -> Vec<Override> {
+    let mut overrides: Vec<Override> = vars
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(ENV_PREFIX)?;
+            let path: Vec<String> = key.split("__").map(|s| s.to_lowercase()).collect();
+            if !CONFIG_KEYS.contains(&path[0].as_str()) || path.iter().any(String::is_empty) {
+                return None;
+            }
+            Some(Override {
+                path,
+                value: parse_value(&value),
+                source: name,
+            })
+        })
+        .collect();
+    // Environment order is unspecified; apply parents before children
+    overrides.sort_by(|a, b| a.path.len().cmp(&b.path.len()).then(a.path.cmp(&b.path)));
+    overrides
+}
+
+/// Parses a `--set key=value` argument
+pub fn parse_set(arg: &str) -> Result<Override, String> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("--set {}: expected key=value", arg))?;
+    let path: Vec<String> = key
+        .trim()
+        .split('.')
+        .map(|s| s.trim().to_string())
+        .collect();
+    if path.iter().any(String::is_empty) {
+        return Err(format!("--set {}: empty key segment", arg));
+    }
+    if !CONFIG_KEYS.contains(&path[0].as_str()) {
+        return Err(format!("--set {}: unknown config key \"{}\"", arg, path[0]));
+    }
+    Ok(Override {
+        path,
+        value: parse_value(value.trim()),
+        source: "--set".to_string(),
+    })
+}
+
+/// Parses a raw value as a TOML value, falling back to a plain string
+fn parse_value(raw: &str) -> Value {
+    toml::from_str::<Table>(&format!("v = {}", raw))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+/// Applies overrides to a raw config table, returning one error per override
+/// that could not be applied
+///
+/// A failed override leaves the table untouched.
+pub fn apply(table: &mut Table, overrides: &[Override]) -> Vec<String> {
+    let mut errors = Vec::new();
+    for o in overrides {
+        let mut candidate = table.clone();
+        match apply_one(&mut candidate, o) {
+            Ok(()) => *table = candidate,
+            Err(e) => errors.push(format!("override {}: {}", o, e)),
+        }
+    }
+    errors
+}
+
+fn apply_one(table: &mut Table, o: &Override) -> Result<(), String> {
+    let mut segs = o.path.iter();
+    let first = segs.next().ok_or("empty key")?;
+    let mut node = table
+        .entry(first.clone())
+        .or_insert_with(|| Value::Table(Table::new()));
+    for seg in segs {
+        node = child_mut(node, seg)?;
+    }
+    *node = o.value.clone();
+    Ok(())
+}
+
+/// Descends one level, creating missing tables but never missing array items
+fn child_mut<'a>(node: &'a mut Value, seg: &str) -> Result<&'a mut Value, String> {
+    match node {
+        Value::Table(_) if seg.parse::<usize>().is_ok() => {
+            Err(format!("cannot index \"{}\" into a table", seg))
+        }
+        Value::Table(t) => Ok(t
+            .entry(seg.to_string())
+            .or_insert_with(|| Value::Table(Table::new()))),
+        Value::Array(items) => {
+            let len = items.len();
+            let idx: usize = seg
+                .parse()
+                .map_err(|_| format!("\"{}\" is not an array index", seg))?;
+            items
+                .get_mut(idx)
+                .ok_or_else(|| format!("index {} out of range ({} items)", idx, len))
+        }
+        _ => Err(format!("cannot set \"{}\" on a plain value", seg)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, DashboardConfig, LocationPreset, TempBand};
+
+    fn env(pairs: &[(&str, &str)]) -> Vec<Override> {
+        from_env(pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let overrides = env(&[
+            ("REDWEATHER_DASHBOARD__FORECAST_DAYS", "7"),
+            ("REDWEATHER_UNITS", "metric"),
+            ("REDWEATHER_CACHE_DIR", "/tmp/x"),
+            ("HOME", "/home/me"),
+        ]);
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides[0].path, vec!["units"]);
+        assert_eq!(overrides[0].value, Value::String("metric".into()));
+        assert_eq!(overrides[1].path, vec!["dashboard", "forecast_days"]);
+        assert_eq!(overrides[1].value, Value::Integer(7));
+    }
+
+    #[test]
+    fn test_parse_set() {
+        let o = parse_set("colors.header=#ff0000").unwrap();
+        assert_eq!(o.path, vec!["colors", "header"]);
+        assert_eq!(o.value, Value::String("#ff0000".into()));
+
+        let o = parse_set("dashboard.show_hourly_graph = false").unwrap();
+        assert_eq!(o.value, Value::Boolean(false));
+
+        assert!(parse_set("units").is_err());
+        assert!(parse_set("bogus=1").is_err());
+        assert!(parse_set("ui..font_size=1").is_err());
+    }
+
+    #[test]
+    fn test_apply_layers_onto_config() {
+        let mut table: Table = toml::from_str(
+            r#"
+units = "imperial"
+active_preset = "home"
+[[location_presets]]
+name = "home"
+lat = 1.0
+lon = 2.0
+label = "Home"
+"#,
+        )
+        .unwrap();
+
+        let mut overrides = env(&[("REDWEATHER_UNITS", "metric")]);
+        overrides.push(parse_set("units=imperial").unwrap());
+        overrides.push(parse_set("dashboard.forecast_days=7").unwrap());
+        overrides.push(parse_set("location_presets.0.label=Casa").unwrap());
+
+        assert!(apply(&mut table, &overrides).is_empty());
+        let cfg: Config = Value::Table(table).try_into().unwrap();
+
+        // --set beats the environment
+        assert_eq!(cfg.units, crate::config::Units::Imperial);
+        assert_eq!(cfg.dashboard.unwrap().forecast_days, 7);
+        assert_eq!(cfg.location_presets.unwrap()[0].label, "Casa");
+    }
+
+    #[test]
+    fn test_apply_reports_bad_paths() {
+        let mut table: Table = toml::from_str("units = \"metric\"").unwrap();
+        let errors = apply(
+            &mut table,
+            &[
+                parse_set("units.sub=1").unwrap(),
+                parse_set("location_presets.3.lat=1").unwrap(),
+            ],
+        );
+        assert_eq!(errors.len(), 2);
+        assert_eq!(table["units"].as_str(), Some("metric"));
+        assert!(!table.contains_key("location_presets"));
+    }
+
+    #[test]
+    fn test_config_keys_cover_every_field() {
+        let full = Config {
+            location_presets: Some(vec![LocationPreset::new("a", 0.0, 0.0, "A")]),
+            active_preset: Some("a".into()),
+            bar_presets: Some(vec!["a".into()]),
+            ui: Some(crate::config::UiConfig {
+                font_size: Some(9),
+                max_desc_len: Some(10),
+            }),
+            colors: Some(crate::config::ColorsConfig {
+                header: None,
+                text: None,
+            }),
+            temp_bands: Some(vec![TempBand {
+                max: 1.0,
+                color: "#fff".into(),
+            }]),
+            time: Some(crate::config::TimeConfig::default()),
+            format: Some(crate::config::FormatConfig::default()),
+            waybar: Some(crate::config::WaybarConfig::default()),
+            i3bar: Some(crate::config::I3barConfig::default()),
+            actions: Some(std::collections::BTreeMap::from([(
+                "a".to_string(),
+                crate::config::ActionConfig::Units,
+            )])),
+            dashboard: Some(DashboardConfig::default()),
+            log: Some(crate::config::LogConfig::default()),
+            cache: Some(crate::config::CacheConfig::default()),
+            ..Config::default()
+        };
+        let table = toml::Table::try_from(&full).unwrap();
+        for key in table.keys().filter(|k| *k != "config_version") {
+            assert!(
+                CONFIG_KEYS.contains(&key.as_str()),
+                "config key {} cannot be overridden",
+                key
+            );
+        }
+    }
+}
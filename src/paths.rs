@@ -0,0 +1,253 @@
+//! Filesystem locations for config, cache and state files
+//!
+//! Follows the XDG base-directory spec: `$XDG_CONFIG_HOME`, `$XDG_CACHE_HOME` and
+//! `$XDG_STATE_HOME`, falling back to `~/.config`, `~/.cache` and `~/.local/state`.
+
+use once_cell::sync::OnceCell;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Directory name used under each XDG base directory
+const APP_DIR: &str = "redweather";
+/// Config file name inside the config directory
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+/// API key file name inside the config directory
+pub const KEY_FILE_NAME: &str = "apikey";
+
+/// Alternate config file selected with `--config <path>`
+static CONFIG_OVERRIDE: OnceCell<PathBuf> = OnceCell::new();
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Resolves an XDG base directory, ignoring relative paths as the spec requires
+fn xdg_base(var: &str, home_fallback: &str) -> Option<PathBuf> {
+    match env::var_os(var) {
+        Some(v) if Path::new(&v).is_absolute() => Some(PathBuf::from(v)),
+        _ => home_dir().map(|h| h.join(home_fallback)),
+    }
+}
+
+/// Returns the config directory (`$XDG_CONFIG_HOME/redweather`)
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_base("XDG_CONFIG_HOME", ".config").map(|d| d.join(APP_DIR))
+}
+
+/// Returns the cache directory (`$XDG_CACHE_HOME/redweather`)
+///
+/// `REDWEATHER_CACHE_DIR` takes precedence. It names the directory itself;
+/// older releases put the cache in `$REDWEATHER_CACHE_DIR/.cache/redweather`.
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("REDWEATHER_CACHE_DIR").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    xdg_base("XDG_CACHE_HOME", ".cache").map(|d| d.join(APP_DIR))
+}
+
+/// Returns the state directory (`$XDG_STATE_HOME/redweather`), where log files go
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_base("XDG_STATE_HOME", ".local/state").map(|d| d.join(APP_DIR))
+}
+
+/// Returns the socket path for `redweather serve`
+///
+/// `$XDG_RUNTIME_DIR/redweather.sock`, else the cache directory.
+/// `REDWEATHER_SOCKET` takes precedence.
+pub fn socket_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("REDWEATHER_SOCKET").filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if Path::new(&dir).is_absolute() => {
+            Some(Path::new(&dir).join(format!("{}.sock", APP_DIR)))
+        }
+        _ => cache_dir().map(|d| d.join(format!("{}.sock", APP_DIR))),
+    }
+}
+
+/// Returns the files Waybar reads its config from, in its search order
+pub fn waybar_config_files() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = xdg_base("XDG_CONFIG_HOME", ".config")
+        .map(|d| d.join("waybar"))
+        .into_iter()
+        .collect();
+    dirs.extend(home_dir().map(|h| h.join(".config/waybar")));
+    dirs.extend(home_dir().map(|h| h.join("waybar")));
+    dirs.push(PathBuf::from("/etc/xdg/waybar"));
+    dirs.dedup();
+    dirs.iter()
+        .flat_map(|d| [d.join("config"), d.join("config.jsonc")])
+        .collect()
+}
+
+/// Selects an alternate config file for the rest of the process
+///
+/// Only the first call has an effect.
+pub fn set_config_override(path: PathBuf) {
+    let _ = CONFIG_OVERRIDE.set(path);
+}
+
+/// Returns the config file path, honouring `--config`
+pub fn config_file() -> Option<PathBuf> {
+    CONFIG_OVERRIDE
+        .get()
+        .cloned()
+        .or_else(|| config_dir().map(|d| d.join(CONFIG_FILE_NAME)))
+}
+
+/// Returns the API key file path
+pub fn key_file() -> Option<PathBuf> {
+    config_dir().map(|d| d.join(KEY_FILE_NAME))
+}
+
+/// Copies files from the pre-XDG locations into the XDG directories
+///
+/// Older releases always used `~/.config/redweather` and `~/.cache/redweather`.
+/// When `XDG_CONFIG_HOME`/`XDG_CACHE_HOME` point elsewhere, the files are
+/// copied over once, when the new directory does not exist yet. The legacy
+/// directories are left alone, so pointing the XDG variables at a scratch
+/// directory (as test sandboxes do) never takes the user's real config away.
+/// Nothing is copied when `--config` selects a config file.
+pub fn migrate_legacy_dirs() {
+    if CONFIG_OVERRIDE.get().is_some() {
+        return;
+    }
+    if let (Some(home), Some(config), Some(cache)) = (
+        home_dir(),
+        config_dir(),
+        xdg_base("XDG_CACHE_HOME", ".cache").map(|d| d.join(APP_DIR)),
+    ) {
+        copy_legacy_dirs(&home, &config, &cache);
+    }
+}
+
+/// Copies `~/.config/redweather` and `~/.cache/redweather` under `home` to
+/// the given config and cache directories
+fn copy_legacy_dirs(home: &Path, config: &Path, cache: &Path) -> usize {
+    copy_legacy_dir(&home.join(".config").join(APP_DIR), config)
+        + copy_legacy_dir(&home.join(".cache").join(APP_DIR), cache)
+}
+
+/// Copies every regular file in `old` to `new`, unless `new` already exists
+fn copy_legacy_dir(old: &Path, new: &Path) -> usize {
+    if old == new || !old.is_dir() || new.exists() {
+        return 0;
+    }
+    let entries = match fs::read_dir(old) {
+        Ok(e) => e,
+        Err(e) => {
+            warn!("Error reading legacy directory {}: {}", old.display(), e);
+            return 0;
+        }
+    };
+    if let Err(e) = fs::create_dir_all(new) {
+        warn!("Error creating directory {}: {}", new.display(), e);
+        return 0;
+    }
+
+    let mut copied = 0;
+    for entry in entries.flatten() {
+        let src = entry.path();
+        if !src.is_file() {
+            continue;
+        }
+        let dest = new.join(entry.file_name());
+        match fs::copy(&src, &dest) {
+            Ok(_) => {
+                info!("Copied {} to {}", src.display(), dest.display());
+                copied += 1;
+            }
+            Err(e) => warn!(
+                "Error copying {} to {}: {}",
+                src.display(),
+                dest.display(),
+                e
+            ),
+        }
+    }
+    copied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_dir_is_copied_once() {
+        let root = env::temp_dir().join("redweather_test_migrate_dir");
+        let _ = fs::remove_dir_all(&root);
+        let old = root.join("old");
+        let new = root.join("new");
+        fs::create_dir_all(&old).unwrap();
+
+        fs::write(old.join("apikey"), "legacy-key").unwrap();
+        fs::write(old.join("config.toml"), "units = \"metric\"").unwrap();
+
+        assert_eq!(copy_legacy_dir(&old, &new), 2);
+        assert_eq!(
+            fs::read_to_string(new.join("apikey")).unwrap(),
+            "legacy-key"
+        );
+        // The legacy directory is untouched
+        assert_eq!(
+            fs::read_to_string(old.join("config.toml")).unwrap(),
+            "units = \"metric\""
+        );
+        assert!(old.join("apikey").exists());
+
+        // Once the new directory exists it is never touched again
+        fs::remove_file(new.join("apikey")).unwrap();
+        fs::write(new.join("config.toml"), "units = \"imperial\"").unwrap();
+        assert_eq!(copy_legacy_dir(&old, &new), 0);
+        assert!(!new.join("apikey").exists());
+        assert_eq!(
+            fs::read_to_string(new.join("config.toml")).unwrap(),
+            "units = \"imperial\""
+        );
+
+        // Same directory is a no-op
+        assert_eq!(copy_legacy_dir(&old, &old), 0);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_scratch_xdg_dirs_leave_legacy_dirs_intact() {
+        let root = env::temp_dir().join("redweather_test_migrate_sandbox");
+        let _ = fs::remove_dir_all(&root);
+        let home = root.join("home");
+        let legacy_config = home.join(".config/redweather");
+        let legacy_cache = home.join(".cache/redweather");
+        fs::create_dir_all(&legacy_config).unwrap();
+        fs::create_dir_all(&legacy_cache).unwrap();
+        fs::write(legacy_config.join("config.toml"), "units = \"metric\"").unwrap();
+        fs::write(legacy_config.join("apikey"), "real-key").unwrap();
+        fs::write(legacy_cache.join("cache_1_2_metric.json"), "{}").unwrap();
+
+        // As with XDG_CONFIG_HOME=$TMP and XDG_CACHE_HOME=$TMP
+        let sandbox = root.join("tmp");
+        let config = sandbox.join("redweather");
+        let cache = sandbox.join("cache/redweather");
+        assert_eq!(copy_legacy_dirs(&home, &config, &cache), 3);
+
+        for file in ["config.toml", "apikey"] {
+            assert!(legacy_config.join(file).is_file());
+            assert!(config.join(file).is_file());
+        }
+        assert!(legacy_cache.join("cache_1_2_metric.json").is_file());
+
+        // Cleaning up the sandbox leaves the real files behind
+        fs::remove_dir_all(&sandbox).unwrap();
+        assert_eq!(
+            fs::read_to_string(legacy_config.join("apikey")).unwrap(),
+            "real-key"
+        );
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,143 @@
+//! Headless preset management (`redweather presets ...`)
+//!
+//! Everything the GTK location dialog can do with presets, from the command
+//! line. `next` and `prev` are meant for Waybar's `on-scroll-up`/`on-scroll-down`.
+
+use tracing::error;
+
+use crate::cli::{PresetsCommand, EXIT_FAILURE, EXIT_OK, EXIT_USAGE};
+use crate::config::{
+    cycle_active_preset, load_config, load_config_file, remove_preset, rename_preset,
+    save_location_preset, update_active_preset, Config,
+};
+use crate::context::require_key;
+use crate::waybar::reload_waybar;
+use crate::weather::geocode_query;
+
+/// Runs `redweather presets <subcommand>` and returns the process exit code
+pub async fn run_presets_command(action: &PresetsCommand) -> i32 {
+    let result = match action {
+        PresetsCommand::List => {
+            list_presets(&load_config());
+            return EXIT_OK;
+        }
+        PresetsCommand::Add { name, query, label } => {
+            return add_preset(name, &query.join(" "), label.as_deref()).await;
+        }
+        PresetsCommand::Remove { name } => {
+            if !preset_exists(name) {
+                return unknown_preset(name);
+            }
+            remove_preset(name).map(|()| format!("Removed preset \"{}\"", name))
+        }
+        PresetsCommand::Rename { old, new } => {
+            if !preset_exists(old) {
+                return unknown_preset(old);
+            }
+            rename_preset(old, new).map(|()| format!("Renamed preset \"{}\" to \"{}\"", old, new))
+        }
+        PresetsCommand::Activate { name } => {
+            if !preset_exists(name) {
+                return unknown_preset(name);
+            }
+            update_active_preset(name).map(|()| format!("Active preset: {}", name))
+        }
+        PresetsCommand::Next => {
+            cycle_active_preset(1).map(|p| format!("Active preset: {} ({})", p.name, p.label))
+        }
+        PresetsCommand::Prev => {
+            cycle_active_preset(-1).map(|p| format!("Active preset: {} ({})", p.name, p.label))
+        }
+    };
+
+    match result {
+        Ok(msg) => {
+            println!("{}", msg);
+            reload_waybar();
+            EXIT_OK
+        }
+        Err(e) => {
+            error!("{}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Prints every preset, marking the one in use with `*`
+fn list_presets(cfg: &Config) {
+    let presets = cfg.location_presets.as_deref().unwrap_or(&[]);
+    if presets.is_empty() {
+        println!("No presets configured. Add one with `redweather presets add <name> <zip|city>`.");
+        return;
+    }
+    let active = cfg.active_preset_index();
+    let width = presets.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    for (i, p) in presets.iter().enumerate() {
+        println!(
+            "{} {:<width$}  {} ({:.4}, {:.4})",
+            if Some(i) == active { "*" } else { " " },
+            p.name,
+            p.label,
+            p.lat,
+            p.lon,
+            width = width
+        );
+    }
+}
+
+/// Geocodes `query` and saves it as preset `name`
+async fn add_preset(name: &str, query: &str, label: Option<&str>) -> i32 {
+    if name.trim().is_empty() {
+        error!("Preset name cannot be empty");
+        return EXIT_USAGE;
+    }
+    let key = match require_key() {
+        Ok(k) => k,
+        Err(f) => return f.code,
+    };
+
+    let loc = match geocode_query(&key, query).await {
+        Ok(Some(loc)) => loc,
+        Ok(None) => {
+            error!("No location found for \"{}\"", query);
+            return EXIT_FAILURE;
+        }
+        Err(e) => {
+            error!("Geocoding failed: {}", e);
+            return EXIT_FAILURE;
+        }
+    };
+
+    let label = label.unwrap_or(&loc.label);
+    match save_location_preset(name, loc.lat, loc.lon, label) {
+        Ok(()) => {
+            println!(
+                "Saved preset \"{}\": {} ({:.4}, {:.4})",
+                name, label, loc.lat, loc.lon
+            );
+            reload_waybar();
+            EXIT_OK
+        }
+        Err(e) => {
+            error!("Failed to save preset: {}", e);
+            EXIT_FAILURE
+        }
+    }
+}
+
+/// Checks the config file (not overrides) for a preset
+fn preset_exists(name: &str) -> bool {
+    load_config_file()
+        .location_presets
+        .iter()
+        .flatten()
+        .any(|p| p.name == name)
+}
+
+fn unknown_preset(name: &str) -> i32 {
+    error!(
+        "Unknown preset \"{}\" (see `redweather presets list`)",
+        name
+    );
+    EXIT_USAGE
+}
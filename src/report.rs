@@ -0,0 +1,735 @@
+//! Terminal forecast for `redweather report`
+//!
+//! Renders current conditions, an hourly table and a daily table as plain text
+//! for SSH sessions and shell scripts. Temperatures are colored with ANSI
+//! truecolor escapes from the configured `temp_bands` when writing to a
+//! terminal, and the tables drop columns to fit narrow terminals.
+
+use clap::ValueEnum;
+use std::io::IsTerminal;
+
+use crate::config::{LocationSettings, Units};
+use crate::utils::{
+    deg_to_dir, format_ago, is_night, is_night_at, moon_phase_icon, pick_icon, temp_color,
+};
+use crate::weather::{ApiResponse, FetchedWeather, Hourly, WeatherDesc};
+
+/// Width used when the terminal size is unknown
+const DEFAULT_WIDTH: usize = 80;
+/// Narrowest layout the tables are designed for
+const MIN_WIDTH: usize = 40;
+/// Rows of characters per chart
+const CHART_ROWS: usize = 5;
+/// Color of the precipitation chart
+const PRECIP_COLOR: &str = "#8caaee";
+/// Color of headings and secondary text
+const DIM_COLOR: &str = "#838ba7";
+/// Color of the note about outdated data
+const STALE_COLOR: &str = "#e5c890";
+
+/// Character set for the temperature and precipitation charts
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ChartStyle {
+    /// Braille dots, two hours per character
+    Braille,
+    /// Vertical block bars, one hour per character
+    Block,
+}
+
+/// What to include in the report and how to draw it
+#[derive(Debug, Clone)]
+pub struct ReportOptions {
+    /// Rows in the hourly table
+    pub hours: usize,
+    /// Rows in the daily table
+    pub days: usize,
+    /// Charts to draw above the hourly table, if any
+    pub chart: Option<ChartStyle>,
+    /// Whether to emit ANSI color escapes
+    pub color: bool,
+    /// Terminal width in columns
+    pub width: usize,
+}
+
+/// Returns true when stdout is a terminal and `NO_COLOR` is not set
+pub fn color_by_default() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// Returns the terminal width from `COLUMNS`, then `stty size`, then 80
+pub fn terminal_width() -> usize {
+    let from_env = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.trim().parse::<usize>().ok());
+    let width = from_env.or_else(|| {
+        if !std::io::stdout().is_terminal() {
+            return None;
+        }
+        let tty = std::fs::File::open("/dev/tty").ok()?;
+        let out = std::process::Command::new("stty")
+            .arg("size")
+            .stdin(tty)
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&out.stdout)
+            .split_whitespace()
+            .nth(1)?
+            .parse()
+            .ok()
+    });
+    width.filter(|w| *w > 0).unwrap_or(DEFAULT_WIDTH)
+}
+
+/// Renders the full report
+///
+/// Data older than the cache TTL is flagged under the heading.
+pub fn render_report(
+    fetched: &FetchedWeather,
+    label: &str,
+    settings: &LocationSettings,
+    opts: &ReportOptions,
+) -> String {
+    let painter = Painter {
+        enabled: opts.color,
+    };
+    let width = opts.width.max(MIN_WIDTH);
+    let data = &fetched.data;
+    let mut out = Vec::new();
+
+    out.extend(render_current(data, label, settings, width, &painter));
+    if fetched.is_outdated(settings.cache.ttl_secs) {
+        let when = settings.time.time(fetched.fetched_at, data.timezone_offset);
+        let note = [
+            if fetched.stale {
+                format!("⚠ Offline: showing data from {}", when)
+            } else {
+                format!("⚠ Showing data from {}", when)
+            },
+            format!("updated {}", format_ago(fetched.age_secs())),
+        ];
+        out.splice(
+            1..1,
+            join_to_width(&note, width)
+                .iter()
+                .map(|l| painter.fg(l, STALE_COLOR)),
+        );
+    }
+
+    let hours: Vec<&Hourly> = data.hourly.iter().take(opts.hours).collect();
+    if let Some(style) = opts.chart {
+        if !hours.is_empty() {
+            out.push(String::new());
+            out.extend(render_charts(&hours, settings, style, width, &painter));
+        }
+    }
+    if !hours.is_empty() {
+        out.push(String::new());
+        out.extend(render_hourly(data, &hours, settings, width, &painter));
+    }
+    if opts.days > 0 && !data.daily.is_empty() {
+        out.push(String::new());
+        out.extend(render_daily(data, opts.days, settings, width, &painter));
+    }
+
+    let mut text = out.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Wraps text in ANSI escapes when color is enabled
+struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    /// Colors `text` with a `#rrggbb` foreground
+    fn fg(&self, text: &str, hex: &str) -> String {
+        match (self.enabled, hex_to_rgb(hex)) {
+            (true, Some((r, g, b))) => format!("\x1b[38;2;{};{};{}m{}\x1b[0m", r, g, b, text),
+            _ => text.to_string(),
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[1m{}\x1b[0m", text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn dim(&self, text: &str) -> String {
+        self.fg(text, DIM_COLOR)
+    }
+}
+
+/// Parses `#rgb`, `#rrggbb` or `#rrggbbaa` (alpha is ignored)
+fn hex_to_rgb(hex: &str) -> Option<(u8, u8, u8)> {
+    let h = hex.trim().strip_prefix('#')?;
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    match h.len() {
+        3 => {
+            let mut it = h.chars().map(|c| c.to_digit(16).map(|d| (d * 17) as u8));
+            Some((it.next()??, it.next()??, it.next()??))
+        }
+        6 | 8 => Some((channel(&h[0..2])?, channel(&h[2..4])?, channel(&h[4..6])?)),
+        _ => None,
+    }
+}
+
+fn unit_labels(units: Units) -> (&'static str, &'static str, &'static str) {
+    match units {
+        Units::Imperial => ("°F", "mph", "mi"),
+        Units::Metric => ("°C", "m/s", "km"),
+    }
+}
+
+fn first_desc(weather: &[WeatherDesc]) -> WeatherDesc {
+    weather.first().cloned().unwrap_or(WeatherDesc {
+        main: Some("Clear".into()),
+        description: Some("Clear".into()),
+    })
+}
+
+/// Pads `text` with spaces to `width` characters (or cuts it down to fit)
+fn fit(text: &str, width: usize) -> String {
+    let mut s: String = text.chars().take(width).collect();
+    let len = s.chars().count();
+    s.extend(std::iter::repeat_n(' ', width - len));
+    s
+}
+
+/// Right-aligns `text` in `width` characters
+fn fit_right(text: &str, width: usize) -> String {
+    format!("{:>width$}", text, width = width)
+}
+
+/// Counts the characters in `text` that are not part of an ANSI escape
+fn visible_len(text: &str) -> usize {
+    let mut len = 0;
+    let mut in_escape = false;
+    for c in text.chars() {
+        match c {
+            '\x1b' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if !in_escape => len += 1,
+            _ => {}
+        }
+    }
+    len
+}
+
+/// Joins items with " · ", starting a new line instead of exceeding `width`
+fn join_to_width(items: &[String], width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for item in items {
+        match lines.last_mut() {
+            Some(line) if visible_len(line) + 3 + visible_len(item) < width => {
+                line.push_str(" · ");
+                line.push_str(item);
+            }
+            _ => lines.push(item.clone()),
+        }
+    }
+    lines
+}
+
+fn render_current(
+    data: &ApiResponse,
+    label: &str,
+    settings: &LocationSettings,
+    width: usize,
+    p: &Painter,
+) -> Vec<String> {
+    let (temp_unit, speed_unit, dist_unit) = unit_labels(settings.units);
+    let cur = &data.current;
+    let desc = first_desc(&cur.weather);
+    let night = is_night(cur.dt, cur.sunrise, cur.sunset);
+    let icon = pick_icon(
+        &desc,
+        night,
+        Some(moon_phase_icon(cur.dt, data.timezone_offset)),
+    );
+    let temp = cur.temp.round();
+    let bands = &settings.temp_bands;
+
+    let mut lines = vec![format!(
+        "{}  {}",
+        p.bold(label),
+        p.dim(&settings.time.time(cur.dt, data.timezone_offset))
+    )];
+    lines.push(format!(
+        "{} {}  {}",
+        icon,
+        p.bold(&p.fg(
+            &format!("{:.0}{}", temp, temp_unit),
+            &temp_color(temp, bands)
+        )),
+        desc.description.as_deref().unwrap_or("N/A")
+    ));
+
+    let mut details = Vec::new();
+    if let Some(feels) = cur.feels_like.map(f64::round) {
+        details.push(format!(
+            "Feels {}",
+            p.fg(
+                &format!("{:.0}{}", feels, temp_unit),
+                &temp_color(feels, bands)
+            )
+        ));
+    }
+    if let Some(h) = cur.humidity {
+        details.push(format!("Humidity {}%", h));
+    }
+    details.push(format!(
+        "Wind {:.0} {} {}",
+        cur.wind_speed.unwrap_or(0.0).round(),
+        speed_unit,
+        deg_to_dir(cur.wind_deg)
+    ));
+    if let Some(pop) = data.hourly.first().and_then(|h| h.pop) {
+        details.push(format!("Rain {:.0}%", pop * 100.0));
+    }
+    lines.extend(join_to_width(&details, width));
+
+    let mut extra = Vec::new();
+    if let Some(uvi) = cur.uvi {
+        extra.push(format!("UV {:.0}", uvi));
+    }
+    if let Some(pressure) = cur.pressure {
+        extra.push(format!("Pressure {} hPa", pressure));
+    }
+    if let Some(vis) = cur.visibility {
+        let v = match settings.units {
+            Units::Imperial => vis as f64 / 1609.34,
+            Units::Metric => vis as f64 / 1000.0,
+        };
+        extra.push(format!("Visibility {:.0} {}", v, dist_unit));
+    }
+    if let Some(t) = cur.sunrise {
+        extra.push(format!(
+            "Sunrise {}",
+            settings.time.time(t, data.timezone_offset)
+        ));
+    }
+    if let Some(t) = cur.sunset {
+        extra.push(format!(
+            "Sunset {}",
+            settings.time.time(t, data.timezone_offset)
+        ));
+    }
+    lines.extend(join_to_width(&extra, width).iter().map(|l| p.dim(l)));
+    lines
+}
+
+/// Heading for the conditions column, left out when the column is too narrow
+fn conditions_heading(desc_w: usize) -> &'static str {
+    const HEADING: &str = "Conditions";
+    if desc_w >= HEADING.len() {
+        HEADING
+    } else {
+        ""
+    }
+}
+
+fn render_hourly(
+    data: &ApiResponse,
+    hours: &[&Hourly],
+    settings: &LocationSettings,
+    width: usize,
+    p: &Painter,
+) -> Vec<String> {
+    let (temp_unit, speed_unit, _) = unit_labels(settings.units);
+    let bands = &settings.temp_bands;
+    let tz = data.timezone_offset;
+
+    // time, temp, rain, wind; conditions get what is left, wind goes first
+    // when the terminal is too narrow
+    let time_w = 5;
+    let temp_w = 6;
+    let pop_w = 5;
+    let wind_w = 11;
+    let show_wind = width >= time_w + temp_w + pop_w + wind_w + 4 + 16;
+    let used = time_w + temp_w + pop_w + if show_wind { wind_w + 1 } else { 0 } + 4;
+    // The weather icon is usually two columns wide
+    let desc_w = width.saturating_sub(used + 1);
+
+    let mut header = format!(
+        "{} {} {}",
+        fit("Time", time_w),
+        fit_right("Temp", temp_w),
+        fit_right("Rain", pop_w)
+    );
+    if show_wind {
+        header.push_str(&format!(" {}", fit_right("Wind", wind_w)));
+    }
+    header.push_str(&format!("  {}", conditions_heading(desc_w)));
+
+    let mut lines = vec![
+        p.bold(&format!("Next {} hours", hours.len())),
+        p.dim(header.trim_end()),
+    ];
+    for h in hours {
+        let desc = first_desc(&h.weather);
+        let night = is_night_at(h.dt, data);
+        let temp = h.temp.round();
+        let temp_text = fit_right(&format!("{:.0}{}", temp, temp_unit), temp_w);
+        let mut row = format!(
+            "{} {} {}",
+            fit(&settings.time.hour(h.dt, tz), time_w),
+            p.fg(&temp_text, &temp_color(temp, bands)),
+            fit_right(&format!("{:.0}%", h.pop.unwrap_or(0.0) * 100.0), pop_w)
+        );
+        if show_wind {
+            let wind = format!(
+                "{:.0} {} {}",
+                h.wind_speed.unwrap_or(0.0).round(),
+                speed_unit,
+                deg_to_dir(h.wind_deg)
+            );
+            row.push_str(&format!(" {}", fit_right(&wind, wind_w)));
+        }
+        let conditions = format!(
+            "{} {}",
+            pick_icon(&desc, night, None),
+            desc.description.as_deref().unwrap_or("")
+        );
+        row.push_str("  ");
+        row.push_str(fit(&conditions, desc_w).trim_end());
+        lines.push(row);
+    }
+    lines
+}
+
+fn render_daily(
+    data: &ApiResponse,
+    days: usize,
+    settings: &LocationSettings,
+    width: usize,
+    p: &Painter,
+) -> Vec<String> {
+    let bands = &settings.temp_bands;
+    let tz = data.timezone_offset;
+
+    let day_w = 10;
+    let temp_w = 5;
+    let pop_w = 5;
+    let used = day_w + temp_w * 2 + pop_w + 3 + 2;
+    let desc_w = width.saturating_sub(used + 1);
+
+    let header = format!(
+        "{} {} {} {}  {}",
+        fit("Day", day_w),
+        fit_right("High", temp_w),
+        fit_right("Low", temp_w),
+        fit_right("Rain", pop_w),
+        conditions_heading(desc_w)
+    );
+    let mut lines = vec![p.bold("Daily"), p.dim(header.trim_end())];
+    for (i, d) in data.daily.iter().take(days).enumerate() {
+        let desc = first_desc(&d.weather);
+        let day = if i == 0 {
+            "Today".to_string()
+        } else {
+            crate::utils::fmt_time(d.dt, tz, "%a %d %b")
+        };
+        let temp_cell = |t: Option<f64>| match t.map(f64::round) {
+            Some(t) => p.fg(
+                &fit_right(&format!("{:.0}°", t), temp_w),
+                &temp_color(t, bands),
+            ),
+            None => fit_right("-", temp_w),
+        };
+        let conditions = format!(
+            "{} {}",
+            pick_icon(&desc, false, None),
+            desc.description.as_deref().unwrap_or("")
+        );
+        lines.push(format!(
+            "{} {} {} {}  {}",
+            fit(&day, day_w),
+            temp_cell(d.temp.max),
+            temp_cell(d.temp.min),
+            fit_right(&format!("{:.0}%", d.pop.unwrap_or(0.0) * 100.0), pop_w),
+            fit(&conditions, desc_w).trim_end()
+        ));
+    }
+    lines
+}
+
+/// Width of the axis labels to the left of each chart, e.g. "100% "
+const AXIS_W: usize = 6;
+
+fn render_charts(
+    hours: &[&Hourly],
+    settings: &LocationSettings,
+    style: ChartStyle,
+    width: usize,
+    p: &Painter,
+) -> Vec<String> {
+    let (temp_unit, _, _) = unit_labels(settings.units);
+    let per_char = match style {
+        ChartStyle::Braille => 2,
+        ChartStyle::Block => 1,
+    };
+    // Drop hours from the end rather than squeezing several into one column
+    let max_points = width.saturating_sub(AXIS_W) * per_char;
+    let hours = &hours[..hours.len().min(max_points)];
+
+    let temps: Vec<f64> = hours.iter().map(|h| h.temp).collect();
+    let pops: Vec<f64> = hours.iter().map(|h| h.pop.unwrap_or(0.0) * 100.0).collect();
+    let lo = temps.iter().copied().fold(f64::INFINITY, f64::min).floor();
+    let hi = temps
+        .iter()
+        .copied()
+        .fold(f64::NEG_INFINITY, f64::max)
+        .ceil();
+
+    let (temp_rows, pop_rows) = match style {
+        ChartStyle::Braille => (
+            braille_chart(&temps, lo, hi, CHART_ROWS, false),
+            braille_chart(&pops, 0.0, 100.0, CHART_ROWS, true),
+        ),
+        ChartStyle::Block => (
+            block_chart(&temps, lo, hi, CHART_ROWS),
+            block_chart(&pops, 0.0, 100.0, CHART_ROWS),
+        ),
+    };
+
+    // Color each temperature column by the warmest hour it covers
+    let column_colors: Vec<String> = temps
+        .chunks(per_char)
+        .map(|c| {
+            let t = c.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            temp_color(t.round(), &settings.temp_bands)
+        })
+        .collect();
+
+    let mut lines = vec![p.bold(&format!("Temperature ({})", temp_unit))];
+    for (i, row) in temp_rows.iter().enumerate() {
+        let axis = match i {
+            0 => format!("{:.0}°", hi),
+            _ if i == CHART_ROWS - 1 => format!("{:.0}°", lo),
+            _ => String::new(),
+        };
+        let colored: String = row
+            .chars()
+            .zip(&column_colors)
+            .map(|(c, color)| p.fg(&c.to_string(), color))
+            .collect();
+        lines.push(format!(
+            "{} {}",
+            p.dim(&fit_right(&axis, AXIS_W - 1)),
+            colored
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push(p.bold("Chance of rain"));
+    for (i, row) in pop_rows.iter().enumerate() {
+        let axis = match i {
+            0 => "100%",
+            _ if i == CHART_ROWS - 1 => "0%",
+            _ => "",
+        };
+        lines.push(format!(
+            "{} {}",
+            p.dim(&fit_right(axis, AXIS_W - 1)),
+            p.fg(row, PRECIP_COLOR)
+        ));
+    }
+    lines
+}
+
+/// Scales `v` from `lo..=hi` onto `0..=steps`
+fn scale(v: f64, lo: f64, hi: f64, steps: usize) -> usize {
+    if (hi - lo).abs() < f64::EPSILON {
+        return steps / 2;
+    }
+    (((v - lo) / (hi - lo)).clamp(0.0, 1.0) * steps as f64).round() as usize
+}
+
+/// Draws one bar per value using eighth-block characters, top row first
+fn block_chart(values: &[f64], lo: f64, hi: f64, rows: usize) -> Vec<String> {
+    const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let heights: Vec<usize> = values.iter().map(|&v| scale(v, lo, hi, rows * 8)).collect();
+    (0..rows)
+        .map(|r| {
+            let base = (rows - 1 - r) * 8;
+            heights
+                .iter()
+                .map(|&h| BLOCKS[h.saturating_sub(base).min(8)])
+                .collect()
+        })
+        .collect()
+}
+
+/// Draws values as a braille line (or filled area), two values per character
+/// and four dots per row, top row first
+fn braille_chart(values: &[f64], lo: f64, hi: f64, rows: usize, fill: bool) -> Vec<String> {
+    // Dot bits by [column][dot row from the top]
+    const DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+    let dot_rows = rows * 4;
+    let cols = values.len().div_ceil(2);
+    let mut cells = vec![vec![0u32; cols]; rows];
+
+    // y counts dots up from the bottom
+    let ys: Vec<usize> = values
+        .iter()
+        .map(|&v| scale(v, lo, hi, dot_rows - 1))
+        .collect();
+    for (x, &y) in ys.iter().enumerate() {
+        let (from, to) = if fill {
+            (0, y)
+        } else {
+            // Join to the previous point so steep changes stay connected
+            let prev = if x > 0 { ys[x - 1] } else { y };
+            let mid = (prev + y) / 2;
+            (mid.min(y), mid.max(y))
+        };
+        for yy in from..=to {
+            let row_from_top = dot_rows - 1 - yy;
+            cells[row_from_top / 4][x / 2] |= DOTS[x % 2][row_from_top % 4];
+        }
+    }
+    cells
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|bits| char::from_u32(0x2800 + bits).unwrap_or(' '))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, LocationSettings};
+
+    fn sample() -> ApiResponse {
+        let hourly: Vec<serde_json::Value> = (0..48)
+            .map(|i| {
+                serde_json::json!({
+                    "dt": 1_684_929_600 + i * 3600,
+                    "temp": 60.0 + i as f64 % 12.0,
+                    "pop": (i % 5) as f64 / 4.0,
+                    "wind_speed": 5.0,
+                    "wind_deg": 180,
+                    "weather": [{"main": "Rain", "description": "light rain"}]
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "timezone_offset": 0,
+            "current": {
+                "dt": 1_684_929_490, "temp": 72.4, "feels_like": 70.0,
+                "humidity": 50, "wind_speed": 9.0, "wind_deg": 225,
+                "sunrise": 1_684_905_000, "sunset": 1_684_958_000,
+                "weather": [{"main": "Clouds", "description": "scattered clouds"}]
+            },
+            "hourly": hourly,
+            "daily": [{
+                "dt": 1_684_929_600, "temp": {"min": 58.0, "max": 75.0}, "pop": 0.2,
+                "weather": [{"main": "Clear", "description": "clear sky"}]
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn opts(width: usize, color: bool, chart: Option<ChartStyle>) -> ReportOptions {
+        ReportOptions {
+            hours: 24,
+            days: 7,
+            chart,
+            color,
+            width,
+        }
+    }
+
+    fn settings() -> LocationSettings {
+        LocationSettings::resolve(&Config::default(), None, "k")
+    }
+
+    #[test]
+    fn test_hex_to_rgb() {
+        assert_eq!(hex_to_rgb("#ff8000"), Some((255, 128, 0)));
+        assert_eq!(hex_to_rgb("#f80"), Some((255, 136, 0)));
+        assert_eq!(hex_to_rgb("#ff800080"), Some((255, 128, 0)));
+        assert_eq!(hex_to_rgb("red"), None);
+    }
+
+    fn fetched(age_secs: i64, stale: bool) -> FetchedWeather {
+        FetchedWeather {
+            data: sample(),
+            fetched_at: chrono::Utc::now().timestamp() - age_secs,
+            stale,
+        }
+    }
+
+    #[test]
+    fn test_report_plain_and_colored() {
+        let data = fetched(0, false);
+        let plain = render_report(&data, "Home", &settings(), &opts(100, false, None));
+        assert!(!plain.contains('\x1b'));
+        assert!(plain.contains("72°F"));
+        assert!(plain.contains("Next 24 hours"));
+        assert!(plain.contains("Today"));
+        assert!(plain.contains("Wind"));
+
+        let colored = render_report(&data, "Home", &settings(), &opts(100, true, None));
+        assert!(colored.contains("\x1b[38;2;"));
+        assert!(!plain.contains("Offline"));
+
+        let old = render_report(
+            &fetched(3 * 3600, true),
+            "Home",
+            &settings(),
+            &opts(100, false, None),
+        );
+        let note = old.lines().nth(1).unwrap();
+        assert!(note.starts_with("⚠ Offline: showing data from "));
+        assert!(note.ends_with(" · updated 3 h ago"));
+
+        // Old data without a failed fetch is not called offline
+        let old = render_report(
+            &fetched(3 * 3600, false),
+            "Home",
+            &settings(),
+            &opts(100, false, None),
+        );
+        let note = old.lines().nth(1).unwrap();
+        assert!(note.starts_with("⚠ Showing data from "));
+    }
+
+    #[test]
+    fn test_report_fits_width() {
+        let data = fetched(3 * 3600, true);
+        for width in [40, 60, 100] {
+            let text = render_report(
+                &data,
+                "Home",
+                &settings(),
+                &opts(width, false, Some(ChartStyle::Block)),
+            );
+            for line in text.lines() {
+                assert!(line.chars().count() < width, "{:?}", line);
+            }
+        }
+        let narrow = render_report(&data, "Home", &settings(), &opts(40, false, None));
+        let header = narrow.lines().find(|l| l.starts_with("Time")).unwrap();
+        assert!(!header.contains("Wind"));
+    }
+
+    #[test]
+    fn test_charts() {
+        let rows = block_chart(&[0.0, 50.0, 100.0], 0.0, 100.0, 2);
+        assert_eq!(rows, vec!["  █", " ██"]);
+
+        let rows = braille_chart(&[0.0, 100.0], 0.0, 100.0, 1, false);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].chars().count(), 1);
+        let rows = braille_chart(&[100.0, 100.0, 100.0], 0.0, 100.0, 2, true);
+        assert_eq!(rows, vec!["⣿⡇", "⣿⡇"]);
+    }
+}
@@ -0,0 +1,253 @@
+//! `redweather serve`: one process that fetches for the bar, dashboard and scripts
+//!
+//! The server refreshes on the same schedule as `redweather daemon` and
+//! publishes every update on a Unix socket (protocol in [`crate::ipc`]).
+//! Clients that attach share its data instead of each fetching on their own.
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::{error, info, warn};
+
+use crate::cli::{Cli, DaemonArgs, EXIT_FAILURE, EXIT_OK};
+use crate::daemon::{PresetChange, Signals, Update, Updater};
+use crate::ipc::{Request, Response, Snapshot, SnapshotWeather};
+use crate::paths;
+
+/// The latest `update` line, `None` until the first update
+type Published = Option<Arc<String>>;
+
+/// A request that changes state, with the channel for its response
+struct Call {
+    request: Request,
+    reply: oneshot::Sender<Response>,
+}
+
+/// Runs the server until SIGTERM or SIGINT; returns the process exit code
+pub async fn run_server(cli: &Cli, args: &DaemonArgs) -> i32 {
+    let Some(path) = paths::socket_path() else {
+        error!("Cannot determine the socket path (is $HOME set?)");
+        return EXIT_FAILURE;
+    };
+    let listener = match bind(&path) {
+        Ok(l) => l,
+        Err(e) => {
+            error!("Cannot listen on {}: {}", path.display(), e);
+            return EXIT_FAILURE;
+        }
+    };
+    let (mut signals, mut term, mut int) = match (
+        Signals::install(),
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) {
+        (Ok(s), Ok(t), Ok(i)) => (s, t, i),
+        _ => {
+            error!("Cannot install signal handlers");
+            let _ = fs::remove_file(&path);
+            return EXIT_FAILURE;
+        }
+    };
+    info!("Listening on {}", path.display());
+
+    let (state_tx, state_rx) = watch::channel::<Published>(None);
+    let (call_tx, mut call_rx) = mpsc::channel::<Call>(16);
+    tokio::spawn(accept_clients(listener, state_rx, call_tx));
+
+    let mut updater = Updater::new(cli.global.clone(), cli.location(), args.interval);
+    let mut force = cli.global.reload;
+    let mut waiting: Vec<oneshot::Sender<Response>> = Vec::new();
+    loop {
+        let update = updater.update(force).await;
+        force = false;
+        let delay = update.delay;
+        let line = update_line(&updater, update);
+        state_tx.send_replace(Some(Arc::new(line)));
+        for reply in waiting.drain(..) {
+            let _ = reply.send(Response::Ok);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            Some(call) = call_rx.recv() => {
+                let result = match call.request {
+                    Request::Refresh => {
+                        force = true;
+                        Ok(())
+                    }
+                    Request::Reload => {
+                        updater.reset();
+                        Ok(())
+                    }
+                    Request::Preset { name: Some(name), .. } => {
+                        updater.select_preset(PresetChange::Activate(name)).map(|_| ())
+                    }
+                    Request::Preset { name: None, step } => updater
+                        .select_preset(PresetChange::Cycle(step.unwrap_or(1)))
+                        .map(|_| ()),
+                    // Answered by the client task
+                    Request::Get | Request::Subscribe => Ok(()),
+                };
+                match result {
+                    Ok(()) => waiting.push(call.reply),
+                    Err(e) => {
+                        let _ = call.reply.send(Response::Error { message: e.to_string() });
+                    }
+                }
+            }
+            _ = signals.refresh.recv() => force = true,
+            _ = signals.next_preset.recv() => {
+                if let Err(e) = updater.select_preset(PresetChange::Cycle(1)) {
+                    warn!("Cannot switch preset: {}", e);
+                }
+            }
+            _ = signals.reload.recv() => {
+                info!("Reloading config");
+                updater.reset();
+            }
+            _ = term.recv() => break,
+            _ = int.recv() => break,
+        }
+    }
+
+    let _ = fs::remove_file(&path);
+    EXIT_OK
+}
+
+/// Binds the socket, replacing one left behind by a server that crashed
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "another redweather serve is running",
+        ));
+    }
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    // Presets and locations are nobody else's business
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Builds the `update` response line for an update
+fn update_line(updater: &Updater, update: Update) -> String {
+    let weather = match (update.weather, updater.context()) {
+        (Some(fetched), Some(ctx)) => Some(SnapshotWeather {
+            location: ctx.resolved.location.clone(),
+            preset: updater.preset_name().map(str::to_string),
+            units: ctx.resolved.settings.units,
+            fetched_at: fetched.fetched_at,
+            stale: fetched.stale,
+            data: fetched.data,
+        }),
+        _ => None,
+    };
+    let response = Response::Update(Box::new(Snapshot {
+        bar: update.bar,
+        weather,
+        error: update.error,
+    }));
+    serde_json::to_string(&response).unwrap_or_else(|e| error_line(&e.to_string()))
+}
+
+fn error_line(message: &str) -> String {
+    serde_json::to_string(&Response::Error {
+        message: message.to_string(),
+    })
+    .unwrap_or_default()
+}
+
+async fn accept_clients(
+    listener: UnixListener,
+    state: watch::Receiver<Published>,
+    calls: mpsc::Sender<Call>,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let (state, calls) = (state.clone(), calls.clone());
+                tokio::spawn(async move {
+                    // A client hanging up mid-response is not an error worth logging
+                    let _ = serve_client(stream, state, calls).await;
+                });
+            }
+            Err(e) => warn!("Failed to accept a client: {}", e),
+        }
+    }
+}
+
+/// Answers one client's requests until it disconnects
+async fn serve_client(
+    stream: UnixStream,
+    mut state: watch::Receiver<Published>,
+    calls: mpsc::Sender<Call>,
+) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(r) => r,
+            Err(e) => {
+                write_line(&mut writer, &error_line(&format!("Bad request: {}", e))).await?;
+                continue;
+            }
+        };
+        match request {
+            Request::Get => {
+                let line = latest(&mut state).await;
+                write_line(&mut writer, &line).await?;
+            }
+            Request::Subscribe => loop {
+                let line = latest(&mut state).await;
+                write_line(&mut writer, &line).await?;
+                if state.changed().await.is_err() {
+                    return Ok(());
+                }
+            },
+            request => {
+                let (reply, response) = oneshot::channel();
+                let response = match calls.send(Call { request, reply }).await {
+                    Ok(()) => response.await.ok(),
+                    Err(_) => None,
+                };
+                let line = match response {
+                    Some(r) => serde_json::to_string(&r).unwrap_or_default(),
+                    None => error_line("The server is shutting down"),
+                };
+                write_line(&mut writer, &line).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Waits for the first update if there is none yet, and marks it seen
+async fn latest(state: &mut watch::Receiver<Published>) -> Arc<String> {
+    loop {
+        if let Some(line) = state.borrow_and_update().clone() {
+            return line;
+        }
+        if state.changed().await.is_err() {
+            return Arc::new(error_line("The server is shutting down"));
+        }
+    }
+}
+
+async fn write_line(writer: &mut tokio::net::unix::OwnedWriteHalf, line: &str) -> io::Result<()> {
+    writer.write_all(line.as_bytes()).await?;
+    writer.write_all(b"\n").await
+}
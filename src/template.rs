@@ -0,0 +1,251 @@
+//! Format templates for the bar text and tooltip sections
+//!
+//! Syntax:
+//! - `{name}` inserts a value, `{name:N}` pads it to at least N characters
+//! - `{?name}...{/name}` keeps the enclosed text only if `name` has a value
+//!   (empty values and 0% chance of rain count as missing); `{!name}...{/name}`
+//!   is the opposite
+//! - `{{` and `}}` are literal braces
+//!
+//! Everything else is copied through, so templates may contain Pango markup.
+
+use std::collections::HashMap;
+
+/// A parsed piece of a template
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Text(String),
+    Field {
+        name: String,
+        width: Option<usize>,
+    },
+    Cond {
+        name: String,
+        negate: bool,
+        body: Vec<Node>,
+    },
+}
+
+/// A placeholder value
+#[derive(Debug, Clone)]
+pub struct Value {
+    /// Text to insert, possibly with Pango markup
+    pub markup: String,
+    /// Number of visible characters, used for `{name:N}` padding
+    pub width: usize,
+    /// Whether `{?name}` blocks are kept
+    pub present: bool,
+}
+
+impl Value {
+    /// A value without markup
+    pub fn plain(text: impl Into<String>) -> Self {
+        let text = text.into();
+        Value {
+            width: text.chars().count(),
+            present: !text.is_empty(),
+            markup: text,
+        }
+    }
+
+    /// A value wrapped in markup; `visible` is the text the user sees
+    pub fn marked(markup: String, visible: &str) -> Self {
+        Value {
+            markup,
+            width: visible.chars().count(),
+            present: !visible.is_empty(),
+        }
+    }
+
+    /// Marks the value as missing for `{?name}` without changing its text
+    pub fn present_if(mut self, present: bool) -> Self {
+        self.present = self.present && present;
+        self
+    }
+}
+
+/// Placeholder values by name
+pub type Values = HashMap<&'static str, Value>;
+
+/// Parses a template, reporting unbalanced braces and conditionals
+pub fn parse(src: &str) -> Result<Vec<Node>, String> {
+    let mut stack: Vec<(String, bool, Vec<Node>)> = Vec::new();
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut text = String::new();
+    let mut chars = src.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => return Err("unmatched \"}\" (write \"}}\" for a literal brace)".into()),
+            '{' => {
+                let mut tag = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('{') | None => {
+                            return Err(format!("unclosed \"{{{}\"", tag));
+                        }
+                        Some(c) => tag.push(c),
+                    }
+                }
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                if let Some(name) = tag.strip_prefix('/') {
+                    let (open, negate, outer) = stack.pop().ok_or_else(|| {
+                        format!("\"{{/{}}}\" without a matching \"{{?{}}}\"", name, name)
+                    })?;
+                    if open != name {
+                        return Err(format!("\"{{/{}}}\" closes \"{{?{}}}\"", name, open));
+                    }
+                    let body = std::mem::replace(&mut nodes, outer);
+                    nodes.push(Node::Cond {
+                        name: open,
+                        negate,
+                        body,
+                    });
+                } else if let Some(name) = tag.strip_prefix('?') {
+                    stack.push((check_name(name)?, false, std::mem::take(&mut nodes)));
+                } else if let Some(name) = tag.strip_prefix('!') {
+                    stack.push((check_name(name)?, true, std::mem::take(&mut nodes)));
+                } else {
+                    let (name, width) =
+                        match tag.split_once(':') {
+                            Some((name, w)) => (
+                                name,
+                                Some(w.trim().parse().map_err(|_| {
+                                    format!("\"{{{}}}\": width must be a number", tag)
+                                })?),
+                            ),
+                            None => (tag.as_str(), None),
+                        };
+                    nodes.push(Node::Field {
+                        name: check_name(name)?,
+                        width,
+                    });
+                }
+            }
+            c => text.push(c),
+        }
+    }
+    if let Some((open, _, _)) = stack.last() {
+        return Err(format!(
+            "\"{{?{}}}\" is never closed with \"{{/{}}}\"",
+            open, open
+        ));
+    }
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+    Ok(nodes)
+}
+
+fn check_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("invalid placeholder name \"{}\"", name));
+    }
+    Ok(name.to_string())
+}
+
+/// Returns the placeholder names used by a template (including conditionals)
+pub fn names(nodes: &[Node]) -> Vec<&str> {
+    let mut out = Vec::new();
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Field { name, .. } => out.push(name.as_str()),
+            Node::Cond { name, body, .. } => {
+                out.push(name.as_str());
+                out.extend(names(body));
+            }
+        }
+    }
+    out
+}
+
+/// Renders parsed nodes; unknown placeholders are left in the output as-is
+pub fn render(nodes: &[Node], values: &Values) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(t) => out.push_str(t),
+            Node::Field { name, width } => match values.get(name.as_str()) {
+                Some(v) => {
+                    out.push_str(&v.markup);
+                    let pad = width.unwrap_or(0).saturating_sub(v.width);
+                    out.extend(std::iter::repeat_n(' ', pad));
+                }
+                None => {
+                    out.push('{');
+                    out.push_str(name);
+                    out.push('}');
+                }
+            },
+            Node::Cond { name, negate, body } => {
+                let present = values.get(name.as_str()).is_some_and(|v| v.present);
+                if present != *negate {
+                    out.push_str(&render(body, values));
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> Values {
+        let mut v = Values::new();
+        v.insert("temp", Value::marked("<b>73°</b>".into(), "73°"));
+        v.insert("pop", Value::plain("0%").present_if(false));
+        v.insert("desc", Value::plain("rain"));
+        v
+    }
+
+    fn render_str(src: &str) -> String {
+        render(&parse(src).unwrap(), &values())
+    }
+
+    #[test]
+    fn test_fields_and_padding() {
+        assert_eq!(render_str("| {temp} {desc:6}|"), "| <b>73°</b> rain  |");
+        assert_eq!(render_str("{temp:5}|"), "<b>73°</b>  |");
+        assert_eq!(render_str("{{x}} {nope}"), "{x} {nope}");
+    }
+
+    #[test]
+    fn test_conditionals() {
+        assert_eq!(render_str("{desc}{?pop} 💧{pop}{/pop}"), "rain");
+        assert_eq!(render_str("{!pop}dry{/pop}{?desc}!{/desc}"), "dry!");
+        assert_eq!(
+            render_str("{?desc}[{?pop}{pop}{/pop}{desc}]{/desc}"),
+            "[rain]"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("{temp").is_err());
+        assert!(parse("temp}").is_err());
+        assert!(parse("{?pop}wet").is_err());
+        assert!(parse("{?pop}wet{/desc}").is_err());
+        assert!(parse("{/pop}").is_err());
+        assert!(parse("{temp:x}").is_err());
+        assert!(parse("{bad name}").is_err());
+        assert_eq!(
+            names(&parse("{a}{?b}{c:3}{/b}").unwrap()),
+            vec!["a", "b", "c"]
+        );
+    }
+}
@@ -6,19 +6,15 @@ use gtk::{
     ListBox, ListBoxRow, Orientation, ScrolledWindow, Separator, SpinButton, Switch, Window,
 };
 use std::cell::RefCell;
-use std::process::Command;
 use std::rc::Rc;
+use tracing::error;
 
 use crate::config::{
-    save_config, save_location_preset, update_active_preset, Config, DashboardConfig,
+    save_location_preset, update_active_preset, update_config, Config, DashboardConfig,
 };
+use crate::waybar::reload_waybar;
 use crate::weather::{geocode_direct, geocode_zip, Location};
 
-/// Reloads Waybar by sending SIGUSR2 signal
-fn reload_waybar() {
-    let _ = Command::new("pkill").arg("-SIGUSR2").arg("waybar").status();
-}
-
 const DIALOG_CSS: &str = r#"
     window {
         background-color: #24283b;
@@ -129,13 +125,13 @@ where
     let on_update_clone_u = on_update_rc.clone();
     units_combo.connect_changed(move |combo| {
         if let Some(id) = combo.active_id() {
-            let mut cfg_borrow = cfg_clone_u.borrow_mut();
-            cfg_borrow.units = match id.as_str() {
+            let units = match id.as_str() {
                 "metric" => crate::config::Units::Metric,
                 _ => crate::config::Units::Imperial,
             };
-            if let Err(e) = save_config(&cfg_borrow) {
-                eprintln!("Failed to save config: {}", e);
+            cfg_clone_u.borrow_mut().units = units;
+            if let Err(e) = update_config(|c| c.units = units) {
+                error!("Failed to save config: {}", e);
             }
             on_update_clone_u();
         }
@@ -154,13 +150,18 @@ where
     let cfg_clone_g = current_cfg.clone();
     let on_update_clone_g = on_update_rc.clone();
     graph_switch.connect_active_notify(move |sw| {
-        let mut cfg_borrow = cfg_clone_g.borrow_mut();
-        let dash_cfg = cfg_borrow
+        let value = sw.is_active();
+        cfg_clone_g
+            .borrow_mut()
             .dashboard
-            .get_or_insert_with(DashboardConfig::default);
-        dash_cfg.show_hourly_graph = sw.is_active();
-        if let Err(e) = save_config(&cfg_borrow) {
-            eprintln!("Failed to save config: {}", e);
+            .get_or_insert_with(DashboardConfig::default)
+            .show_hourly_graph = value;
+        if let Err(e) = update_config(|c| {
+            c.dashboard
+                .get_or_insert_with(DashboardConfig::default)
+                .show_hourly_graph = value
+        }) {
+            error!("Failed to save config: {}", e);
         }
         on_update_clone_g();
     });
@@ -185,13 +186,18 @@ where
     let cfg_clone_h = current_cfg.clone();
     let on_update_clone_h = on_update_rc.clone();
     hours_spin.connect_value_changed(move |sb| {
-        let mut cfg_borrow = cfg_clone_h.borrow_mut();
-        let dash_cfg = cfg_borrow
+        let value = sb.value() as usize;
+        cfg_clone_h
+            .borrow_mut()
             .dashboard
-            .get_or_insert_with(DashboardConfig::default);
-        dash_cfg.forecast_hours = sb.value() as usize;
-        if let Err(e) = save_config(&cfg_borrow) {
-            eprintln!("Failed to save config: {}", e);
+            .get_or_insert_with(DashboardConfig::default)
+            .forecast_hours = value;
+        if let Err(e) = update_config(|c| {
+            c.dashboard
+                .get_or_insert_with(DashboardConfig::default)
+                .forecast_hours = value
+        }) {
+            error!("Failed to save config: {}", e);
         }
         on_update_clone_h();
     });
@@ -216,13 +222,18 @@ where
     let cfg_clone_d = current_cfg.clone();
     let on_update_clone_d = on_update_rc.clone();
     days_spin.connect_value_changed(move |sb| {
-        let mut cfg_borrow = cfg_clone_d.borrow_mut();
-        let dash_cfg = cfg_borrow
+        let value = sb.value() as usize;
+        cfg_clone_d
+            .borrow_mut()
             .dashboard
-            .get_or_insert_with(DashboardConfig::default);
-        dash_cfg.forecast_days = sb.value() as usize;
-        if let Err(e) = save_config(&cfg_borrow) {
-            eprintln!("Failed to save config: {}", e);
+            .get_or_insert_with(DashboardConfig::default)
+            .forecast_days = value;
+        if let Err(e) = update_config(|c| {
+            c.dashboard
+                .get_or_insert_with(DashboardConfig::default)
+                .forecast_days = value
+        }) {
+            error!("Failed to save config: {}", e);
         }
         on_update_clone_d();
     });
@@ -399,10 +410,10 @@ where
         }
         if let Some(preset_id) = preset_ids.get(idx as usize) {
             if let Err(e) = update_active_preset(preset_id.as_str()) {
-                eprintln!("Failed to activate preset: {}", e);
+                error!("Failed to activate preset: {}", e);
                 // Optionally show error to user in dialog
             } else {
-                reload_waybar();
+                reload_waybar_in_background(dlg_preset.application());
                 on_update_rc_preset();
                 dlg_preset.close();
             }
@@ -426,9 +437,9 @@ where
                 .to_string();
 
             if let Err(e) = save_location_preset(&name, loc.lat, loc.lon, &loc.label) {
-                eprintln!("Failed to save preset: {}", e);
+                error!("Failed to save preset: {}", e);
             } else {
-                reload_waybar();
+                reload_waybar_in_background(dlg_use.application());
                 on_update_rc_use();
                 dlg_use.close();
             }
@@ -444,6 +455,20 @@ where
     dialog.show();
 }
 
+/// Runs [`reload_waybar`] without blocking the GTK main loop
+///
+/// Telling a running server to reload waits until it has fetched again, which
+/// can take many seconds. `app` is held until then, so closing its last window
+/// doesn't exit before Waybar is signalled.
+fn reload_waybar_in_background(app: Option<Application>) {
+    glib::spawn_future_local(async move {
+        let _hold = app.as_ref().map(|a| a.hold());
+        if let Err(e) = tokio::task::spawn_blocking(reload_waybar).await {
+            error!("Failed to reload Waybar: {}", e);
+        }
+    });
+}
+
 /// Runs the GTK prompt application (legacy wrapper)
 pub fn run_prompt(key: &str, cfg: &Config) -> Result<()> {
     let key = key.to_string();
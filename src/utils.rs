@@ -5,8 +5,9 @@
 use chrono::{DateTime, Duration, Utc};
 
 use crate::config::TempBand;
-use crate::weather::WeatherDesc;
+use crate::weather::{ApiResponse, WeatherDesc};
 
+pub const SECONDS_PER_DAY: i64 = 86_400;
 const SYNODIC_MONTH: f64 = 29.530_588_67;
 const NEW_MOON_EPOCH_UTC: i64 = 947_182_440; // 2000-01-06 18:14:00 UTC
 
@@ -44,6 +45,24 @@ pub fn is_night(dt: i64, sunrise: Option<i64>, sunset: Option<i64>) -> bool {
     }
 }
 
+/// Sunrise and sunset on the local day of `dt`, from the daily forecast
+///
+/// Falls back to today's for timestamps past the forecast.
+pub fn sun_window_for(dt: i64, data: &ApiResponse) -> Option<(i64, i64)> {
+    let bucket = (dt + data.timezone_offset).div_euclid(SECONDS_PER_DAY);
+    data.daily
+        .iter()
+        .filter(|d| (d.dt + data.timezone_offset).div_euclid(SECONDS_PER_DAY) == bucket)
+        .find_map(|d| d.sunrise.zip(d.sunset))
+        .or_else(|| data.current.sunrise.zip(data.current.sunset))
+}
+
+/// Whether `dt` is at night, using the sun times of its own day
+pub fn is_night_at(dt: i64, data: &ApiResponse) -> bool {
+    let (sunrise, sunset) = sun_window_for(dt, data).unzip();
+    is_night(dt, sunrise, sunset)
+}
+
 /// Selects an appropriate emoji icon based on weather description and time of day
 pub fn pick_icon(desc: &WeatherDesc, is_night: bool, moon_icon: Option<&str>) -> String {
     let main = desc.main.as_deref().unwrap_or("").to_lowercase();
@@ -57,11 +76,7 @@ pub fn pick_icon(desc: &WeatherDesc, is_night: bool, moon_icon: Option<&str>) ->
     } else if full.contains("fog") || full.contains("mist") || full.contains("haze") {
         "🌫️".into()
     } else if main.contains("cloud") || full.contains("cloud") || full.contains("overcast") {
-        if is_night {
-            "☁️".into()
-        } else {
-            "☁️".into()
-        }
+        "☁️".into()
     } else if main.contains("clear") || full.contains("clear") || full.contains("sun") {
         if is_night {
             moon_icon.unwrap_or("🌙").to_string()
@@ -75,7 +90,6 @@ pub fn pick_icon(desc: &WeatherDesc, is_night: bool, moon_icon: Option<&str>) ->
 
 /// Maps weather description to a small icon key for chart usage.
 /// Keys are asset identifiers (e.g., "clear_day") rather than emoji.
-#[allow(dead_code)]
 pub fn pick_small_icon_key(desc: &WeatherDesc, is_night: bool) -> &'static str {
     let main = desc.main.as_deref().unwrap_or("").to_lowercase();
     let full = desc.description.as_deref().unwrap_or("").to_lowercase();
@@ -198,6 +212,24 @@ pub fn escape_pango(input: &str) -> String {
         .replace('"', "&quot;")
 }
 
+/// Describes an age in seconds, e.g. "just now", "5 min", "3 h" or "2 days"
+pub fn format_age(secs: i64) -> String {
+    match secs.max(0) {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{} min", s / 60),
+        s if s < 2 * 86400 => format!("{} h", s / 3600),
+        s => format!("{} days", s / 86400),
+    }
+}
+
+/// Describes how long ago something happened, e.g. "just now" or "5 min ago"
+pub fn format_ago(secs: i64) -> String {
+    match format_age(secs) {
+        age if secs < 60 => age,
+        age => format!("{} ago", age),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -294,6 +326,17 @@ mod tests {
         assert!(sl.ends_with('█'));
     }
 
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(-5), "just now");
+        assert_eq!(format_age(59), "just now");
+        assert_eq!(format_age(600), "10 min");
+        assert_eq!(format_age(7200), "2 h");
+        assert_eq!(format_age(3 * 86400), "3 days");
+        assert_eq!(format_ago(30), "just now");
+        assert_eq!(format_ago(840), "14 min ago");
+    }
+
     #[test]
     fn test_uvi_color() {
         assert_eq!(uvi_color(1.0), "#a3be8c");
@@ -0,0 +1,771 @@
+//! Configuration validation
+//!
+//! This module checks config.toml for mistakes that would otherwise be papered
+//! over by defaults (bad colors, unsorted bands, dangling preset names, ...) and
+//! reports them with the line they occur on.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::actions::{all_actions, preset_action_target, ACTION_FIELDS};
+use crate::config::{
+    ActionConfig, CacheConfigResolved, Config, SectionKind, TempBand, MAX_FORECAST_DAYS,
+    MIN_FORECAST_DAYS,
+};
+use crate::formatting::{CURRENT_FIELDS, LOCATIONS_FIELD, PLACE_FIELD};
+use crate::logging::{check_level, DEFAULT_FILE_LEVEL};
+use crate::template;
+use crate::waybar::MAX_SIGNAL;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The setting is wrong and will be ignored or misbehave
+    Error,
+    /// The setting works but probably not the way the user intended
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single problem found in the config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line number, if the problem could be located
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// An error at `line`, if known
+    pub fn error(line: Option<usize>, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// A warning at `line`, if known
+    pub fn warning(line: Option<usize>, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}: {}", line, self.severity, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+/// Returns true if any diagnostic is an error
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+/// Validates the config file at `path`
+///
+/// A missing file is not an error (defaults are used), so it yields no diagnostics.
+pub fn check_config_file(path: &Path) -> Vec<Diagnostic> {
+    match fs::read_to_string(path) {
+        Ok(contents) => validate_config_str(&contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => vec![Diagnostic::error(
+            None,
+            format!("cannot read {}: {}", path.display(), e),
+        )],
+    }
+}
+
+/// Validates config TOML contents and returns every problem found
+pub fn validate_config_str(contents: &str) -> Vec<Diagnostic> {
+    let config: Config = match toml::from_str(contents) {
+        Ok(c) => c,
+        Err(e) => {
+            let line = e.span().map(|s| line_of_offset(contents, s.start));
+            let message = e.message().trim().replace('\n', "; ");
+            return vec![Diagnostic::error(line, message)];
+        }
+    };
+
+    let lines = KeyLines::index(contents);
+    let mut diags = Vec::new();
+
+    if let Some(colors) = &config.colors {
+        for (key, value) in [("header", &colors.header), ("text", &colors.text)] {
+            if let Some(v) = value {
+                if !is_hex_color(v) {
+                    diags.push(Diagnostic::error(
+                        lines.find(&format!("colors.{}", key)),
+                        format!("colors.{}: invalid hex color \"{}\"", key, v),
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some(bands) = &config.temp_bands {
+        check_temp_bands(bands, "temp_bands", &lines, &mut diags);
+    }
+
+    let presets = config.location_presets.as_deref().unwrap_or(&[]);
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (i, preset) in presets.iter().enumerate() {
+        if !(-90.0..=90.0).contains(&preset.lat) {
+            diags.push(Diagnostic::error(
+                lines.find(&format!("location_presets[{}].lat", i)),
+                format!(
+                    "preset \"{}\": latitude {} is outside -90..90",
+                    preset.name, preset.lat
+                ),
+            ));
+        }
+        if !(-180.0..=180.0).contains(&preset.lon) {
+            diags.push(Diagnostic::error(
+                lines.find(&format!("location_presets[{}].lon", i)),
+                format!(
+                    "preset \"{}\": longitude {} is outside -180..180",
+                    preset.name, preset.lon
+                ),
+            ));
+        }
+        if let Some(bands) = &preset.temp_bands {
+            let path = format!("location_presets[{}].temp_bands", i);
+            check_temp_bands(bands, &path, &lines, &mut diags);
+        }
+        if preset
+            .api_key
+            .as_deref()
+            .is_some_and(|k| k.trim().is_empty())
+        {
+            diags.push(Diagnostic::error(
+                lines.find(&format!("location_presets[{}].api_key", i)),
+                format!(
+                    "preset \"{}\": api_key is empty; remove it to use the global key",
+                    preset.name
+                ),
+            ));
+        }
+        if let Some(first) = seen.insert(&preset.name, i) {
+            diags.push(Diagnostic::error(
+                lines.find(&format!("location_presets[{}].name", i)),
+                format!(
+                    "duplicate preset name \"{}\" (also location_presets[{}])",
+                    preset.name, first
+                ),
+            ));
+        }
+    }
+
+    if let Some(active) = &config.active_preset {
+        if !presets.iter().any(|p| &p.name == active) {
+            diags.push(Diagnostic::error(
+                lines.find("active_preset"),
+                format!("active_preset \"{}\" does not match any preset", active),
+            ));
+        }
+    }
+
+    for name in config.bar_presets.iter().flatten() {
+        if !presets.iter().any(|p| &p.name == name) {
+            diags.push(Diagnostic::error(
+                lines.find("bar_presets"),
+                format!("bar_presets: \"{}\" does not match any preset", name),
+            ));
+        }
+    }
+
+    if let Some(signal) = config.waybar.as_ref().and_then(|w| w.signal) {
+        if !(1..=MAX_SIGNAL).contains(&signal) {
+            diags.push(Diagnostic::error(
+                lines.find("waybar.signal"),
+                format!(
+                    "waybar.signal = {} is outside 1..{}; Waybar is reloaded instead",
+                    signal, MAX_SIGNAL
+                ),
+            ));
+        }
+    }
+
+    for (name, action) in config.actions.iter().flatten() {
+        let path = format!("actions.{}", name);
+        if let Some(preset) = preset_action_target(action) {
+            if !presets.iter().any(|p| p.name == preset) {
+                diags.push(Diagnostic::error(
+                    lines.find(&format!("{}.preset", path)),
+                    format!(
+                        "{}.preset \"{}\" is not next, prev or a preset",
+                        path, preset
+                    ),
+                ));
+            }
+        }
+        let (src, path) = match action {
+            ActionConfig::Url { url } => (url, format!("{}.url", path)),
+            ActionConfig::Command { command } => (command, format!("{}.command", path)),
+            _ => continue,
+        };
+        match template::parse(src) {
+            Ok(_) => check_template(src, ACTION_FIELDS, &path, &lines, &mut diags),
+            Err(e) => diags.push(Diagnostic::error(
+                lines.find(&path),
+                format!("{}: {}; the action will fail", path, e),
+            )),
+        }
+    }
+
+    if let Some(i3bar) = &config.i3bar {
+        let actions = all_actions(&config);
+        let buttons = [
+            ("left", &i3bar.left),
+            ("middle", &i3bar.middle),
+            ("right", &i3bar.right),
+            ("scroll_up", &i3bar.scroll_up),
+            ("scroll_down", &i3bar.scroll_down),
+        ];
+        for (button, name) in buttons {
+            let Some(name) = name.as_deref().filter(|n| !n.is_empty()) else {
+                continue;
+            };
+            if !actions.contains_key(name) {
+                let path = format!("i3bar.{}", button);
+                diags.push(Diagnostic::error(
+                    lines.find(&path),
+                    format!(
+                        "{} \"{}\" is not an action; the button does nothing",
+                        path, name
+                    ),
+                ));
+            }
+        }
+    }
+
+    if let Some(log) = &config.log {
+        if let Some(Err(e)) = log.level.as_deref().map(check_level) {
+            diags.push(Diagnostic::error(
+                lines.find("log.level"),
+                format!(
+                    "log.level: {}; the default \"{}\" is used instead",
+                    e, DEFAULT_FILE_LEVEL
+                ),
+            ));
+        }
+        if log.keep_days == Some(0) {
+            diags.push(Diagnostic::warning(
+                lines.find("log.keep_days"),
+                "log.keep_days = 0 keeps one day of logs".to_string(),
+            ));
+        }
+    }
+
+    if let Some(cache) = &config.cache {
+        if cache.ttl_minutes == Some(0) {
+            diags.push(Diagnostic::warning(
+                lines.find("cache.ttl_minutes"),
+                "cache.ttl_minutes = 0 fetches on every update, which may exceed the API's free calls"
+                    .to_string(),
+            ));
+        }
+        let policy = CacheConfigResolved::from_config(&config.cache);
+        // 0 deliberately never shows old data
+        if cache.max_stale_hours != Some(0) && policy.max_stale_secs < policy.ttl_secs {
+            diags.push(Diagnostic::warning(
+                lines.find("cache.max_stale_hours"),
+                "cache.max_stale_hours is shorter than cache.ttl_minutes, so old data is never shown when a fetch fails"
+                    .to_string(),
+            ));
+        }
+    }
+
+    if let Some(dash) = &config.dashboard {
+        let days = dash.forecast_days;
+        if days != 0 && !(MIN_FORECAST_DAYS..=MAX_FORECAST_DAYS).contains(&days) {
+            diags.push(Diagnostic::warning(
+                lines.find("dashboard.forecast_days"),
+                format!(
+                    "dashboard.forecast_days = {} is outside {}..{} and will be clamped",
+                    days, MIN_FORECAST_DAYS, MAX_FORECAST_DAYS
+                ),
+            ));
+        }
+    }
+
+    if let Some(format) = &config.format {
+        let location_fields: Vec<&str> = CURRENT_FIELDS
+            .iter()
+            .copied()
+            .chain([PLACE_FIELD])
+            .collect();
+        if let Some(bar) = &format.bar {
+            let mut fields = CURRENT_FIELDS.to_vec();
+            if config.bar_presets.is_some() {
+                fields.push(LOCATIONS_FIELD);
+            }
+            check_template(bar, &fields, "format.bar", &lines, &mut diags);
+        }
+        if let Some(t) = &format.location_bar {
+            let path = "format.location_bar";
+            check_template(t, &location_fields, path, &lines, &mut diags);
+        }
+        if let Some(t) = &format.location_tooltip {
+            let path = "format.location_tooltip";
+            check_template(t, &location_fields, path, &lines, &mut diags);
+        }
+        for (i, section) in format.tooltip.iter().flatten().enumerate() {
+            let defaults = section.section.defaults();
+            let path = format!("format.tooltip[{}]", i);
+            if let Some(t) = &section.template {
+                let path = format!("{}.template", path);
+                check_template(t, defaults.fields, &path, &lines, &mut diags);
+            }
+            if let Some(t) = &section.title {
+                let path = format!("{}.title", path);
+                check_template(t, defaults.title_fields, &path, &lines, &mut diags);
+            }
+            match section.count {
+                Some(_) if section.section == SectionKind::Current => {
+                    diags.push(Diagnostic::warning(
+                        lines.find(&format!("{}.count", path)),
+                        format!("{}.count has no effect on the current section", path),
+                    ));
+                }
+                Some(n) if n > defaults.max_count => {
+                    diags.push(Diagnostic::warning(
+                        lines.find(&format!("{}.count", path)),
+                        format!(
+                            "{}.count = {} is more than the forecast provides ({})",
+                            path, n, defaults.max_count
+                        ),
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    diags.sort_by_key(|d| d.line.unwrap_or(usize::MAX));
+    diags
+}
+
+/// Checks that a format template parses and only uses known placeholders
+fn check_template(
+    src: &str,
+    fields: &[&str],
+    path: &str,
+    lines: &KeyLines,
+    diags: &mut Vec<Diagnostic>,
+) {
+    let line = lines.find(path);
+    match template::parse(src) {
+        Ok(nodes) => {
+            for name in template::names(&nodes) {
+                if !fields.contains(&name) {
+                    diags.push(Diagnostic::warning(
+                        line,
+                        format!(
+                            "{}: unknown placeholder {{{}}} (available: {})",
+                            path,
+                            name,
+                            fields.join(", ")
+                        ),
+                    ));
+                }
+            }
+        }
+        Err(e) => diags.push(Diagnostic::error(
+            line,
+            format!("{}: {}; the default is used instead", path, e),
+        )),
+    }
+}
+
+/// Checks a temp_bands list (global or per-preset) found at `path`
+fn check_temp_bands(bands: &[TempBand], path: &str, lines: &KeyLines, diags: &mut Vec<Diagnostic>) {
+    if bands.is_empty() {
+        diags.push(Diagnostic::error(
+            lines.find(path),
+            format!("{} is empty; remove it to use the default palette", path),
+        ));
+    }
+    for (i, band) in bands.iter().enumerate() {
+        if !is_hex_color(&band.color) {
+            diags.push(Diagnostic::error(
+                lines.find(&format!("{}[{}].color", path, i)),
+                format!(
+                    "{}[{}].color: invalid hex color \"{}\"",
+                    path, i, band.color
+                ),
+            ));
+        }
+        if i > 0 && band.max < bands[i - 1].max {
+            diags.push(Diagnostic::error(
+                lines.find(&format!("{}[{}].max", path, i)),
+                format!(
+                    "{} must be sorted by ascending max ({} comes after {})",
+                    path,
+                    band.max,
+                    bands[i - 1].max
+                ),
+            ));
+        }
+    }
+}
+
+/// Checks for `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`
+pub fn is_hex_color(s: &str) -> bool {
+    match s.strip_prefix('#') {
+        Some(hex) => {
+            matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
+/// Converts a byte offset into a 1-based line number
+fn line_of_offset(contents: &str, offset: usize) -> usize {
+    contents[..offset.min(contents.len())]
+        .bytes()
+        .filter(|&b| b == b'\n')
+        .count()
+        + 1
+}
+
+/// Maps dotted key paths (e.g. `location_presets[1].lat`) to the line defining them
+///
+/// This is a line-oriented scan rather than a full TOML parser; it only needs to
+/// be good enough to point users at the right line for values that did parse.
+struct KeyLines(HashMap<String, usize>);
+
+/// Rewrites a table header inside an array of tables to point at its latest
+/// element, e.g. `location_presets.time` -> `location_presets[1].time`
+fn nest_in_arrays(name: &str, array_counts: &HashMap<String, usize>) -> String {
+    let name = name.trim();
+    array_counts
+        .iter()
+        .filter(|(array, _)| {
+            name.len() > array.len()
+                && name.starts_with(array.as_str())
+                && name.as_bytes()[array.len()] == b'.'
+        })
+        .max_by_key(|(array, _)| array.len())
+        .map(|(array, n)| format!("{}[{}]{}", array, n - 1, &name[array.len()..]))
+        .unwrap_or_else(|| name.to_string())
+}
+
+impl KeyLines {
+    fn index(contents: &str) -> Self {
+        let mut map = HashMap::new();
+        let mut table = String::new();
+        let mut array_counts: HashMap<String, usize> = HashMap::new();
+
+        for (idx, raw) in contents.lines().enumerate() {
+            let line = raw.trim();
+            let line_no = idx + 1;
+
+            if let Some(rest) = line.strip_prefix("[[") {
+                let name = nest_in_arrays(rest.split("]]").next().unwrap_or(""), &array_counts);
+                let n = array_counts.entry(name.clone()).or_insert(0);
+                table = format!("{}[{}]", name, n);
+                *n += 1;
+                map.entry(name).or_insert(line_no);
+                map.insert(table.clone(), line_no);
+            } else if let Some(rest) = line.strip_prefix('[') {
+                table = nest_in_arrays(rest.split(']').next().unwrap_or(""), &array_counts);
+                map.insert(table.clone(), line_no);
+            } else if let Some((key, _)) = line.split_once('=') {
+                let key = key.trim().trim_matches('"');
+                if key.is_empty() || key.starts_with('#') {
+                    continue;
+                }
+                let path = if table.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", table, key)
+                };
+                map.entry(path).or_insert(line_no);
+            }
+        }
+
+        KeyLines(map)
+    }
+
+    /// Finds the line for `path`, falling back to its enclosing table
+    fn find(&self, path: &str) -> Option<usize> {
+        let mut path = path;
+        loop {
+            if let Some(line) = self.0.get(path) {
+                return Some(*line);
+            }
+            path = &path[..path.rfind('.')?];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_config_has_no_diagnostics() {
+        let toml = r##"
+units = "metric"
+active_preset = "home"
+
+[colors]
+header = "#f4b8e4"
+
+[[location_presets]]
+name = "home"
+lat = 40.7
+lon = -74.0
+label = "New York"
+
+[[temp_bands]]
+max = 50.0
+color = "#8caaee"
+
+[[temp_bands]]
+max = 500.0
+color = "#e78284"
+"##;
+        assert!(validate_config_str(toml).is_empty());
+    }
+
+    #[test]
+    fn test_parse_error_is_line_numbered() {
+        let toml = "units = \"metric\"\nactive_preset = home\n";
+        let diags = validate_config_str(toml);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].severity, Severity::Error);
+        assert_eq!(diags[0].line, Some(2));
+    }
+
+    #[test]
+    fn test_semantic_errors_are_located() {
+        let toml = r##"active_preset = "office"
+
+[colors]
+header = "pink"
+
+[[location_presets]]
+name = "home"
+lat = 140.0
+lon = -74.0
+label = "Home"
+
+[[location_presets]]
+name = "home"
+lat = 10.0
+lon = 10.0
+label = "Home again"
+
+[[temp_bands]]
+max = 80.0
+color = "#fff"
+
+[[temp_bands]]
+max = 60.0
+color = "#000000"
+
+[dashboard]
+forecast_days = 30
+"##;
+        let diags = validate_config_str(toml);
+        let found: Vec<(Option<usize>, Severity)> =
+            diags.iter().map(|d| (d.line, d.severity)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(1), Severity::Error),    // active_preset
+                (Some(4), Severity::Error),    // colors.header
+                (Some(8), Severity::Error),    // lat
+                (Some(13), Severity::Error),   // duplicate name
+                (Some(23), Severity::Error),   // unsorted band
+                (Some(27), Severity::Warning), // forecast_days
+            ]
+        );
+        assert!(has_errors(&diags));
+    }
+
+    #[test]
+    fn test_preset_overrides_are_checked() {
+        let toml = r##"[[location_presets]]
+name = "berlin"
+lat = 52.5
+lon = 13.4
+label = "Berlin"
+units = "metric"
+api_key = " "
+
+[location_presets.time]
+clock = "24h"
+
+[[location_presets.temp_bands]]
+max = 10.0
+color = "#8caaee"
+
+[[location_presets.temp_bands]]
+max = 5.0
+color = "blue"
+"##;
+        let diags = validate_config_str(toml);
+        let lines: Vec<Option<usize>> = diags.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![Some(7), Some(17), Some(18)]);
+    }
+
+    #[test]
+    fn test_format_templates_are_checked() {
+        let toml = r#"
+[format]
+bar = "{icon} {temp"
+
+[[format.tooltip]]
+section = "hourly"
+count = 72
+template = "{time} {hi}"
+
+[[format.tooltip]]
+section = "daily"
+title = "{?count}Next {count} days{/count}"
+"#;
+        let got: Vec<(Option<usize>, Severity)> = validate_config_str(toml)
+            .into_iter()
+            .map(|d| (d.line, d.severity))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                (Some(3), Severity::Error),   // unclosed placeholder
+                (Some(7), Severity::Warning), // count
+                (Some(8), Severity::Warning), // {hi} is not an hourly field
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bar_presets_are_checked() {
+        let toml = r#"
+bar_presets = ["home", "cabin"]
+
+[format]
+bar = "{locations}"
+location_bar = "{place} {temp} {time}"
+
+[[location_presets]]
+name = "home"
+label = "Home"
+lat = 40.7
+lon = -74.0
+"#;
+        let got: Vec<(Option<usize>, Severity)> = validate_config_str(toml)
+            .into_iter()
+            .map(|d| (d.line, d.severity))
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                (Some(2), Severity::Error),   // no preset "cabin"
+                (Some(6), Severity::Warning), // {time}
+            ]
+        );
+        // {locations} only makes sense with bar_presets
+        let diags = validate_config_str("[format]\nbar = \"{locations}\"\n");
+        assert_eq!(diags.len(), 1);
+    }
+
+    #[test]
+    fn test_waybar_signal_range() {
+        assert!(validate_config_str("[waybar]\nsignal = 8\n").is_empty());
+        let diags = validate_config_str("[waybar]\nsignal = 0\n");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].line, Some(2));
+    }
+
+    #[test]
+    fn test_actions_are_checked() {
+        let toml = r#"
+[actions.radar]
+type = "url"
+url = "https://example.com/?at={lat},{lon}&z={zoom}"
+
+[actions.cabin]
+type = "preset"
+preset = "cabin"
+
+[actions.up]
+type = "preset"
+preset = "next"
+"#;
+        let diags = validate_config_str(toml);
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].line, Some(4));
+        assert!(diags[0].message.contains("{zoom}"));
+        assert_eq!(diags[1].severity, Severity::Error);
+        assert_eq!(diags[1].line, Some(8));
+
+        let diags =
+            validate_config_str("[actions.x]\ntype = \"command\"\ncommand = \"echo {lat\"\n");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].severity, Severity::Error);
+
+        let diags = validate_config_str(
+            "[actions.radar]\ntype = \"url\"\nurl = \"https://example.com\"\n\n\
+             [i3bar]\nleft = \"radar\"\nmiddle = \"\"\nright = \"radr\"\n",
+        );
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].line, Some(8));
+        assert!(diags[0].message.contains("\"radr\""));
+    }
+
+    #[test]
+    fn test_log_level() {
+        let ok = "[log]\nfile = true\nlevel = \"warn,redweather::weather=trace\"\n";
+        assert!(validate_config_str(ok).is_empty());
+        let diags = validate_config_str("[log]\nlevel = \"redweather=loud\"\n");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].line, Some(2));
+    }
+
+    #[test]
+    fn test_cache_policy() {
+        let ok = "[cache]\nttl_minutes = 15\nmax_stale_hours = 0\n";
+        assert!(validate_config_str(ok).is_empty());
+        let diags = validate_config_str("[cache]\nttl_minutes = 180\nmax_stale_hours = 2\n");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].line, Some(3));
+    }
+
+    #[test]
+    fn test_empty_temp_bands() {
+        let diags = validate_config_str("temp_bands = []\n");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].line, Some(1));
+    }
+
+    #[test]
+    fn test_is_hex_color() {
+        assert!(is_hex_color("#fff"));
+        assert!(is_hex_color("#A6D189"));
+        assert!(is_hex_color("#a6d18980"));
+        assert!(!is_hex_color("a6d189"));
+        assert!(!is_hex_color("#a6d18"));
+        assert!(!is_hex_color("#gggggg"));
+    }
+}
@@ -0,0 +1,474 @@
+//! Waybar integration helpers
+
+use serde_json::{json, Value};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::{
+    load_config, ColorsResolved, Config, LocationSettings, PercentageMetric, UiConfigResolved,
+    Units, WaybarConfig,
+};
+use crate::formatting::{
+    format_config_diagnostics, format_locations_text, format_popup_text, LocationEntry,
+};
+use crate::ipc::{self, Request};
+use crate::utils::{is_night, pick_small_icon_key};
+use crate::validate::Diagnostic;
+use crate::weather::{ApiResponse, BarLocation, FetchedWeather, ResolvedLocation, WeatherDesc};
+
+/// Highest `waybar.signal`; Waybar accepts SIGRTMIN+1 up to SIGRTMAX
+pub const MAX_SIGNAL: u32 = 30;
+
+/// Makes Waybar show preset and config changes immediately
+///
+/// A running `redweather serve` is told to re-read the config first, so
+/// attached modules see the change too; this blocks until it has fetched
+/// again, so GUI code must call it off the main loop. With `waybar.signal` set, only the
+/// weather module is refreshed (plus any `redweather daemon`, which Waybar
+/// does not restart); otherwise SIGUSR2 reloads Waybar and every module.
+/// Only this user's processes are signalled.
+pub fn reload_waybar() {
+    let _ = ipc::request(&Request::Reload);
+    let Some(uid) = current_uid() else {
+        return;
+    };
+    let signal = load_config().waybar.and_then(|w| w.signal);
+    match signal.filter(|n| (1..=MAX_SIGNAL).contains(n)) {
+        Some(n) => {
+            reload_daemons(uid);
+            signal_waybar(&format!("RTMIN+{}", n), uid);
+        }
+        None => signal_waybar("USR2", uid),
+    }
+}
+
+fn signal_waybar(signal: &str, uid: u32) {
+    let _ = Command::new("pkill")
+        .args(["--signal", signal, "-U", &uid.to_string(), "-x", "waybar"])
+        .status();
+}
+
+/// Sends SIGHUP to this user's `redweather daemon` processes
+fn reload_daemons(uid: u32) {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return;
+    };
+    let own = std::process::id().to_string();
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(pid) = name.to_str() else {
+            continue;
+        };
+        if pid == own || !pid.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        if entry.metadata().map(|m| m.uid()).ok() != Some(uid) {
+            continue;
+        }
+        let is_daemon = fs::read(entry.path().join("cmdline"))
+            .map(|c| is_daemon_cmdline(&c))
+            .unwrap_or(false);
+        if is_daemon {
+            let _ = Command::new("kill").args(["-HUP", pid]).status();
+        }
+    }
+}
+
+/// Whether a NUL-separated command line runs `redweather daemon`
+fn is_daemon_cmdline(cmdline: &[u8]) -> bool {
+    let mut args = cmdline
+        .split(|&b| b == 0)
+        .map(|a| String::from_utf8_lossy(a));
+    let is_redweather = args
+        .next()
+        .is_some_and(|a| Path::new(a.as_ref()).file_name() == Some("redweather".as_ref()));
+    is_redweather && args.any(|a| a == "daemon")
+}
+
+fn current_uid() -> Option<u32> {
+    fs::metadata("/proc/self").map(|m| m.uid()).ok()
+}
+
+/// Why the bar shows a fallback instead of the weather
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fallback {
+    NoKey,
+    NoLocation,
+    UnknownPreset,
+    NotFound,
+    ConfigError,
+    LocationError,
+    FetchError,
+}
+
+impl Fallback {
+    /// CSS classes: `setup` or `error`, then one naming the problem
+    pub fn classes(self) -> [&'static str; 2] {
+        match self {
+            Fallback::NoKey => ["setup", "no-key"],
+            Fallback::NoLocation => ["setup", "no-location"],
+            Fallback::UnknownPreset => ["error", "unknown-preset"],
+            Fallback::NotFound => ["error", "not-found"],
+            Fallback::ConfigError => ["error", "config-error"],
+            Fallback::LocationError => ["error", "location-error"],
+            Fallback::FetchError => ["error", "fetch-error"],
+        }
+    }
+}
+
+/// Builds the JSON for a fallback; `alt` is the specific class
+pub fn fallback_json(text: &str, tooltip: &str, fallback: Fallback) -> Value {
+    let classes = fallback.classes();
+    json!({
+        "text": text,
+        "tooltip": tooltip,
+        "markup": "pango",
+        "class": classes,
+        "alt": classes[1],
+    })
+}
+
+/// Formats the bar text and tooltip and builds the JSON for them
+///
+/// Config problems are listed at the top of the tooltip.
+pub fn bar_json(
+    fetched: &FetchedWeather,
+    resolved: &ResolvedLocation,
+    cfg: &Config,
+    diagnostics: &[Diagnostic],
+) -> Value {
+    let ui_resolved = UiConfigResolved::from_config(&cfg.ui);
+    let colors_resolved = ColorsResolved::from_config(&cfg.colors);
+    let (text, tooltip) = format_popup_text(
+        fetched,
+        &resolved.location.label,
+        &ui_resolved,
+        &colors_resolved,
+        &resolved.settings,
+        cfg.format.as_ref(),
+    );
+    let tooltip = with_diagnostics(tooltip, diagnostics, &ui_resolved, &colors_resolved);
+    weather_json(
+        &text,
+        &tooltip,
+        fetched,
+        &resolved.settings,
+        cfg.waybar.as_ref(),
+    )
+}
+
+/// Builds the JSON for several locations side by side (`bar_presets`)
+///
+/// `results` are in the same order as `locations`. Class, `alt` and
+/// `percentage` describe the first location with weather; `stale` is added if
+/// any location is outdated or failed.
+pub fn multi_bar_json(
+    locations: &[BarLocation],
+    results: &[anyhow::Result<FetchedWeather>],
+    cfg: &Config,
+    diagnostics: &[Diagnostic],
+) -> Value {
+    let ui_resolved = UiConfigResolved::from_config(&cfg.ui);
+    let colors_resolved = ColorsResolved::from_config(&cfg.colors);
+    let entries: Vec<LocationEntry> = locations
+        .iter()
+        .zip(results)
+        .map(|(l, r)| LocationEntry {
+            place: &l.place,
+            label: &l.resolved.location.label,
+            settings: &l.resolved.settings,
+            weather: r.as_ref().map_err(|e| format!("Weather error: {}", e)),
+        })
+        .collect();
+    let (text, tooltip) = format_locations_text(
+        &entries,
+        &ui_resolved,
+        &colors_resolved,
+        cfg.format.as_ref(),
+    );
+    let tooltip = with_diagnostics(tooltip, diagnostics, &ui_resolved, &colors_resolved);
+
+    let first = locations
+        .iter()
+        .zip(results)
+        .find_map(|(l, r)| r.as_ref().ok().map(|f| (l, f)));
+    let Some((location, fetched)) = first else {
+        return fallback_json(&text, &tooltip, Fallback::FetchError);
+    };
+    let mut json = weather_json(
+        &text,
+        &tooltip,
+        fetched,
+        &location.resolved.settings,
+        cfg.waybar.as_ref(),
+    );
+    let degraded = locations.iter().zip(results).any(|(l, r)| {
+        r.as_ref()
+            .map_or(true, |f| f.is_outdated(l.resolved.settings.cache.ttl_secs))
+    });
+    if let Some(classes) = json["class"].as_array_mut() {
+        if degraded && !classes.iter().any(|c| c == "stale") {
+            classes.push("stale".into());
+        }
+    }
+    json
+}
+
+/// Lists config problems at the top of the tooltip
+fn with_diagnostics(
+    tooltip: String,
+    diagnostics: &[Diagnostic],
+    ui: &UiConfigResolved,
+    colors: &ColorsResolved,
+) -> String {
+    if diagnostics.is_empty() {
+        return tooltip;
+    }
+    let section = format_config_diagnostics(diagnostics, colors, ui.font_size);
+    format!("{}\n\n{}", section.join("\n"), tooltip)
+}
+
+/// Builds the JSON for a successful update
+pub fn weather_json(
+    text: &str,
+    tooltip: &str,
+    fetched: &FetchedWeather,
+    settings: &LocationSettings,
+    cfg: Option<&WaybarConfig>,
+) -> Value {
+    let data = &fetched.data;
+    json!({
+        "text": text,
+        "tooltip": tooltip,
+        "markup": "pango",
+        "class": weather_classes(fetched, settings, cfg),
+        "alt": condition_key(data),
+        "percentage": percentage(data, settings, cfg),
+    })
+}
+
+/// Thresholds for the temperature and wind classes in the given units
+struct Thresholds {
+    hot: f64,
+    freezing: f64,
+    windy: f64,
+}
+
+impl Thresholds {
+    fn resolve(cfg: Option<&WaybarConfig>, units: Units) -> Self {
+        let (hot, freezing, windy) = match units {
+            Units::Imperial => (86.0, 32.0, 20.0),
+            Units::Metric => (30.0, 0.0, 9.0),
+        };
+        Thresholds {
+            hot: cfg.and_then(|c| c.hot).unwrap_or(hot),
+            freezing: cfg.and_then(|c| c.freezing).unwrap_or(freezing),
+            windy: cfg.and_then(|c| c.windy).unwrap_or(windy),
+        }
+    }
+}
+
+fn current_desc(data: &ApiResponse) -> WeatherDesc {
+    data.current
+        .weather
+        .first()
+        .cloned()
+        .unwrap_or(WeatherDesc {
+            main: None,
+            description: None,
+        })
+}
+
+/// The condition key used for `alt`, e.g. `rain_light` or `clear_night`
+fn condition_key(data: &ApiResponse) -> &'static str {
+    let cur = &data.current;
+    pick_small_icon_key(
+        &current_desc(data),
+        is_night(cur.dt, cur.sunrise, cur.sunset),
+    )
+}
+
+/// CSS classes describing the current conditions
+pub fn weather_classes(
+    fetched: &FetchedWeather,
+    settings: &LocationSettings,
+    cfg: Option<&WaybarConfig>,
+) -> Vec<&'static str> {
+    let data = &fetched.data;
+    let cur = &data.current;
+    let limits = Thresholds::resolve(cfg, settings.units);
+    let mut classes = Vec::new();
+
+    let condition = match condition_key(data) {
+        "thunderstorm" => Some("storm"),
+        "snow" | "sleet" => Some("snow"),
+        "rain" | "rain_light" | "rain_heavy" => Some("rain"),
+        "fog" => Some("fog"),
+        "cloudy" | "partly_cloudy_day" | "partly_cloudy_night" => Some("cloudy"),
+        "clear_day" | "clear_night" => Some("clear"),
+        _ => None,
+    };
+    classes.extend(condition);
+    if cur.temp >= limits.hot {
+        classes.push("hot");
+    }
+    if cur.temp <= limits.freezing {
+        classes.push("freezing");
+    }
+    if cur.wind_speed.is_some_and(|w| w >= limits.windy) {
+        classes.push("windy");
+    }
+    if is_night(cur.dt, cur.sunrise, cur.sunset) {
+        classes.push("night");
+    }
+    if fetched.is_outdated(settings.cache.ttl_secs) {
+        classes.push("stale");
+    }
+    classes
+}
+
+/// Maps the configured metric onto 0-100 for Waybar's `percentage`
+pub fn percentage(
+    data: &ApiResponse,
+    settings: &LocationSettings,
+    cfg: Option<&WaybarConfig>,
+) -> u8 {
+    let cur = &data.current;
+    let pct = match cfg.and_then(|c| c.percentage).unwrap_or_default() {
+        PercentageMetric::Temperature => {
+            // From the first band's max to where the last (open-ended) band starts
+            let bands = &settings.temp_bands;
+            let lo = bands.first().map(|b| b.max).unwrap_or(0.0);
+            let hi = match bands.len() {
+                0 | 1 => lo,
+                n => bands[n - 2].max,
+            };
+            if hi > lo {
+                (cur.temp - lo) / (hi - lo) * 100.0
+            } else if cur.temp > lo {
+                100.0
+            } else {
+                0.0
+            }
+        }
+        PercentageMetric::Pop => data.hourly.first().and_then(|h| h.pop).unwrap_or(0.0) * 100.0,
+        PercentageMetric::Humidity => cur.humidity.unwrap_or(0) as f64,
+        PercentageMetric::Uv => cur.uvi.unwrap_or(0.0) / 11.0 * 100.0,
+    };
+    pct.round().clamp(0.0, 100.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn fetched(temp: f64, wind: f64, main: &str, desc: &str, stale: bool) -> FetchedWeather {
+        let data = serde_json::from_value(json!({
+            "timezone_offset": 0,
+            "current": {
+                "dt": 1_684_929_490, "temp": temp, "wind_speed": wind, "humidity": 40,
+                "uvi": 5.5, "sunrise": 1_684_905_000, "sunset": 1_684_958_000,
+                "weather": [{"main": main, "description": desc}]
+            },
+            "hourly": [{"dt": 1_684_929_600, "temp": temp, "pop": 0.25, "weather": []}],
+            "daily": []
+        }))
+        .unwrap();
+        FetchedWeather {
+            data,
+            fetched_at: chrono::Utc::now().timestamp(),
+            stale,
+        }
+    }
+
+    fn settings(units: &str) -> LocationSettings {
+        let cfg: Config = toml::from_str(&format!("units = \"{}\"", units)).unwrap();
+        LocationSettings::resolve(&cfg, None, "k")
+    }
+
+    #[test]
+    fn test_weather_classes() {
+        let imperial = settings("imperial");
+        let rain = fetched(90.0, 25.0, "Rain", "light rain", true);
+        assert_eq!(
+            weather_classes(&rain, &imperial, None),
+            vec!["rain", "hot", "windy", "stale"]
+        );
+        assert_eq!(condition_key(&rain.data), "rain_light");
+
+        let snow = fetched(-3.0, 2.0, "Snow", "snow", false);
+        assert_eq!(
+            weather_classes(&snow, &settings("metric"), None),
+            vec!["snow", "freezing"]
+        );
+
+        let cfg = WaybarConfig {
+            hot: Some(70.0),
+            ..Default::default()
+        };
+        let clear = fetched(72.0, 5.0, "Clear", "clear sky", false);
+        assert_eq!(
+            weather_classes(&clear, &imperial, Some(&cfg)),
+            vec!["clear", "hot"]
+        );
+
+        // Data past the cache TTL is stale even if no fetch has failed yet
+        let old = FetchedWeather {
+            fetched_at: clear.fetched_at - 3600,
+            ..clear
+        };
+        assert_eq!(
+            weather_classes(&old, &imperial, Some(&cfg)),
+            vec!["clear", "hot", "stale"]
+        );
+    }
+
+    #[test]
+    fn test_percentage() {
+        let imperial = settings("imperial");
+        // Default bands run from 59 to 92 before the open-ended last band
+        let data = fetched(75.5, 0.0, "Clear", "clear sky", false).data;
+        assert_eq!(percentage(&data, &imperial, None), 50);
+        let data = fetched(120.0, 0.0, "Clear", "clear sky", false).data;
+        assert_eq!(percentage(&data, &imperial, None), 100);
+
+        let metric = |m| WaybarConfig {
+            percentage: Some(m),
+            ..Default::default()
+        };
+        assert_eq!(
+            percentage(&data, &imperial, Some(&metric(PercentageMetric::Pop))),
+            25
+        );
+        assert_eq!(
+            percentage(&data, &imperial, Some(&metric(PercentageMetric::Humidity))),
+            40
+        );
+        assert_eq!(
+            percentage(&data, &imperial, Some(&metric(PercentageMetric::Uv))),
+            50
+        );
+    }
+
+    #[test]
+    fn test_fallback_json() {
+        let out = fallback_json("| ❓ Setup", "tip", Fallback::NoLocation);
+        assert_eq!(out["class"], json!(["setup", "no-location"]));
+        assert_eq!(out["alt"], "no-location");
+        assert!(out.get("percentage").is_none());
+    }
+
+    #[test]
+    fn test_daemon_cmdline() {
+        assert!(is_daemon_cmdline(
+            b"/usr/bin/redweather\0daemon\0--interval=600\0"
+        ));
+        assert!(is_daemon_cmdline(b"redweather\0--config\0a.toml\0daemon\0"));
+        assert!(!is_daemon_cmdline(b"redweather\0serve\0"));
+        assert!(!is_daemon_cmdline(b"redweather\0presets\0next\0"));
+        assert!(!is_daemon_cmdline(b"/usr/bin/other\0daemon\0"));
+    }
+}
@@ -5,25 +5,33 @@
 
 use anyhow::{anyhow, Result};
 use chrono::Utc;
+use fs4::fs_std::FileExt;
 use once_cell::sync::Lazy;
-use reqwest::{Client, Url};
+use reqwest::{Client, StatusCode, Url};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::env;
 use std::fs;
-use std::path::PathBuf;
-use std::time::Duration as StdDuration;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration as StdDuration, Instant};
+use tracing::{debug, info, warn};
 
-use crate::config::{Config, Units};
+use crate::config::{CacheConfigResolved, Config, LocationPreset, LocationSettings, Units};
+use crate::logging::redact_key;
+use crate::paths;
+use crate::utils::format_age;
 
-/// Maximum age of cached weather data in seconds (10 minutes)
-pub const CACHE_MAX_AGE_SECS: i64 = 600;
-/// Cache directory path relative to HOME
-pub const CACHE_FILE: &str = ".cache/redweather";
+/// Name of the weather data provider, reported in `redweather json`
+pub const PROVIDER: &str = "openweathermap";
 /// Maximum retry attempts for API requests
 const MAX_RETRIES: u32 = 3;
 /// Base delay for exponential backoff (milliseconds)
 const RETRY_BASE_DELAY_MS: u64 = 500;
+/// How long to wait for another process fetching the same location; a fetch
+/// with every retry timing out takes about 32 seconds
+const FETCH_LOCK_TIMEOUT: StdDuration = StdDuration::from_secs(35);
+/// How often to try a fetch lock that another process holds
+const FETCH_LOCK_POLL: StdDuration = StdDuration::from_millis(100);
 
 /// Shared HTTP client for reuse across requests
 static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
@@ -33,14 +41,63 @@ static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
         .expect("Failed to create HTTP client")
 });
 
+/// Drops the URL from a reqwest error, since it contains the API key
+fn without_key(e: reqwest::Error) -> reqwest::Error {
+    e.without_url()
+}
+
 /// Represents a geographic location with coordinates and display label
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Location {
     pub lat: f64,
     pub lon: f64,
     pub label: String,
 }
 
+impl Location {
+    /// A location at the given coordinates
+    pub fn new(lat: f64, lon: f64, label: impl Into<String>) -> Self {
+        Location {
+            lat,
+            lon,
+            label: label.into(),
+        }
+    }
+}
+
+/// A location together with the settings that apply to it
+#[derive(Debug, Clone)]
+pub struct ResolvedLocation {
+    pub location: Location,
+    pub settings: LocationSettings,
+}
+
+impl ResolvedLocation {
+    /// A preset's location with its settings
+    pub fn from_preset(cfg: &Config, preset: &LocationPreset, api_key: &str) -> Self {
+        ResolvedLocation {
+            location: Location {
+                lat: preset.lat,
+                lon: preset.lon,
+                label: preset.label.clone(),
+            },
+            settings: LocationSettings::resolve(cfg, Some(preset), api_key),
+        }
+    }
+
+    /// Cache key for this location's weather
+    pub fn cache_key(&self) -> String {
+        cache_key(&self.location, self.settings.units)
+    }
+}
+
+/// Cache key for weather at a location; data is fetched in a specific unit
+/// system, so the units are part of the key
+pub fn cache_key(loc: &Location, units: Units) -> String {
+    format!("{:.3}_{:.3}_{}", loc.lat, loc.lon, units.as_str())
+}
+
 /// Weather condition description from API
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct WeatherDesc {
@@ -60,6 +117,7 @@ pub struct Current {
     pub visibility: Option<u32>,
     pub wind_speed: Option<f64>,
     pub wind_deg: Option<i64>,
+    pub wind_gust: Option<f64>,
     pub sunrise: Option<i64>,
     pub sunset: Option<i64>,
     pub weather: Vec<WeatherDesc>,
@@ -80,6 +138,7 @@ pub struct Hourly {
     pub pop: Option<f64>,
     pub wind_speed: Option<f64>,
     pub wind_deg: Option<i64>,
+    pub wind_gust: Option<f64>,
     pub weather: Vec<WeatherDesc>,
 }
 
@@ -107,6 +166,7 @@ pub struct Daily {
 
 /// OpenWeatherMap API response structure
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct ApiResponse {
     pub timezone_offset: i64,
     pub current: Current,
@@ -114,95 +174,393 @@ pub struct ApiResponse {
     pub daily: Vec<Daily>,
 }
 
+impl ApiResponse {
+    /// Parses a One Call 3.0 response body, or the `data` of a cache file
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| anyhow!("Invalid weather data: {}", e))
+    }
+}
+
+/// Weather data together with when it was fetched
+#[derive(Debug, Clone)]
+pub struct FetchedWeather {
+    pub data: ApiResponse,
+    /// Unix time the data was fetched from the provider
+    pub fetched_at: i64,
+    /// True when the fetch failed and older cached data is being shown
+    pub stale: bool,
+}
+
+impl FetchedWeather {
+    /// Seconds since the data was fetched
+    pub fn age_secs(&self) -> i64 {
+        (Utc::now().timestamp() - self.fetched_at).max(0)
+    }
+
+    /// True when the last fetch failed or the data is older than `ttl_secs`,
+    /// e.g. because the machine was asleep
+    pub fn is_outdated(&self, ttl_secs: i64) -> bool {
+        self.stale || self.age_secs() > ttl_secs
+    }
+}
+
 /// Cached weather data with timestamp
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct CacheFile {
     fetched_at: i64,
+    /// Files written before this was recorded all came from OpenWeatherMap
+    #[serde(default = "default_provider")]
+    provider: String,
     data: ApiResponse,
 }
 
+fn default_provider() -> String {
+    PROVIDER.to_string()
+}
+
+/// A weather file in the cache directory, as `redweather cache` lists it
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    /// Size on disk in bytes
+    pub size: u64,
+    /// When the data was fetched; `None` if the file cannot be read
+    pub fetched_at: Option<i64>,
+    pub provider: Option<String>,
+}
+
 /// Generates cache file path for a given cache key
-fn cache_path(cache_key: &str) -> PathBuf {
-    // Check for test override first, then HOME, then current directory
-    let home = env::var("REDWEATHER_CACHE_DIR")
-        .or_else(|_| env::var("HOME"))
-        .unwrap_or_else(|_| ".".to_string());
+pub fn cache_path(cache_key: &str) -> PathBuf {
+    // Fall back to the current directory if no cache directory can be determined
+    let dir = paths::cache_dir().unwrap_or_else(|| PathBuf::from("."));
 
     let safe_key = cache_key
         .chars()
         .map(|c| if c.is_alphanumeric() { c } else { '_' })
         .collect::<String>();
-    PathBuf::from(home).join(format!("{}/cache_{}.json", CACHE_FILE, safe_key))
+    dir.join(format!("cache_{}.json", safe_key))
 }
 
-/// Loads cached weather data if it exists and is fresh
-pub fn load_cache(cache_key: &str) -> Option<ApiResponse> {
-    let path = cache_path(cache_key);
-    let contents = match fs::read_to_string(&path) {
+/// Reads a cache file, logging (and ignoring) unreadable or corrupt files
+fn read_cache(path: &Path) -> Option<CacheFile> {
+    let contents = match fs::read_to_string(path) {
         Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error reading cache file {}: {}", path.display(), e);
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            debug!("No cache file {}", path.display());
             return None;
         }
-    };
-    let cached: CacheFile = match serde_json::from_str(&contents) {
-        Ok(c) => c,
         Err(e) => {
-            eprintln!("Error parsing cache file {}: {}", path.display(), e);
+            warn!("Error reading cache file {}: {}", path.display(), e);
             return None;
         }
     };
+    match serde_json::from_str(&contents) {
+        Ok(c) => Some(c),
+        Err(e) => {
+            warn!("Error parsing cache file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Loads cached weather data if it is at most `ttl_secs` old
+pub fn load_cache(cache_key: &str, ttl_secs: i64) -> Option<FetchedWeather> {
+    let cached = read_cache(&cache_path(cache_key))?;
     let age = Utc::now().timestamp() - cached.fetched_at;
-    if age <= CACHE_MAX_AGE_SECS {
-        Some(cached.data)
+    if age <= ttl_secs {
+        info!("Cache hit for {} ({}s old)", cache_key, age);
+        Some(FetchedWeather {
+            data: cached.data,
+            fetched_at: cached.fetched_at,
+            stale: false,
+        })
     } else {
+        debug!("Cache for {} is {}s old; fetching", cache_key, age);
         None
     }
 }
 
-/// Loads cached weather data regardless of age (for error fallback)
-pub fn load_stale_cache(cache_key: &str) -> Option<ApiResponse> {
-    let path = cache_path(cache_key);
-    let contents = match fs::read_to_string(&path) {
-        Ok(c) => c,
+/// Returns when the cached data for a location was fetched, without logging
+/// a missing or unreadable file
+pub fn cached_at(cache_key: &str) -> Option<i64> {
+    let contents = fs::read_to_string(cache_path(cache_key)).ok()?;
+    serde_json::from_str::<CacheFile>(&contents)
+        .ok()
+        .map(|c| c.fetched_at)
+}
+
+/// Lock file serializing fetches of one location across processes
+fn lock_path(cache_file: &Path) -> PathBuf {
+    cache_file.with_extension("lock")
+}
+
+/// An advisory lock on a location's lock file, released when dropped
+struct FetchLock {
+    _file: fs::File,
+}
+
+/// Waits for the fetch lock of the location cached in `cache_file`
+///
+/// Returns `None` if the lock file cannot be opened or another process holds
+/// the lock for longer than `FETCH_LOCK_TIMEOUT`; the caller then fetches
+/// without it, as before locking existed.
+async fn lock_fetch(cache_file: &Path) -> Option<FetchLock> {
+    let path = lock_path(cache_file);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let file = match fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+    {
+        Ok(f) => f,
         Err(e) => {
-            eprintln!("Error reading stale cache file {}: {}", path.display(), e);
+            warn!("Cannot open lock file {}: {}", path.display(), e);
             return None;
         }
     };
-    let cached: CacheFile = match serde_json::from_str(&contents) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error parsing stale cache file {}: {}", path.display(), e);
-            return None;
+    let started = Instant::now();
+    loop {
+        match file.try_lock_exclusive() {
+            Ok(true) => {
+                if started.elapsed() >= FETCH_LOCK_POLL {
+                    debug!(
+                        "Waited {} ms for another fetch of {}",
+                        started.elapsed().as_millis(),
+                        cache_file.display()
+                    );
+                }
+                return Some(FetchLock { _file: file });
+            }
+            Ok(false) if started.elapsed() < FETCH_LOCK_TIMEOUT => {
+                tokio::time::sleep(FETCH_LOCK_POLL).await;
+            }
+            Ok(false) => {
+                warn!(
+                    "Another process has been fetching {} for {}s; fetching anyway",
+                    cache_file.display(),
+                    FETCH_LOCK_TIMEOUT.as_secs()
+                );
+                return None;
+            }
+            Err(e) => {
+                warn!("Cannot lock {}: {}", path.display(), e);
+                return None;
+            }
         }
-    };
-    Some(cached.data)
+    }
+}
+
+/// Loads cached weather data of any age up to `max_stale_secs`, to show
+/// while a fetch is under way
+///
+/// No fetch has failed yet, so `stale` is false; see
+/// [`FetchedWeather::is_outdated`].
+pub fn load_stale_cache(cache_key: &str, max_stale_secs: i64) -> Option<FetchedWeather> {
+    read_cache(&cache_path(cache_key))
+        .filter(|c| Utc::now().timestamp() - c.fetched_at <= max_stale_secs)
+        .map(|c| FetchedWeather {
+            data: c.data,
+            fetched_at: c.fetched_at,
+            stale: false,
+        })
 }
 
 /// Saves weather data to cache
+///
+/// The file is written under a temporary name and renamed into place, so
+/// readers in other processes see the old file or the new one, never half
+/// of either.
 pub fn save_cache(cache_key: &str, data: &ApiResponse) {
-    let path = cache_path(cache_key);
+    write_cache(&cache_path(cache_key), data);
+}
+
+fn write_cache(path: &Path, data: &ApiResponse) {
+    static TEMP_COUNTER: AtomicU32 = AtomicU32::new(0);
+
     if let Some(parent) = path.parent() {
         if let Err(e) = fs::create_dir_all(parent) {
-            eprintln!("Error creating cache directory {}: {}", parent.display(), e);
+            warn!("Error creating cache directory {}: {}", parent.display(), e);
             return;
         }
     }
     let cache = CacheFile {
         fetched_at: Utc::now().timestamp(),
+        provider: default_provider(),
         data: data.clone(),
     };
     match serde_json::to_string(&cache) {
         Ok(json) => {
-            if let Err(e) = fs::write(&path, json) {
-                eprintln!("Error writing cache file {}: {}", path.display(), e);
+            let temp = path.with_extension(format!(
+                "{}.{}.tmp",
+                std::process::id(),
+                TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            if let Err(e) = fs::write(&temp, json).and_then(|_| fs::rename(&temp, path)) {
+                warn!("Error writing cache file {}: {}", path.display(), e);
+                let _ = fs::remove_file(&temp);
             }
         }
         Err(e) => {
-            eprintln!("Error serializing cache data for {}: {}", path.display(), e);
+            warn!("Error serializing cache data for {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Lists the weather files in the cache directory, sorted by name
+pub fn cache_entries() -> Result<Vec<CacheEntry>> {
+    let dir = match paths::cache_dir() {
+        Some(d) => d,
+        None => return Ok(Vec::new()),
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(anyhow!("Failed to read {}: {}", dir.display(), e)),
+    };
+    let mut list: Vec<CacheEntry> = entries
+        .flatten()
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            name.starts_with("cache_") && name.ends_with(".json")
+        })
+        .map(|e| {
+            let path = e.path();
+            let cached = fs::read_to_string(&path)
+                .ok()
+                .and_then(|c| serde_json::from_str::<CacheFile>(&c).ok());
+            CacheEntry {
+                size: e.metadata().map(|m| m.len()).unwrap_or(0),
+                fetched_at: cached.as_ref().map(|c| c.fetched_at),
+                provider: cached.map(|c| c.provider),
+                path,
+            }
+        })
+        .collect();
+    list.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(list)
+}
+
+/// Deletes every cached weather file, returning how many were removed
+///
+/// Temporary files left by an interrupted write go too. Lock files stay, as
+/// another process may be holding one.
+pub fn clear_cache() -> Result<usize> {
+    let dir = match paths::cache_dir() {
+        Some(d) => d,
+        None => return Ok(0),
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(anyhow!("Failed to read {}: {}", dir.display(), e)),
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("cache_") && (name.ends_with(".json") || name.ends_with(".tmp")) {
+            fs::remove_file(entry.path())
+                .map_err(|e| anyhow!("Failed to remove {}: {}", entry.path().display(), e))?;
+            removed += 1;
         }
     }
+    Ok(removed)
+}
+
+/// Returns weather for a resolved location: fresh cache, else the API, else
+/// stale cache
+///
+/// `reload` skips the fresh-cache check. A failed fetch falls back to cached
+/// data up to `cache.max_stale_hours` old; the error is only returned if there
+/// is nothing that recent.
+///
+/// Only one process fetches a location at a time. The others wait for it and
+/// use what it saved, so bars on several monitors make one API call.
+pub async fn load_weather(resolved: &ResolvedLocation, reload: bool) -> Result<FetchedWeather> {
+    let cache_key = resolved.cache_key();
+    let path = cache_path(&cache_key);
+    let CacheConfigResolved {
+        ttl_secs,
+        max_stale_secs,
+    } = resolved.settings.cache;
+    if !reload {
+        if let Some(cached) = load_cache(&cache_key, ttl_secs) {
+            return Ok(cached);
+        }
+    }
+
+    let requested_at = Utc::now().timestamp();
+    let _lock = lock_fetch(&path).await;
+    // Another process may have fetched while we waited
+    if let Some(c) = read_cache(&path) {
+        let age = Utc::now().timestamp() - c.fetched_at;
+        if c.fetched_at >= requested_at || (!reload && age <= ttl_secs) {
+            info!("Using weather for {} fetched by another process", cache_key);
+            return Ok(FetchedWeather {
+                data: c.data,
+                fetched_at: c.fetched_at,
+                stale: false,
+            });
+        }
+    }
+
+    let settings = &resolved.settings;
+    match fetch_weather_for_loc(&settings.api_key, &resolved.location, settings.units).await {
+        Ok(d) => {
+            write_cache(&path, &d);
+            Ok(FetchedWeather {
+                data: d,
+                fetched_at: Utc::now().timestamp(),
+                stale: false,
+            })
+        }
+        Err(e) => fall_back_to_cache(
+            &cache_key,
+            read_cache(&path),
+            e,
+            max_stale_secs,
+            Utc::now().timestamp(),
+        ),
+    }
+}
+
+/// After a failed fetch, the cached data if it is at most `max_stale_secs`
+/// old, marked stale; otherwise the fetch error
+fn fall_back_to_cache(
+    cache_key: &str,
+    cached: Option<CacheFile>,
+    error: anyhow::Error,
+    max_stale_secs: i64,
+    now: i64,
+) -> Result<FetchedWeather> {
+    let Some(c) = cached else {
+        return Err(error);
+    };
+    let age = now - c.fetched_at;
+    if age <= max_stale_secs {
+        warn!(
+            "Using {}s old cache for {} due to error: {}",
+            age, cache_key, error
+        );
+        return Ok(FetchedWeather {
+            data: c.data,
+            fetched_at: c.fetched_at,
+            stale: true,
+        });
+    }
+    warn!(
+        "Cache for {} is {}s old, past the {}s limit; not using it",
+        cache_key, age, max_stale_secs
+    );
+    Err(anyhow!(
+        "{} (the last data, from {} ago, is too old to show)",
+        error,
+        format_age(age)
+    ))
 }
 
 /// Fetches weather data for a specific location with retry logic
@@ -220,81 +578,183 @@ pub async fn fetch_weather_for_loc(key: &str, loc: &Location, units: Units) -> R
         .append_pair("units", units_str)
         .append_pair("exclude", "minutely,alerts");
 
+    debug!(
+        "Fetching {:.3},{:.3} ({}) with key {}",
+        loc.lat,
+        loc.lon,
+        units_str,
+        redact_key(key)
+    );
+
     // Retry logic with exponential backoff
     let mut last_error = None;
     for attempt in 0..MAX_RETRIES {
-        match HTTP_CLIENT.get(url.clone()).send().await {
+        let started = Instant::now();
+        let error = match HTTP_CLIENT.get(url.clone()).send().await {
             Ok(resp) => match resp.error_for_status() {
                 Ok(r) => match r.json::<ApiResponse>().await {
-                    Ok(parsed) => return Ok(parsed),
-                    Err(e) => last_error = Some(anyhow!("Failed to parse JSON: {}", e)),
+                    Ok(parsed) => {
+                        info!(
+                            "Fetched weather for {} in {} ms",
+                            loc.label,
+                            started.elapsed().as_millis()
+                        );
+                        return Ok(parsed);
+                    }
+                    Err(e) => anyhow!("Failed to parse JSON: {}", without_key(e)),
                 },
-                Err(e) => last_error = Some(anyhow!("API returned error status: {}", e)),
+                Err(e) => anyhow!("API returned error status: {}", without_key(e)),
             },
-            Err(e) => last_error = Some(anyhow!("Request failed: {}", e)),
-        }
+            Err(e) => anyhow!("Request failed: {}", without_key(e)),
+        };
 
         // Exponential backoff before retry
         if attempt < MAX_RETRIES - 1 {
             let delay = RETRY_BASE_DELAY_MS * 2_u64.pow(attempt);
+            info!(
+                "Attempt {} of {} failed after {} ms: {}; retrying in {} ms",
+                attempt + 1,
+                MAX_RETRIES,
+                started.elapsed().as_millis(),
+                error,
+                delay
+            );
             tokio::time::sleep(StdDuration::from_millis(delay)).await;
         }
+        last_error = Some(error);
     }
 
     Err(last_error
         .unwrap_or_else(|| anyhow!("Weather fetch failed after {} attempts", MAX_RETRIES)))
 }
 
+/// Makes a single One Call request to see whether the provider accepts a key
+///
+/// Only the current conditions are requested and nothing is retried. Returns
+/// the HTTP status and the provider's message, if any; only a failed
+/// connection is an error.
+pub async fn check_api_key(key: &str, loc: &Location) -> Result<(StatusCode, Option<String>)> {
+    let mut url = Url::parse("https://api.openweathermap.org/data/3.0/onecall")?;
+    url.query_pairs_mut()
+        .append_pair("lat", &loc.lat.to_string())
+        .append_pair("lon", &loc.lon.to_string())
+        .append_pair("appid", key)
+        .append_pair("exclude", "minutely,hourly,daily,alerts");
+    let resp = HTTP_CLIENT
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| anyhow!("Request failed: {}", without_key(e)))?;
+    let status = resp.status();
+    let message = resp
+        .json::<serde_json::Value>()
+        .await
+        .ok()
+        .and_then(|v| v.get("message")?.as_str().map(str::to_string));
+    Ok((status, message))
+}
+
 /// Resolves a location from command-line overrides or configured presets
+///
+/// The result carries the effective settings for that location: the preset's
+/// overrides layered over the global config. A one-off ZIP or city argument
+/// uses the global settings.
 pub async fn resolve_location(
     key: &str,
     zip: Option<&str>,
     cfg: &Config,
-) -> Result<Option<Location>> {
+) -> Result<Option<ResolvedLocation>> {
     // Priority 1: Command line ZIP argument (one-time override)
     if let Some(z) = zip {
-        // Try ZIP geocoding first
-        match geocode_zip_with_retry(key, z).await {
-            Ok(Some(loc)) => return Ok(Some(loc)),
-            Err(e) => {
-                // If it wasn't a 404/not found logic error but a network/API error, maybe log it?
-                // For now, we fall through to try direct geocoding, but we might want to surface this error if direct also fails.
-                eprintln!("ZIP geocoding warning: {}", e);
-            }
-            Ok(None) => {} // Just not found as ZIP, try direct
-        }
-
-        // Try direct name geocoding
-        match geocode_direct_with_retry(key, z).await {
-            Ok(Some(loc)) => return Ok(Some(loc)),
-            Err(e) => return Err(e), // Return the error if direct geocoding failed technically
-            Ok(None) => return Ok(None), // Both methods returned None (Not Found)
-        }
+        return Ok(geocode_query(key, z)
+            .await?
+            .map(|location| ResolvedLocation {
+                location,
+                settings: LocationSettings::resolve(cfg, None, key),
+            }));
     }
 
     // Priority 2: Location presets from config
-    if let Some(presets) = cfg.location_presets.as_ref() {
-        if let Some(active) = cfg.active_preset.as_ref() {
-            if let Some(preset) = presets.iter().find(|p| &p.name == active) {
-                return Ok(Some(Location {
-                    lat: preset.lat,
-                    lon: preset.lon,
-                    label: preset.label.clone(),
-                }));
-            }
-        }
+    let presets = cfg.location_presets.as_deref().unwrap_or(&[]);
+    let preset = cfg
+        .active_preset
+        .as_ref()
+        .and_then(|active| presets.iter().find(|p| &p.name == active))
         // Use first preset if active not specified
-        if let Some(first) = presets.first() {
-            return Ok(Some(Location {
-                lat: first.lat,
-                lon: first.lon,
-                label: first.label.clone(),
-            }));
+        .or_else(|| presets.first());
+
+    // No location configured
+    Ok(preset.map(|p| ResolvedLocation::from_preset(cfg, p, key)))
+}
+
+/// One of the locations shown side by side in the bar
+#[derive(Debug, Clone)]
+pub struct BarLocation {
+    /// The preset's icon, else its name
+    pub place: String,
+    pub resolved: ResolvedLocation,
+}
+
+/// Resolves the presets listed in `bar_presets`, skipping unknown names
+///
+/// Returns an empty list when `bar_presets` is not set.
+pub fn resolve_bar_locations(cfg: &Config, key: &str) -> Vec<BarLocation> {
+    let presets = cfg.location_presets.as_deref().unwrap_or(&[]);
+    cfg.bar_presets
+        .iter()
+        .flatten()
+        .filter_map(|name| {
+            let preset = presets.iter().find(|p| &p.name == name);
+            if preset.is_none() {
+                warn!("bar_presets: no preset named \"{}\"", name);
+            }
+            preset
+        })
+        .map(|p| BarLocation {
+            place: p.icon.clone().unwrap_or_else(|| p.name.clone()),
+            resolved: ResolvedLocation::from_preset(cfg, p, key),
+        })
+        .collect()
+}
+
+/// Loads the weather for several locations concurrently, in the given order
+pub async fn load_weather_all(
+    locations: &[BarLocation],
+    reload: bool,
+) -> Vec<Result<FetchedWeather>> {
+    let tasks: Vec<_> = locations
+        .iter()
+        .map(|l| {
+            let resolved = l.resolved.clone();
+            tokio::spawn(async move { load_weather(&resolved, reload).await })
+        })
+        .collect();
+    let mut results = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        results.push(
+            task.await
+                .unwrap_or_else(|e| Err(anyhow!("Weather task failed: {}", e))),
+        );
+    }
+    results
+}
+
+/// Geocodes a ZIP code or place name, trying it as a ZIP code first
+///
+/// Returns `Ok(None)` if neither lookup finds anything.
+pub async fn geocode_query(key: &str, query: &str) -> Result<Option<Location>> {
+    // Try ZIP geocoding first
+    match geocode_zip_with_retry(key, query).await {
+        Ok(Some(loc)) => return Ok(Some(loc)),
+        Err(e) => {
+            // Fall through to direct geocoding, whose error is the one returned
+            warn!("ZIP geocoding of \"{}\" failed: {}", query, e);
         }
+        Ok(None) => {} // Just not found as ZIP, try direct
     }
 
-    // No location configured
-    Ok(None)
+    // Try direct name geocoding; its error is returned if it failed technically
+    geocode_direct_with_retry(key, query).await
 }
 
 /// Geocodes a ZIP code with retry logic
@@ -303,7 +763,10 @@ async fn geocode_zip_with_retry(key: &str, zip: &str) -> Result<Option<Location>
     for attempt in 0..MAX_RETRIES {
         match geocode_zip(key, zip).await {
             Ok(opt) => return Ok(opt),
-            Err(e) => last_error = Some(e),
+            Err(e) => {
+                debug!("Geocoding attempt {} failed: {}", attempt + 1, e);
+                last_error = Some(e)
+            }
         }
         if attempt < MAX_RETRIES - 1 {
             let delay = RETRY_BASE_DELAY_MS * 2_u64.pow(attempt);
@@ -319,7 +782,10 @@ async fn geocode_direct_with_retry(key: &str, query: &str) -> Result<Option<Loca
     for attempt in 0..MAX_RETRIES {
         match geocode_direct(key, query).await {
             Ok(opt) => return Ok(opt),
-            Err(e) => last_error = Some(e),
+            Err(e) => {
+                debug!("Geocoding attempt {} failed: {}", attempt + 1, e);
+                last_error = Some(e)
+            }
         }
         if attempt < MAX_RETRIES - 1 {
             let delay = RETRY_BASE_DELAY_MS * 2_u64.pow(attempt);
@@ -341,7 +807,7 @@ pub async fn geocode_zip(key: &str, zip: &str) -> Result<Option<Location>> {
         .append_pair("zip", &zip_param)
         .append_pair("appid", key);
 
-    let resp = HTTP_CLIENT.get(url).send().await?;
+    let resp = HTTP_CLIENT.get(url).send().await.map_err(without_key)?;
     if resp.status().as_u16() == 404 {
         return Ok(None);
     }
@@ -356,7 +822,7 @@ pub async fn geocode_zip(key: &str, zip: &str) -> Result<Option<Location>> {
         name: Option<String>,
         country: Option<String>,
     }
-    let zr: ZipResp = resp.json().await?;
+    let zr: ZipResp = resp.json().await.map_err(without_key)?;
     let label = zr
         .name
         .or_else(|| Some(format!("ZIP {}", zip)))
@@ -383,7 +849,7 @@ pub async fn geocode_direct(key: &str, query: &str) -> Result<Option<Location>>
         .append_pair("limit", "1")
         .append_pair("appid", key);
 
-    let resp = HTTP_CLIENT.get(url).send().await?;
+    let resp = HTTP_CLIENT.get(url).send().await.map_err(without_key)?;
     if !resp.status().is_success() {
         return Err(anyhow!("API error: {}", resp.status()));
     }
@@ -396,7 +862,7 @@ pub async fn geocode_direct(key: &str, query: &str) -> Result<Option<Location>>
         country: Option<String>,
         state: Option<String>,
     }
-    let list: Vec<DirResp> = resp.json().await?;
+    let list: Vec<DirResp> = resp.json().await.map_err(without_key)?;
     if list.is_empty() {
         return Ok(None);
     }
@@ -428,6 +894,46 @@ mod tests {
         assert!(path.to_string_lossy().contains("cache_37_545__97_268"));
     }
 
+    #[tokio::test]
+    async fn test_resolve_location_uses_preset_settings() {
+        let config: Config = toml::from_str(
+            r#"
+active_preset = "berlin"
+
+[[location_presets]]
+name = "home"
+lat = 40.7
+lon = -74.0
+label = "Home"
+
+[[location_presets]]
+name = "berlin"
+lat = 52.52
+lon = 13.405
+label = "Berlin"
+units = "metric"
+"#,
+        )
+        .unwrap();
+
+        let resolved = resolve_location("key", None, &config)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolved.location.label, "Berlin");
+        assert_eq!(resolved.settings.units, Units::Metric);
+        assert_eq!(resolved.cache_key(), "52.520_13.405_metric");
+
+        let mut config = config;
+        config.active_preset = None;
+        let resolved = resolve_location("key", None, &config)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(resolved.location.label, "Home");
+        assert_eq!(resolved.settings.units, Units::Imperial);
+    }
+
     #[test]
     fn test_api_response_parsing() {
         let json = r#"{
@@ -469,7 +975,8 @@ mod tests {
             ]
         }"#;
 
-        let resp: ApiResponse = serde_json::from_str(json).expect("Failed to parse valid JSON");
+        let resp = ApiResponse::from_json(json).expect("Failed to parse valid JSON");
+        assert!(ApiResponse::from_json("{}").is_err());
 
         assert_eq!(resp.timezone_offset, -18000);
         assert_eq!(resp.current.temp, 72.5);
@@ -480,16 +987,13 @@ mod tests {
         assert_eq!(resp.daily[0].temp.max, Some(75.0));
     }
 
-    #[test]
-    fn test_cache_lifecycle() {
-        // Create a temporary directory for cache testing
+    #[tokio::test]
+    async fn test_cache_lifecycle() {
+        // A scratch directory, passed explicitly: tests run in parallel and
+        // share the process environment
         let temp_dir = env::temp_dir().join("redweather_test_cache");
-        fs::create_dir_all(&temp_dir).unwrap();
-
-        // Override cache directory using our new env var
-        env::set_var("REDWEATHER_CACHE_DIR", &temp_dir);
-
-        let cache_key = "test_loc_123";
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.join("cache_test_loc_123.json");
 
         // Create dummy data
         let dummy_data = ApiResponse {
@@ -504,6 +1008,7 @@ mod tests {
                 visibility: None,
                 wind_speed: None,
                 wind_deg: None,
+                wind_gust: None,
                 sunrise: None,
                 sunset: None,
                 weather: vec![],
@@ -515,23 +1020,68 @@ mod tests {
         };
 
         // Test Save
-        save_cache(cache_key, &dummy_data);
+        write_cache(&path, &dummy_data);
+        assert!(path.exists(), "Cache file was not created at {:?}", path);
+
+        // Test Load
+        let loaded = read_cache(&path);
+        assert!(loaded.is_some(), "Failed to load cached data");
+        assert_eq!(loaded.unwrap().data.current.temp, 20.0);
 
-        // Verify file exists
-        let expected_path = temp_dir.join(format!("{}/cache_{}.json", CACHE_FILE, cache_key));
+        // Writes go through a temporary file that is renamed away
+        let leftovers: Vec<_> = fs::read_dir(&temp_dir)
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
         assert!(
-            expected_path.exists(),
-            "Cache file was not created at {:?}",
-            expected_path
+            leftovers.is_empty(),
+            "Left temporary files: {:?}",
+            leftovers
         );
 
-        // Test Load
-        let loaded = load_cache(cache_key);
-        assert!(loaded.is_some(), "Failed to load cached data");
-        assert_eq!(loaded.unwrap().current.temp, 20.0);
+        // The fetch lock excludes other holders until dropped
+        let lock = lock_fetch(&path).await;
+        assert!(lock.is_some());
+        let other = fs::File::open(lock_path(&path)).unwrap();
+        assert!(!other.try_lock_exclusive().unwrap());
+        drop(lock);
+        assert!(other.try_lock_exclusive().unwrap());
 
         // Cleanup
-        env::remove_var("REDWEATHER_CACHE_DIR");
         let _ = fs::remove_dir_all(temp_dir);
     }
+
+    #[test]
+    fn test_failed_fetch_falls_back_within_max_stale() {
+        let now = 1_700_000_000;
+        let cached = |age: i64| {
+            Some(CacheFile {
+                fetched_at: now - age,
+                provider: default_provider(),
+                data: ApiResponse::from_json(
+                    r#"{"timezone_offset": 0, "current": {"dt": 0, "temp": 20.0, "weather": []},
+                        "hourly": [], "daily": []}"#,
+                )
+                .unwrap(),
+            })
+        };
+        let offline = || anyhow!("Request failed: offline");
+        let day = 24 * 3600;
+
+        let shown = fall_back_to_cache("k", cached(3600), offline(), day, now).unwrap();
+        assert!(shown.stale);
+        assert_eq!(shown.fetched_at, now - 3600);
+
+        // Past the limit the error comes back instead of the data
+        let err = fall_back_to_cache("k", cached(2 * day), offline(), day, now).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Request failed: offline (the last data, from 2 days ago, is too old to show)"
+        );
+        // 0 never shows old data
+        assert!(fall_back_to_cache("k", cached(60), offline(), 0, now).is_err());
+        let err = fall_back_to_cache("k", None, offline(), day, now).unwrap_err();
+        assert_eq!(err.to_string(), "Request failed: offline");
+    }
 }